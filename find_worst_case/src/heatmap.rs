@@ -0,0 +1,317 @@
+// Headless renderings of a sampled grid: one PNG per metric, plus an SVG with the error heatmap, the receivers and
+// their view bounds drawn over it.
+
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use mini_tracker::units::{Mm, Transform};
+use mini_tracker::{Point, Table};
+
+use crate::Sample;
+
+// how far the view bounds are drawn out from each receiver in the SVG
+const VIEW_LINE_LENGTH: f32 = 60.0;
+
+const NO_SOLUTION: [u8; 3] = [0, 0, 0];
+const OUTSIDE: [u8; 3] = [64, 64, 64];
+
+#[derive(Clone, Copy, Debug)]
+pub enum Metric {
+    Area,
+    Error,
+    Correct,
+}
+
+// where the samples land on the image: the table size and the spacing of the sample grid
+#[derive(Clone, Copy, Debug)]
+pub struct Canvas {
+    pub transform: Transform,
+    pub width: Mm,
+    pub height: Mm,
+    pub cell: Mm,
+}
+
+impl Metric {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::Area => "area",
+            Metric::Error => "error",
+            Metric::Correct => "correct",
+        }
+    }
+
+    fn value(&self, sample: &Sample) -> Option<f32> {
+        match self {
+            Metric::Area => sample.area,
+            Metric::Error => sample.error,
+            Metric::Correct => sample.error.map(|_| if sample.correct { 0.0 } else { 1.0 }),
+        }
+    }
+
+    fn max(&self, samples: &[Sample]) -> f32 {
+        samples
+            .iter()
+            .filter_map(|s| self.value(s))
+            .fold(0.0, f32::max)
+    }
+
+    fn colour(&self, sample: &Sample, max: f32) -> [u8; 3] {
+        match self.value(sample) {
+            None => NO_SOLUTION,
+            Some(_) if matches!(self, Metric::Correct) => {
+                if sample.correct {
+                    [0, 160, 0]
+                } else {
+                    [220, 0, 0]
+                }
+            }
+            Some(v) if max > 0.0 => heat_colour(v / max),
+            Some(_) => heat_colour(0.0),
+        }
+    }
+}
+
+// green at 0 through yellow to red at 1
+pub fn heat_colour(t: f32) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0);
+    if t < 0.5 {
+        [(t * 2.0 * 255.0) as u8, 200, 0]
+    } else {
+        [255, ((1.0 - t) * 2.0 * 200.0) as u8, 0]
+    }
+}
+
+// the screen rectangle a sample covers, as (left, top, right, bottom) px
+fn cell_rect(canvas: &Canvas, location: Point) -> (f32, f32, f32, f32) {
+    let half = canvas.cell.0 / 2.0;
+    let top_left = canvas.transform.to_screen(Point {
+        x: location.x - half,
+        y: location.y + half,
+    });
+    let bottom_right = canvas.transform.to_screen(Point {
+        x: location.x + half,
+        y: location.y - half,
+    });
+    (
+        top_left.x.0,
+        top_left.y.0,
+        bottom_right.x.0,
+        bottom_right.y.0,
+    )
+}
+
+fn image_size(canvas: &Canvas) -> (u32, u32) {
+    (
+        canvas.transform.length_to_screen(canvas.width).0.ceil() as u32,
+        canvas.transform.length_to_screen(canvas.height).0.ceil() as u32,
+    )
+}
+
+pub fn render(samples: &[Sample], metric: Metric, canvas: &Canvas) -> (u32, u32, Vec<u8>) {
+    let (width, height) = image_size(canvas);
+    let mut pixels: Vec<u8> = OUTSIDE
+        .iter()
+        .copied()
+        .cycle()
+        .take((width * height * 3) as usize)
+        .collect();
+
+    let max = metric.max(samples);
+    for sample in samples {
+        let colour = metric.colour(sample, max);
+        let (left, top, right, bottom) = cell_rect(canvas, sample.location);
+        let clamp_x = |v: f32| (v.round().max(0.0) as u32).min(width);
+        let clamp_y = |v: f32| (v.round().max(0.0) as u32).min(height);
+
+        for y in clamp_y(top)..clamp_y(bottom) {
+            for x in clamp_x(left)..clamp_x(right) {
+                let i = ((y * width + x) * 3) as usize;
+                pixels[i..i + 3].copy_from_slice(&colour);
+            }
+        }
+    }
+
+    (width, height, pixels)
+}
+
+pub fn write_png(
+    path: &Path,
+    samples: &[Sample],
+    metric: Metric,
+    canvas: &Canvas,
+) -> io::Result<()> {
+    let (width, height, pixels) = render(samples, metric, canvas);
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&pixels).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+pub fn svg(table: &Table, samples: &[Sample], canvas: &Canvas) -> String {
+    let (width, height) = image_size(canvas);
+    let mut out = String::new();
+
+    // writing into a String can't fail
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    );
+    let _ = writeln!(
+        out,
+        r#"<rect width="{width}" height="{height}" fill="rgb({},{},{})"/>"#,
+        OUTSIDE[0], OUTSIDE[1], OUTSIDE[2]
+    );
+
+    let max = Metric::Error.max(samples);
+    for sample in samples {
+        let [r, g, b] = Metric::Error.colour(sample, max);
+        let (left, top, right, bottom) = cell_rect(canvas, sample.location);
+        let stroke = if sample.correct {
+            ""
+        } else {
+            r#" stroke="red" stroke-width="2""#
+        };
+        let _ = writeln!(
+            out,
+            r#"<rect x="{left:.1}" y="{top:.1}" width="{:.1}" height="{:.1}" fill="rgb({r},{g},{b})"{stroke}><title>({:.1}, {:.1}) error {} area {} {}</title></rect>"#,
+            right - left,
+            bottom - top,
+            sample.location.x,
+            sample.location.y,
+            sample
+                .error
+                .map(|e| format!("{e:.2}"))
+                .unwrap_or("-".into()),
+            sample.area.map(|a| format!("{a:.1}")).unwrap_or("-".into()),
+            if sample.correct { "correct" } else { "wrong" },
+        );
+    }
+
+    if let Some(worst) = samples
+        .iter()
+        .filter(|s| s.error.is_some())
+        .max_by(|a, b| a.error.partial_cmp(&b.error).unwrap())
+    {
+        let centre = canvas.transform.to_screen(worst.location);
+        let radius = canvas.transform.length_to_screen(canvas.cell).0;
+        let _ = writeln!(
+            out,
+            r#"<circle cx="{:.1}" cy="{:.1}" r="{radius:.1}" fill="none" stroke="magenta" stroke-width="3"/>"#,
+            centre.x.0, centre.y.0
+        );
+    }
+
+    for receiver in &table.receivers {
+        let location = canvas.transform.to_screen(receiver.location);
+        for bound in [receiver.view_bound1, receiver.view_bound2] {
+            let along = bound.point2.distance(&bound.point1);
+            let end = canvas.transform.to_screen(Point {
+                x: bound.point1.x + (bound.point2.x - bound.point1.x) * VIEW_LINE_LENGTH / along,
+                y: bound.point1.y + (bound.point2.y - bound.point1.y) * VIEW_LINE_LENGTH / along,
+            });
+            let _ = writeln!(
+                out,
+                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="white" stroke-opacity="0.5"/>"#,
+                location.x.0, location.y.0, end.x.0, end.y.0
+            );
+        }
+        let _ = writeln!(
+            out,
+            r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="blue"><title>({:.1}, {:.1}) {:?} {:.0} deg</title></circle>"#,
+            location.x.0,
+            location.y.0,
+            receiver.location.x,
+            receiver.location.y,
+            receiver.facing,
+            receiver.view_angle.0
+        );
+    }
+
+    out.push_str("</svg>\n");
+    out
+}
+
+pub fn write_svg(
+    path: &Path,
+    table: &Table,
+    samples: &[Sample],
+    canvas: &Canvas,
+) -> io::Result<()> {
+    fs::write(path, svg(table, samples, canvas))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn samples() -> Vec<Sample> {
+        vec![
+            Sample {
+                location: Point { x: 15.0, y: 15.0 },
+                area: Some(100.0),
+                error: Some(5.0),
+                correct: true,
+            },
+            Sample {
+                location: Point { x: 35.0, y: 15.0 },
+                area: Some(400.0),
+                error: Some(20.0),
+                correct: false,
+            },
+            Sample {
+                location: Point { x: 15.0, y: 35.0 },
+                area: None,
+                error: None,
+                correct: false,
+            },
+        ]
+    }
+
+    const CANVAS: Canvas = Canvas {
+        transform: Transform::new(2.0, Mm(50.0)),
+        width: Mm(60.0),
+        height: Mm(50.0),
+        cell: Mm(20.0),
+    };
+
+    #[test]
+    fn colours() {
+        assert_eq!(heat_colour(0.0), [0, 200, 0]);
+        assert_eq!(heat_colour(1.0), [255, 0, 0]);
+        assert_eq!(heat_colour(2.0), [255, 0, 0]);
+    }
+
+    #[test]
+    fn render_cells() {
+        let (width, height, pixels) = render(&samples(), Metric::Error, &CANVAS);
+        assert_eq!((width, height), (120, 100));
+        let pixel = |x: u32, y: u32| {
+            let i = ((y * width + x) * 3) as usize;
+            [pixels[i], pixels[i + 1], pixels[i + 2]]
+        };
+
+        // y is flipped, the bottom row of samples is at the bottom of the image
+        assert_eq!(pixel(30, 70), heat_colour(0.25));
+        assert_eq!(pixel(70, 70), heat_colour(1.0));
+        assert_eq!(pixel(30, 30), NO_SOLUTION);
+        assert_eq!(pixel(110, 5), OUTSIDE);
+
+        let (_, _, pixels) = render(&samples(), Metric::Correct, &CANVAS);
+        assert_eq!(pixels[((70 * width + 70) * 3) as usize], 220);
+    }
+
+    #[test]
+    fn svg_overlay() {
+        let svg = svg(&Table::new(Mm(60.0), Mm(50.0), vec![]), &samples(), &CANVAS);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), 4);
+        assert_eq!(svg.matches(r#"stroke="red""#).count(), 2);
+        assert!(svg.contains("error 20.00"));
+    }
+}
@@ -1,10 +1,18 @@
+use std::io;
+use std::path::PathBuf;
+
+use clap::Parser;
+use mini_tracker::units::{Degrees, Mm, Transform, BASE_DIAMETER, MM_PER_INCH, STANDOFF_DISTANCE};
 use mini_tracker::{self, Point, Receiver, Table};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
-// mod receiver_placements;
+use heatmap::{Canvas, Metric};
+use search::{Bounds, SearchOptions};
 
-// 1 px == 1 mm
-// 25mm == ~1 in
-const MM_PER_INCH: f32 = 25.4;
+mod heatmap;
+mod search;
+// mod receiver_placements;
 
 // grid is
 // |
@@ -12,16 +20,14 @@ const MM_PER_INCH: f32 = 25.4;
 // |
 // .--------
 
-// 2 in min distance from mini to edge
-const STANDOFF_DISTANCE: f32 = 2.0 * MM_PER_INCH;
-const TABLE_WIDTH: f32 = 930.0 + STANDOFF_DISTANCE;
-const TABLE_HEIGHT: f32 = 523.0 + STANDOFF_DISTANCE;
+const TABLE_WIDTH: Mm = Mm(930.0 + STANDOFF_DISTANCE.0);
+const TABLE_HEIGHT: Mm = Mm(523.0 + STANDOFF_DISTANCE.0);
 
 const GRID_SIZE: f32 = MM_PER_INCH;
 
 fn get_mini_edge_points(mini_center: Point) -> [Point; 360] {
     let mut points = [Point { x: 0.0, y: 0.0 }; 360];
-    let distance = MM_PER_INCH / 2.0;
+    let distance = BASE_DIAMETER.0 / 2.0;
     for (i, point) in points.iter_mut().enumerate() {
         let angle = i as f32;
         point.x = mini_center.x + distance * angle.to_radians().cos();
@@ -30,10 +36,10 @@ fn get_mini_edge_points(mini_center: Point) -> [Point; 360] {
     points
 }
 
-fn place_vertical_receivers(view_angle: f32, receivers_per_mm: f32) -> Vec<Receiver> {
+fn place_vertical_receivers(view_angle: Degrees, receivers_per_mm: f32) -> Vec<Receiver> {
     let mut receivers = Vec::new();
     let mut y = MM_PER_INCH / 2.0;
-    while y < TABLE_HEIGHT {
+    while y < TABLE_HEIGHT.0 {
         receivers.push(Receiver::new(
             TABLE_WIDTH,
             TABLE_HEIGHT,
@@ -45,7 +51,10 @@ fn place_vertical_receivers(view_angle: f32, receivers_per_mm: f32) -> Vec<Recei
             TABLE_WIDTH,
             TABLE_HEIGHT,
             view_angle,
-            Point { x: TABLE_WIDTH, y },
+            Point {
+                x: TABLE_WIDTH.0,
+                y,
+            },
             mini_tracker::Direction::Left,
         ));
 
@@ -55,10 +64,10 @@ fn place_vertical_receivers(view_angle: f32, receivers_per_mm: f32) -> Vec<Recei
     receivers
 }
 
-fn place_horizontal_receivers(view_angle: f32, receivers_per_mm: f32) -> Vec<Receiver> {
+fn place_horizontal_receivers(view_angle: Degrees, receivers_per_mm: f32) -> Vec<Receiver> {
     let mut receivers = Vec::new();
     let mut x = MM_PER_INCH / 2.0;
-    while x < TABLE_WIDTH {
+    while x < TABLE_WIDTH.0 {
         receivers.push(Receiver::new(
             TABLE_WIDTH,
             TABLE_HEIGHT,
@@ -70,7 +79,10 @@ fn place_horizontal_receivers(view_angle: f32, receivers_per_mm: f32) -> Vec<Rec
             TABLE_WIDTH,
             TABLE_HEIGHT,
             view_angle,
-            Point { x, y: TABLE_HEIGHT },
+            Point {
+                x,
+                y: TABLE_HEIGHT.0,
+            },
             mini_tracker::Direction::Down,
         ));
 
@@ -88,125 +100,248 @@ struct TestResult {
     avg_error: f32,
     max_area: f32,
     max_error: f32,
+    // where the largest area and error were
+    max_area_at: Point,
+    max_error_at: Point,
 }
 
-fn run_test(
+// what the table worked out for a mini at one grid location. area and error are None when no receiver bounds could be
+// found at all.
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    pub location: Point,
+    pub area: Option<f32>,
+    pub error: Option<f32>,
+    pub correct: bool,
+}
+
+fn build_table(
     vert_density: f32,
     horiz_density: f32,
-    vert_view_angle: f32,
-    horiz_view_angle: f32,
-) -> TestResult {
+    vert_view_angle: Degrees,
+    horiz_view_angle: Degrees,
+) -> Table {
     let mut receivers = place_horizontal_receivers(horiz_view_angle, horiz_density);
     receivers.append(&mut place_vertical_receivers(vert_view_angle, vert_density));
 
-    let total_receivers = receivers.len();
+    Table::new(TABLE_WIDTH, TABLE_HEIGHT, receivers)
+}
+
+// what the table works out for a mini centred at `mini_location`
+pub fn sample_at(table: &Table, mini_location: Point) -> Sample {
+    let mini_edge_points = get_mini_edge_points(mini_location);
+    let visible_receivers: Vec<(Receiver, bool)> = table
+        .receivers
+        .iter()
+        .map(|receiver| {
+            let can_see = mini_edge_points.iter().any(|point| receiver.can_see(point));
+            (*receiver, can_see)
+        })
+        .collect();
+
+    match table.get_bounding_polygon(&visible_receivers[..]) {
+        Some(bounding_polygon) => Sample {
+            location: mini_location,
+            area: Some(bounding_polygon.area()),
+            error: Some(bounding_polygon.centre_error(BASE_DIAMETER)),
+            correct: bounding_polygon.center().distance(&mini_location) < GRID_SIZE,
+        },
+        None => Sample {
+            location: mini_location,
+            area: None,
+            error: None,
+            correct: false,
+        },
+    }
+}
 
-    let table = Table::new(TABLE_WIDTH, TABLE_HEIGHT, receivers);
+// For each valid table location, check if table can determine mini location within error bounds
+fn sample_grid(table: &Table) -> Vec<Sample> {
+    let mut samples = Vec::new();
 
-    // For each valid table location, check if table can determine mini location within error bounds
-    let mut num_correct = 0;
-    let mut tot_locations = 0;
+    let mut x = STANDOFF_DISTANCE.0 + MM_PER_INCH / 2.0;
+    while x < (TABLE_WIDTH - STANDOFF_DISTANCE).0 - MM_PER_INCH / 2.0 {
+        let mut y = STANDOFF_DISTANCE.0 + MM_PER_INCH / 2.0;
+        while y < (TABLE_HEIGHT - STANDOFF_DISTANCE).0 - MM_PER_INCH / 2.0 {
+            samples.push(sample_at(table, Point { x, y }));
+            y += GRID_SIZE;
+        }
+
+        x += GRID_SIZE;
+    }
+
+    samples
+}
+
+fn run_test(table: &Table, samples: &[Sample]) -> TestResult {
     let mut avg_area = 0.0;
     let mut avg_error = 0.0;
     let mut max_error = 0.0;
     let mut max_area = 0.0;
 
-    let mut max_error_mini = Point { x: 0.0, y: 0.0 };
-    let mut max_area_mini = Point { x: 0.0, y: 0.0 };
-
-    let mut x = STANDOFF_DISTANCE + MM_PER_INCH / 2.0;
-    while x < TABLE_WIDTH - STANDOFF_DISTANCE - MM_PER_INCH / 2.0 {
-        let mut y = STANDOFF_DISTANCE + MM_PER_INCH / 2.0;
-        while y < TABLE_HEIGHT - STANDOFF_DISTANCE - MM_PER_INCH / 2.0 {
-            tot_locations += 1;
-            let mini_location = Point { x, y };
-            let mut num_visible_receivers = 0;
-            let mini_edge_points = get_mini_edge_points(mini_location);
-            let mut visible_receivers = Vec::new();
-            for receiver in table.receivers.iter() {
-                let mut can_see = false;
-                for point in &mini_edge_points {
-                    if receiver.can_see(point) {
-                        can_see = true;
-                        break;
-                    }
-                }
-                if can_see {
-                    num_visible_receivers += 1;
-                }
-                visible_receivers.push((*receiver, can_see));
-            }
-
-            assert!(num_visible_receivers > 0);
-
-            // println!("{:?}", mini_location);
-            // dbg!(
-            //     vert_density,
-            //     horiz_density,
-            //     vert_view_angle,
-            //     horiz_view_angle
-            // );
-            let bounding_polygon = table.get_bounding_polygon(&visible_receivers[..]).unwrap();
-            // let Some(shrink_polygon) = bounding_polygon.shrink(25.4 / 2.0) else {
-            //     return TestResult {
-            //         total_receivers,
-            //         all_correct: false,
-            //         avg_area: avg_area / tot_locations as f32,
-            //         avg_error: avg_error / tot_locations as f32,
-            //         max_area,
-            //         max_error,
-            //     };
-            // };
-            let guessed_location = bounding_polygon.center();
-            let error = bounding_polygon.max_width();
-            let area = bounding_polygon.area();
-            avg_area += area;
-            avg_error += error;
-
-            if guessed_location.distance(&mini_location) < GRID_SIZE {
-                num_correct += 1;
-            } else {
-                // dbg!(mini_location, guessed_location);
-                return TestResult {
-                    total_receivers,
-                    all_correct: false,
-                    avg_area: avg_area / tot_locations as f32,
-                    avg_error: avg_error / tot_locations as f32,
-                    max_area,
-                    max_error,
-                };
-            }
-
-            if error > max_error {
-                max_error = error;
-                max_error_mini = mini_location;
-                // dbg!(mini_location);
-            }
-
-            if area > max_area {
-                max_area = area;
-                // dbg!(mini_location);
-                max_area_mini = mini_location;
-            }
-            y += GRID_SIZE;
+    let mut max_error_at = Point { x: 0.0, y: 0.0 };
+    let mut max_area_at = Point { x: 0.0, y: 0.0 };
+
+    for sample in samples {
+        let area = sample.area.unwrap_or(0.0);
+        let error = sample.error.unwrap_or(0.0);
+        avg_area += area;
+        avg_error += error;
+
+        if error > max_error {
+            max_error = error;
+            max_error_at = sample.location;
         }
 
-        x += GRID_SIZE;
+        if area > max_area {
+            max_area = area;
+            max_area_at = sample.location;
+        }
     }
 
-    dbg!(max_error_mini);
-    dbg!(max_area_mini);
-
     TestResult {
-        total_receivers,
-        all_correct: num_correct == tot_locations,
-        avg_area: avg_area / tot_locations as f32,
-        avg_error: avg_error / tot_locations as f32,
+        total_receivers: table.receivers.len(),
+        all_correct: samples.iter().all(|s| s.correct),
+        avg_area: avg_area / samples.len() as f32,
+        avg_error: avg_error / samples.len() as f32,
         max_area,
         max_error,
+        max_area_at,
+        max_error_at,
     }
 }
 
-fn main() {
-    println!("{:?}", run_test(3.5, 3.5, 10.0, 10.0));
+#[derive(Parser)]
+#[command(about = "Find where a receiver layout does worst at locating a mini")]
+struct Cli {
+    /// Receivers per inch on the left and right edges
+    #[arg(long, default_value_t = 3.5)]
+    vert_density: f32,
+    /// Receivers per inch on the top and bottom edges
+    #[arg(long, default_value_t = 3.5)]
+    horiz_density: f32,
+    #[arg(long, default_value_t = 10.0)]
+    vert_view_angle: f32,
+    #[arg(long, default_value_t = 10.0)]
+    horiz_view_angle: f32,
+    /// Also write <PREFIX>_area.png, <PREFIX>_error.png, <PREFIX>_correct.png and <PREFIX>.svg showing every grid
+    /// position
+    #[arg(long, value_name = "PREFIX")]
+    heatmap: Option<PathBuf>,
+    /// Resolution of the heatmap images
+    #[arg(long, default_value_t = 1.0)]
+    px_per_mm: f32,
+    /// Positions to sample at random on top of the grid
+    #[arg(long, default_value_t = 0)]
+    random_samples: usize,
+    /// Hill climb on error from this many of the worst positions sampled
+    #[arg(long, default_value_t = 0)]
+    climbs: usize,
+    /// Smallest step in mm the hill climb takes before it stops
+    #[arg(long, default_value_t = 1.0)]
+    min_step: f32,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// How many of the worst positions to list
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+}
+
+fn main() -> io::Result<()> {
+    let cli = Cli::parse();
+    // the climb halves its step until it's under this, which never happens if it's not positive
+    if cli.min_step <= 0.0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "min step must be positive",
+        ));
+    }
+
+    let table = build_table(
+        cli.vert_density,
+        cli.horiz_density,
+        Degrees(cli.vert_view_angle),
+        Degrees(cli.horiz_view_angle),
+    );
+    let samples = sample_grid(&table);
+    println!("{:?}", run_test(&table, &samples));
+
+    if cli.random_samples > 0 || cli.climbs > 0 {
+        let bounds = Bounds::playable(TABLE_WIDTH, TABLE_HEIGHT, BASE_DIAMETER);
+        let mut rng = StdRng::seed_from_u64(cli.seed);
+
+        let mut seeds = samples.clone();
+        seeds.append(&mut search::random_samples(
+            &table,
+            &bounds,
+            cli.random_samples,
+            &mut rng,
+        ));
+
+        let report = search::adversarial_search(
+            &table,
+            &bounds,
+            &seeds,
+            &SearchOptions {
+                climbs: cli.climbs,
+                step: Mm(GRID_SIZE / 2.0),
+                min_step: Mm(cli.min_step),
+                region_fraction: 0.9,
+            },
+        );
+
+        let mut worst = report.worst;
+        if worst.is_empty() {
+            // no climbs, so the worst of what was sampled is all there is
+            worst = seeds;
+            search::sort_worst_first(&mut worst);
+        }
+
+        println!("{} positions evaluated, worst:", report.evaluated);
+        for sample in worst.iter().take(cli.top) {
+            println!(
+                "  ({:.1}, {:.1}) error {} area {} {}",
+                sample.location.x,
+                sample.location.y,
+                sample
+                    .error
+                    .map(|e| format!("{e:.2}"))
+                    .unwrap_or("-".into()),
+                sample.area.map(|a| format!("{a:.1}")).unwrap_or("-".into()),
+                if sample.correct { "correct" } else { "WRONG" }
+            );
+        }
+        if let Some(region) = report.region {
+            println!(
+                "max error region: x {:.1} to {:.1}, y {:.1} to {:.1} ({} positions within 90% of the max error)",
+                region.min.x, region.max.x, region.min.y, region.max.y, report.region_positions
+            );
+        }
+    }
+
+    if let Some(prefix) = cli.heatmap {
+        let canvas = Canvas {
+            transform: Transform::new(cli.px_per_mm, TABLE_HEIGHT),
+            width: TABLE_WIDTH,
+            height: TABLE_HEIGHT,
+            cell: Mm(GRID_SIZE),
+        };
+        let with_suffix = |suffix: &str| {
+            let mut path = prefix.clone().into_os_string();
+            path.push(suffix);
+            PathBuf::from(path)
+        };
+
+        for metric in [Metric::Area, Metric::Error, Metric::Correct] {
+            heatmap::write_png(
+                &with_suffix(&format!("_{}.png", metric.name())),
+                &samples,
+                metric,
+                &canvas,
+            )?;
+        }
+        heatmap::write_svg(&with_suffix(".svg"), &table, &samples, &canvas)?;
+    }
+
+    Ok(())
 }
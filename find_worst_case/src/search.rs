@@ -0,0 +1,265 @@
+// The grid only looks at one spot per inch, so the worst places for a layout can sit between grid points. Random
+// samples spread the search over the whole playable area, then hill climbing from the worst spots found walks uphill
+// on error until no nearby position is any worse.
+
+use mini_tracker::units::{Mm, STANDOFF_DISTANCE};
+use mini_tracker::{Point, Table};
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::{sample_at, Sample};
+
+// where the centre of a mini can be while the whole base stays clear of the standoff
+#[derive(Clone, Copy, Debug)]
+pub struct Bounds {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Bounds {
+    pub fn playable(width: Mm, height: Mm, footprint: Mm) -> Self {
+        let inset = (STANDOFF_DISTANCE + footprint / 2.0).0;
+        Self {
+            min: Point { x: inset, y: inset },
+            max: Point {
+                x: width.0 - inset,
+                y: height.0 - inset,
+            },
+        }
+    }
+
+    fn clamp(&self, point: Point) -> Point {
+        Point {
+            x: point.x.clamp(self.min.x, self.max.x),
+            y: point.y.clamp(self.min.y, self.max.y),
+        }
+    }
+}
+
+// positions the table can't solve at all, or gets wrong, are worse than any error
+fn badness(sample: &Sample) -> (bool, f32) {
+    match sample.error {
+        Some(error) if sample.correct => (false, error),
+        _ => (true, sample.error.unwrap_or(f32::INFINITY)),
+    }
+}
+
+pub fn worse(a: &Sample, b: &Sample) -> bool {
+    badness(a) > badness(b)
+}
+
+pub fn sort_worst_first(samples: &mut [Sample]) {
+    samples.sort_by(|a, b| badness(b).partial_cmp(&badness(a)).unwrap());
+}
+
+pub fn random_samples(
+    table: &Table,
+    bounds: &Bounds,
+    count: usize,
+    rng: &mut StdRng,
+) -> Vec<Sample> {
+    (0..count)
+        .map(|_| {
+            let location = Point {
+                x: rng.gen_range(bounds.min.x..=bounds.max.x),
+                y: rng.gen_range(bounds.min.y..=bounds.max.y),
+            };
+            sample_at(table, location)
+        })
+        .collect()
+}
+
+// Moves to whichever of the 8 neighbours `step` away is worst, halving the step whenever none of them are worse than
+// where it is, until the step drops under `min_step`. Every position looked at is pushed onto `visited`.
+pub fn hill_climb(
+    table: &Table,
+    bounds: &Bounds,
+    start: Sample,
+    step: Mm,
+    min_step: Mm,
+    visited: &mut Vec<Sample>,
+) -> Sample {
+    let mut current = start;
+    let mut step = step.0;
+
+    while step >= min_step.0 {
+        let mut best = current;
+        for (dx, dy) in [
+            (-1.0, -1.0),
+            (0.0, -1.0),
+            (1.0, -1.0),
+            (-1.0, 0.0),
+            (1.0, 0.0),
+            (-1.0, 1.0),
+            (0.0, 1.0),
+            (1.0, 1.0),
+        ] {
+            let location = bounds.clamp(Point {
+                x: current.location.x + dx * step,
+                y: current.location.y + dy * step,
+            });
+            if location == current.location {
+                continue;
+            }
+
+            let sample = sample_at(table, location);
+            visited.push(sample);
+            if worse(&sample, &best) {
+                best = sample;
+            }
+        }
+
+        if worse(&best, &current) {
+            current = best;
+        } else {
+            step /= 2.0;
+        }
+    }
+
+    current
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchReport {
+    // worst first, one per local maximum
+    pub worst: Vec<Sample>,
+    pub evaluated: usize,
+    // bounding box of every position looked at that came within `region_fraction` of the largest error
+    pub region: Option<Bounds>,
+    pub region_positions: usize,
+}
+
+pub struct SearchOptions {
+    pub climbs: usize,
+    pub step: Mm,
+    pub min_step: Mm,
+    pub region_fraction: f32,
+}
+
+// Climbs from the worst `climbs` of `seeds`. `seeds` would usually be the grid samples plus some random ones.
+pub fn adversarial_search(
+    table: &Table,
+    bounds: &Bounds,
+    seeds: &[Sample],
+    options: &SearchOptions,
+) -> SearchReport {
+    let mut starts = seeds.to_vec();
+    sort_worst_first(&mut starts);
+    starts.truncate(options.climbs);
+
+    let mut visited = seeds.to_vec();
+    let mut worst: Vec<Sample> = Vec::new();
+    for start in starts {
+        let peak = hill_climb(
+            table,
+            bounds,
+            start,
+            options.step,
+            options.min_step,
+            &mut visited,
+        );
+        // climbs that end up on the same peak only need reporting once
+        if !worst
+            .iter()
+            .any(|w| w.location.distance(&peak.location) < options.min_step.0)
+        {
+            worst.push(peak);
+        }
+    }
+    sort_worst_first(&mut worst);
+
+    let max_error = visited
+        .iter()
+        .filter(|s| s.correct)
+        .filter_map(|s| s.error)
+        .fold(0.0, f32::max);
+    let in_region: Vec<Point> = visited
+        .iter()
+        .filter(|s| s.correct && s.error.unwrap_or(0.0) >= max_error * options.region_fraction)
+        .map(|s| s.location)
+        .collect();
+    let region = in_region.iter().fold(None, |region: Option<Bounds>, p| {
+        Some(match region {
+            None => Bounds { min: *p, max: *p },
+            Some(r) => Bounds {
+                min: Point {
+                    x: r.min.x.min(p.x),
+                    y: r.min.y.min(p.y),
+                },
+                max: Point {
+                    x: r.max.x.max(p.x),
+                    y: r.max.y.max(p.y),
+                },
+            },
+        })
+    });
+
+    SearchReport {
+        worst,
+        evaluated: visited.len(),
+        region,
+        region_positions: in_region.len(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mini_tracker::units::{Degrees, BASE_DIAMETER};
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::{build_table, TABLE_HEIGHT, TABLE_WIDTH};
+
+    #[test]
+    fn climbs_uphill() {
+        let table = build_table(0.5, 0.5, Degrees(30.0), Degrees(30.0));
+        let bounds = Bounds::playable(TABLE_WIDTH, TABLE_HEIGHT, BASE_DIAMETER);
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let seeds = random_samples(&table, &bounds, 6, &mut rng);
+        for s in &seeds {
+            assert!(s.location.x >= bounds.min.x && s.location.x <= bounds.max.x);
+            assert!(s.location.y >= bounds.min.y && s.location.y <= bounds.max.y);
+        }
+        let again = random_samples(&table, &bounds, 6, &mut StdRng::seed_from_u64(3));
+        assert_eq!(
+            seeds.iter().map(|s| s.location).collect::<Vec<_>>(),
+            again.iter().map(|s| s.location).collect::<Vec<_>>()
+        );
+
+        let report = adversarial_search(
+            &table,
+            &bounds,
+            &seeds,
+            &SearchOptions {
+                climbs: 2,
+                step: Mm(40.0),
+                min_step: Mm(10.0),
+                region_fraction: 0.9,
+            },
+        );
+
+        assert!(!report.worst.is_empty());
+        assert!(report.evaluated > seeds.len());
+        // nothing the climb started from is worse than where it ended up
+        assert!(seeds.iter().all(|s| !worse(s, &report.worst[0])));
+        for w in &report.worst {
+            assert!(w.location.x >= bounds.min.x && w.location.x <= bounds.max.x);
+            assert!(w.location.y >= bounds.min.y && w.location.y <= bounds.max.y);
+        }
+    }
+
+    #[test]
+    fn failures_are_worst() {
+        let sample = |error, correct| Sample {
+            location: Point { x: 0.0, y: 0.0 },
+            area: error,
+            error,
+            correct,
+        };
+
+        assert!(worse(&sample(Some(20.0), true), &sample(Some(10.0), true)));
+        assert!(worse(&sample(Some(1.0), false), &sample(Some(100.0), true)));
+        assert!(worse(&sample(None, false), &sample(Some(1.0), false)));
+    }
+}
@@ -0,0 +1,342 @@
+// Identifying minis without a sync. Instead of flashing in its slot, each mini sends a short burst carrying its address
+// every so often, at random so two minis that collide once are unlikely to collide again, and the receivers read the
+// address straight out of the burst. Slower to cover every mini than the slots, but a mini that missed the sync is
+// still seen.
+//
+// A burst starts with three chips on and three off, which Manchester coding can never produce, then the address and a
+// CRC-8 of it, most significant bit first, Manchester coded: a 1 is on then off, a 0 is off then on. Bursts that
+// overlap come out as a mess of both, which the coding or the checksum catches, and the decoder drops them.
+
+const START_CHIPS: usize = 3;
+const DATA_BITS: usize = 16;
+
+pub const BURST_CHIPS: usize = 2 * START_CHIPS + 2 * DATA_BITS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BurstError {
+    // two chips that aren't a Manchester bit, usually two bursts on top of each other
+    BadCoding,
+    BadChecksum,
+}
+
+// CRC-8 with polynomial 0x07
+pub fn checksum(address: u8) -> u8 {
+    let mut crc = address;
+    for _ in 0..8 {
+        crc = if crc & 0x80 != 0 {
+            (crc << 1) ^ 0x07
+        } else {
+            crc << 1
+        };
+    }
+    crc
+}
+
+// whether the emitter is on for each chip of the burst
+pub fn encode(address: u8) -> [bool; BURST_CHIPS] {
+    let mut chips = [false; BURST_CHIPS];
+    chips[..START_CHIPS].fill(true);
+
+    let word = (address as u16) << 8 | checksum(address) as u16;
+    for bit in 0..DATA_BITS {
+        let one = word & (1 << (DATA_BITS - 1 - bit)) != 0;
+        let chip = 2 * START_CHIPS + 2 * bit;
+        chips[chip] = one;
+        chips[chip + 1] = !one;
+    }
+    chips
+}
+
+// The mini side, ticked at a fixed rate on the mini's own clock.
+#[derive(Debug, Clone, Copy)]
+pub struct BurstEmitter {
+    chips: [bool; BURST_CHIPS],
+    ticks_per_chip: u32,
+    min_gap: u32,
+    max_gap: u32,
+    rng: u32,
+    // ticks left before the next burst
+    gap: u32,
+    // ticks into the burst being sent
+    position: Option<u32>,
+}
+
+impl BurstEmitter {
+    // waits between min_gap and max_gap ticks, chosen at random, after each burst and before the first. The seed wants
+    // to differ between minis, the address will do if nothing better is around. It's mixed first, addresses are often
+    // one apart.
+    pub fn new(address: u8, ticks_per_chip: u32, min_gap: u32, max_gap: u32, seed: u32) -> Self {
+        assert!(ticks_per_chip > 0, "a chip has to last at least a tick");
+        assert!(min_gap <= max_gap);
+
+        let seed = seed.wrapping_mul(0x9E37_79B9) ^ (seed >> 16);
+        let mut emitter = Self {
+            chips: encode(address),
+            ticks_per_chip,
+            min_gap,
+            max_gap,
+            // xorshift never leaves 0
+            rng: if seed == 0 { 0x2545_F491 } else { seed },
+            gap: 0,
+            position: None,
+        };
+        emitter.gap = emitter.random_gap();
+        emitter
+    }
+
+    // xorshift32
+    fn random_gap(&mut self) -> u32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        // in u64 so the whole range of u32 gaps fits
+        let range = (self.max_gap - self.min_gap) as u64 + 1;
+        self.min_gap + (self.rng as u64 % range) as u32
+    }
+
+    // whether the emitter is on for this tick
+    pub fn tick(&mut self) -> bool {
+        if let Some(position) = self.position {
+            let chip = (position / self.ticks_per_chip) as usize;
+            if chip < BURST_CHIPS {
+                self.position = Some(position + 1);
+                return self.chips[chip];
+            }
+            self.position = None;
+            self.gap = self.random_gap();
+        }
+
+        if self.gap == 0 {
+            self.position = Some(1);
+            return self.chips[0];
+        }
+        self.gap -= 1;
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DecodeState {
+    // samples the receiver has been lit for
+    Hunting { on: u32 },
+    // samples it's been dark for since a start
+    Start { off: u32 },
+    // samples since the data began, and the chips so far
+    Data { sample: u32, chips: u32, count: u32 },
+}
+
+// The receiver side. Takes one sample at a time from a receiver sampled several times a chip, so it can find the
+// middle of each chip whatever the phase of the emitter's clock.
+#[derive(Debug, Clone, Copy)]
+pub struct BurstDecoder {
+    samples_per_chip: u32,
+    state: DecodeState,
+}
+
+impl BurstDecoder {
+    pub fn new(samples_per_chip: u32) -> Self {
+        assert!(samples_per_chip > 0);
+        Self {
+            samples_per_chip,
+            state: DecodeState::Hunting { on: 0 },
+        }
+    }
+
+    // Some once a whole burst has come in, with the address or why it couldn't be read
+    pub fn push(&mut self, lit: bool) -> Option<Result<u8, BurstError>> {
+        let spc = self.samples_per_chip;
+        let start = START_CHIPS as u32 * spc;
+
+        self.state = match self.state {
+            DecodeState::Hunting { on } if lit => DecodeState::Hunting {
+                on: on.saturating_add(1),
+            },
+            DecodeState::Hunting { on } => {
+                if on.abs_diff(start) <= spc / 2 {
+                    DecodeState::Start { off: 1 }
+                } else {
+                    DecodeState::Hunting { on: 0 }
+                }
+            }
+            DecodeState::Start { .. } if lit => DecodeState::Hunting { on: 1 },
+            DecodeState::Start { off } if off + 1 == start => DecodeState::Data {
+                sample: 0,
+                chips: 0,
+                count: 0,
+            },
+            DecodeState::Start { off } => DecodeState::Start { off: off + 1 },
+            DecodeState::Data {
+                sample,
+                mut chips,
+                mut count,
+            } => {
+                if sample % spc == spc / 2 {
+                    chips = chips << 1 | lit as u32;
+                    count += 1;
+                    if count == 2 * DATA_BITS as u32 {
+                        self.state = DecodeState::Hunting { on: 0 };
+                        return Some(Self::decode(chips));
+                    }
+                }
+                DecodeState::Data {
+                    sample: sample + 1,
+                    chips,
+                    count,
+                }
+            }
+        };
+        None
+    }
+
+    fn decode(chips: u32) -> Result<u8, BurstError> {
+        let mut word: u16 = 0;
+        for bit in 0..DATA_BITS {
+            let pair = (chips >> (2 * (DATA_BITS - 1 - bit))) & 0b11;
+            word = word << 1
+                | match pair {
+                    0b10 => 1,
+                    0b01 => 0,
+                    _ => return Err(BurstError::BadCoding),
+                };
+        }
+
+        let address = (word >> 8) as u8;
+        if checksum(address) != word as u8 {
+            return Err(BurstError::BadChecksum);
+        }
+        Ok(address)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    const SAMPLES_PER_CHIP: u32 = 4;
+
+    // a burst sampled SAMPLES_PER_CHIP times a chip, starting `phase` samples into a dark chip
+    fn samples(chips: &[bool], phase: usize) -> Vec<bool> {
+        let mut samples = std::vec![false; phase + 5];
+        for chip in chips {
+            samples.extend(core::iter::repeat_n(*chip, SAMPLES_PER_CHIP as usize));
+        }
+        samples.extend([false; 10]);
+        samples
+    }
+
+    fn decode_all(samples: &[bool]) -> Vec<Result<u8, BurstError>> {
+        let mut decoder = BurstDecoder::new(SAMPLES_PER_CHIP);
+        samples.iter().filter_map(|s| decoder.push(*s)).collect()
+    }
+
+    #[test]
+    fn every_address() {
+        for address in 0..=u8::MAX {
+            let chips = encode(address);
+            // nothing in the data looks like a start
+            assert!(!chips[2 * START_CHIPS..]
+                .windows(3)
+                .any(|w| w.iter().all(|c| *c)));
+
+            let phase = address as usize % SAMPLES_PER_CHIP as usize;
+            assert_eq!(decode_all(&samples(&chips, phase)), [Ok(address)]);
+        }
+    }
+
+    #[test]
+    fn corrupted_bursts() {
+        let mut chips = encode(0x5a);
+        // one bit flipped, both halves, so it's still valid Manchester
+        chips[2 * START_CHIPS + 4] = !chips[2 * START_CHIPS + 4];
+        chips[2 * START_CHIPS + 5] = !chips[2 * START_CHIPS + 5];
+        assert_eq!(
+            decode_all(&samples(&chips, 0)),
+            [Err(BurstError::BadChecksum)]
+        );
+
+        // two bursts on top of each other, half a bit apart
+        let (a, b) = (encode(0x12), encode(0x34));
+        let overlapped: Vec<bool> = (0..BURST_CHIPS)
+            .map(|i| a[i] || (i >= 2 && b[i - 2]))
+            .collect();
+        assert!(!decode_all(&samples(&overlapped, 1))
+            .iter()
+            .any(|r| r.is_ok()));
+
+        // a start that's too short isn't one
+        assert!(decode_all(&samples(&encode(7)[1..], 0)).is_empty());
+    }
+
+    #[test]
+    fn emitter_sends_repeatedly() {
+        let mut emitter = BurstEmitter::new(42, SAMPLES_PER_CHIP, 100, 300, 42);
+        let samples: Vec<bool> = (0..5000).map(|_| emitter.tick()).collect();
+        let decoded = decode_all(&samples);
+        assert!(decoded.len() >= 10);
+        assert!(decoded.iter().all(|r| *r == Ok(42)));
+
+        // any gap at all
+        let mut emitter = BurstEmitter::new(42, 1, 0, u32::MAX, 42);
+        for _ in 0..100 {
+            emitter.tick();
+        }
+    }
+
+    #[test]
+    fn neighbouring_addresses() {
+        // seeded by address and switched on together, so only the seed keeps them apart
+        let mut emitters =
+            [2u8, 3].map(|a| BurstEmitter::new(a, SAMPLES_PER_CHIP, 500, 4000, a as u32));
+        let mut decoder = BurstDecoder::new(SAMPLES_PER_CHIP);
+        let mut seen = Vec::new();
+        for _ in 0..100_000 {
+            // both have to tick, whatever the first did
+            let lit = emitters[0].tick() | emitters[1].tick();
+            if let Some(Ok(address)) = decoder.push(lit) {
+                seen.push(address);
+            }
+        }
+        assert!(seen.contains(&2) && seen.contains(&3), "{seen:?}");
+    }
+
+    #[test]
+    fn unsynchronized_minis() {
+        let addresses = [3u8, 17, 42, 99, 200, 201];
+        // no two minis start at the same time, and their clocks don't line up with the receiver's
+        let mut emitters: Vec<(BurstEmitter, usize)> = addresses
+            .iter()
+            .enumerate()
+            .map(|(i, &a)| {
+                (
+                    BurstEmitter::new(a, SAMPLES_PER_CHIP, 500, 4000, a as u32 * 7919),
+                    i * 37,
+                )
+            })
+            .collect();
+
+        let mut decoder = BurstDecoder::new(SAMPLES_PER_CHIP);
+        let mut seen = Vec::new();
+        let mut errors = 0;
+        for sample in 0..200_000 {
+            let lit = emitters
+                .iter_mut()
+                .filter(|(_, delay)| sample >= *delay)
+                .fold(false, |lit, (emitter, _)| emitter.tick() || lit);
+            match decoder.push(lit) {
+                Some(Ok(address)) => seen.push(address),
+                Some(Err(_)) => errors += 1,
+                None => (),
+            }
+        }
+
+        // some collided, the rest got through
+        assert!(errors > 0);
+        for address in addresses {
+            assert!(seen.contains(&address), "{address} never identified");
+        }
+        assert!(seen.iter().all(|a| addresses.contains(a)));
+    }
+}
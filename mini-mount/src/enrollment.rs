@@ -0,0 +1,648 @@
+// Handing out addresses to minis as they're put on the table, so a mini doesn't need its slot flashed in.
+//
+// One slot per frame is kept for contention. A mini without an address picks a frame at random and sends a Request
+// with its nonce (something unique to it, like a serial number) in that slot, and the controller answers every
+// contention slot it heard something in: Assign gives the nonce an address, Full says there's none left, and Collision
+// means more than one mini spoke at once and the message was lost. Minis that collide, or hear nothing back, back off
+// for a random number of frames, doubling the window each time. A mini taken off the table sends Release with its
+// nonce, so a stale one can't free an address that's been handed on. One that goes quiet in its slot for too long, or
+// that the controller revokes, has its address taken back anyway: Revoke goes out in the next idle contention slot,
+// and the address isn't handed out again until it has, in case the mini is still there.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnrollmentMessage {
+    Request { nonce: u32 },
+    Assign { nonce: u32, address: u8 },
+    Full { nonce: u32 },
+    Collision,
+    Release { nonce: u32, address: u8 },
+    Revoke { nonce: u32, address: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnrollmentError {
+    BufferTooSmall,
+    Truncated,
+    UnknownMessage(u8),
+}
+
+const REQUEST_TAG: u8 = 0x01;
+const ASSIGN_TAG: u8 = 0x02;
+const FULL_TAG: u8 = 0x03;
+const COLLISION_TAG: u8 = 0x04;
+const RELEASE_TAG: u8 = 0x05;
+const REVOKE_TAG: u8 = 0x06;
+
+pub const MAX_MESSAGE_LEN: usize = 6;
+
+impl EnrollmentMessage {
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            EnrollmentMessage::Request { .. } | EnrollmentMessage::Full { .. } => 5,
+            EnrollmentMessage::Assign { .. }
+            | EnrollmentMessage::Release { .. }
+            | EnrollmentMessage::Revoke { .. } => 6,
+            EnrollmentMessage::Collision => 1,
+        }
+    }
+
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, EnrollmentError> {
+        let len = self.encoded_len();
+        if buf.len() < len {
+            return Err(EnrollmentError::BufferTooSmall);
+        }
+
+        match self {
+            EnrollmentMessage::Request { nonce } => {
+                buf[0] = REQUEST_TAG;
+                buf[1..5].copy_from_slice(&nonce.to_le_bytes());
+            }
+            EnrollmentMessage::Assign { nonce, address } => {
+                buf[0] = ASSIGN_TAG;
+                buf[1..5].copy_from_slice(&nonce.to_le_bytes());
+                buf[5] = *address;
+            }
+            EnrollmentMessage::Full { nonce } => {
+                buf[0] = FULL_TAG;
+                buf[1..5].copy_from_slice(&nonce.to_le_bytes());
+            }
+            EnrollmentMessage::Collision => buf[0] = COLLISION_TAG,
+            EnrollmentMessage::Release { nonce, address } => {
+                buf[0] = RELEASE_TAG;
+                buf[1..5].copy_from_slice(&nonce.to_le_bytes());
+                buf[5] = *address;
+            }
+            EnrollmentMessage::Revoke { nonce, address } => {
+                buf[0] = REVOKE_TAG;
+                buf[1..5].copy_from_slice(&nonce.to_le_bytes());
+                buf[5] = *address;
+            }
+        }
+
+        Ok(len)
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<(Self, usize), EnrollmentError> {
+        let Some(tag) = buf.first() else {
+            return Err(EnrollmentError::Truncated);
+        };
+        let nonce = || -> Result<u32, EnrollmentError> {
+            let bytes = buf.get(1..5).ok_or(EnrollmentError::Truncated)?;
+            Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+        let byte = |i: usize| buf.get(i).copied().ok_or(EnrollmentError::Truncated);
+
+        let message = match *tag {
+            REQUEST_TAG => EnrollmentMessage::Request { nonce: nonce()? },
+            ASSIGN_TAG => EnrollmentMessage::Assign {
+                nonce: nonce()?,
+                address: byte(5)?,
+            },
+            FULL_TAG => EnrollmentMessage::Full { nonce: nonce()? },
+            COLLISION_TAG => EnrollmentMessage::Collision,
+            RELEASE_TAG => EnrollmentMessage::Release {
+                nonce: nonce()?,
+                address: byte(5)?,
+            },
+            REVOKE_TAG => EnrollmentMessage::Revoke {
+                nonce: nonce()?,
+                address: byte(5)?,
+            },
+            tag => return Err(EnrollmentError::UnknownMessage(tag)),
+        };
+
+        Ok((message, message.encoded_len()))
+    }
+}
+
+// the most frames a mini will wait between requests is 2^MAX_BACKOFF_EXPONENT
+const MAX_BACKOFF_EXPONENT: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnrollmentState {
+    // frames left before the next request
+    BackingOff { frames: u8 },
+    // sent a request this frame, waiting on the answer
+    Requested,
+    Enrolled { address: u8 },
+}
+
+// The mini side, driven once per frame by the contention slot.
+#[derive(Debug, Clone, Copy)]
+pub struct MiniEnrollment {
+    nonce: u32,
+    state: EnrollmentState,
+    attempts: u8,
+    rng: u32,
+}
+
+impl MiniEnrollment {
+    // The nonce also seeds the backoff, so minis that collide once don't keep picking the same frames. It's mixed
+    // first, nonces are often serial numbers one apart.
+    pub fn new(nonce: u32) -> Self {
+        let seed = nonce.wrapping_mul(0x9E37_79B9) ^ (nonce >> 16);
+        let mut enrollment = Self {
+            nonce,
+            state: EnrollmentState::Requested,
+            attempts: 0,
+            // xorshift never leaves 0
+            rng: if seed == 0 { 0x2545_F491 } else { seed },
+        };
+        enrollment.back_off();
+        enrollment
+    }
+
+    pub fn state(&self) -> EnrollmentState {
+        self.state
+    }
+
+    pub fn address(&self) -> Option<u8> {
+        match self.state {
+            EnrollmentState::Enrolled { address } => Some(address),
+            _ => None,
+        }
+    }
+
+    // xorshift32
+    fn random(&mut self) -> u32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng
+    }
+
+    fn back_off(&mut self) {
+        let window = 1u32 << self.attempts.min(MAX_BACKOFF_EXPONENT);
+        self.attempts = self.attempts.saturating_add(1);
+        self.state = EnrollmentState::BackingOff {
+            frames: (self.random() % window) as u8,
+        };
+    }
+
+    // Call before every contention slot, returns what to send in it. A request that went unanswered is treated as
+    // lost in a collision nobody could make out.
+    pub fn contention_slot(&mut self) -> Option<EnrollmentMessage> {
+        match self.state {
+            EnrollmentState::Requested => {
+                self.back_off();
+                None
+            }
+            EnrollmentState::BackingOff { frames: 0 } => {
+                self.state = EnrollmentState::Requested;
+                Some(EnrollmentMessage::Request { nonce: self.nonce })
+            }
+            EnrollmentState::BackingOff { frames } => {
+                self.state = EnrollmentState::BackingOff { frames: frames - 1 };
+                None
+            }
+            EnrollmentState::Enrolled { .. } => None,
+        }
+    }
+
+    // everything the controller broadcasts comes through here, whether or not it's meant for this mini
+    pub fn handle(&mut self, message: EnrollmentMessage) {
+        if let EnrollmentState::Enrolled { address } = self.state {
+            if message
+                == (EnrollmentMessage::Revoke {
+                    nonce: self.nonce,
+                    address,
+                })
+            {
+                self.attempts = 0;
+                self.back_off();
+            }
+            return;
+        }
+        if self.state != EnrollmentState::Requested {
+            return;
+        }
+        match message {
+            EnrollmentMessage::Assign { nonce, address } if nonce == self.nonce => {
+                self.state = EnrollmentState::Enrolled { address };
+            }
+            EnrollmentMessage::Full { nonce } if nonce == self.nonce => {
+                self.attempts = MAX_BACKOFF_EXPONENT;
+                self.back_off();
+            }
+            // someone else got through, so ours was lost under theirs
+            EnrollmentMessage::Assign { .. }
+            | EnrollmentMessage::Full { .. }
+            | EnrollmentMessage::Collision => self.back_off(),
+            EnrollmentMessage::Request { .. }
+            | EnrollmentMessage::Release { .. }
+            | EnrollmentMessage::Revoke { .. } => (),
+        }
+    }
+
+    // taking the mini off the table, the release goes in the next contention slot
+    pub fn release(&mut self) -> Option<EnrollmentMessage> {
+        let address = self.address()?;
+        self.attempts = 0;
+        self.back_off();
+        Some(EnrollmentMessage::Release {
+            nonce: self.nonce,
+            address,
+        })
+    }
+}
+
+// what the controller made of a contention slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Contention {
+    Idle,
+    Heard(EnrollmentMessage),
+    // energy in the slot but nothing that decoded, more than one mini talking at once
+    Garbled,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Lease {
+    nonce: u32,
+    // frames in a row the mini hasn't been seen in its slot
+    missed: u16,
+    // taken back, but the mini hasn't been told yet
+    revoked: bool,
+}
+
+// The controller side. Hands out addresses 1 to N, so the frame needs at least N + 2 slots with the sync and
+// contention slots.
+#[derive(Debug, Clone)]
+pub struct Controller<const N: usize> {
+    leases: [Option<Lease>; N],
+    max_missed: u16,
+}
+
+impl<const N: usize> Controller<N> {
+    // a mini that misses its slot in more than `max_missed` frames in a row is taken to be gone
+    pub fn new(max_missed: u16) -> Self {
+        assert!(N < u8::MAX as usize - 1, "addresses have to fit in a u8");
+        Self {
+            leases: [None; N],
+            max_missed,
+        }
+    }
+
+    fn index(address: u8) -> Option<usize> {
+        (address as usize).checked_sub(1).filter(|i| *i < N)
+    }
+
+    pub fn address_of(&self, nonce: u32) -> Option<u8> {
+        self.leases
+            .iter()
+            .position(|l| l.is_some_and(|l| l.nonce == nonce && !l.revoked))
+            .map(|i| i as u8 + 1)
+    }
+
+    pub fn enrolled(&self) -> impl Iterator<Item = u8> + '_ {
+        self.leases
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| l.is_some_and(|l| !l.revoked))
+            .map(|(i, _)| i as u8 + 1)
+    }
+
+    // only frees the address if it's still leased to `nonce`
+    pub fn release(&mut self, nonce: u32, address: u8) {
+        if let Some(i) = Self::index(address) {
+            if self.leases[i].is_some_and(|l| l.nonce == nonce) {
+                self.leases[i] = None;
+            }
+        }
+    }
+
+    // Takes an address back from a mini that may still be on the table. The address stays out of use until Revoke
+    // has gone out in an idle contention slot.
+    pub fn revoke(&mut self, address: u8) {
+        if let Some(l) = Self::index(address).and_then(|i| self.leases[i].as_mut()) {
+            l.revoked = true;
+        }
+    }
+
+    // Handles the contention slot, returns what to broadcast back. A mini asking again gets the address it already
+    // has, in case it missed the first answer.
+    pub fn contention(&mut self, heard: Contention) -> Option<EnrollmentMessage> {
+        match heard {
+            Contention::Idle => {
+                let i = self
+                    .leases
+                    .iter()
+                    .position(|l| l.is_some_and(|l| l.revoked))?;
+                let nonce = self.leases[i].take()?.nonce;
+                Some(EnrollmentMessage::Revoke {
+                    nonce,
+                    address: i as u8 + 1,
+                })
+            }
+            Contention::Garbled => Some(EnrollmentMessage::Collision),
+            Contention::Heard(EnrollmentMessage::Request { nonce }) => {
+                if let Some(address) = self.address_of(nonce) {
+                    return Some(EnrollmentMessage::Assign { nonce, address });
+                }
+                // it's asking, so it already knows it lost its old address
+                if let Some(l) = self
+                    .leases
+                    .iter_mut()
+                    .find(|l| l.is_some_and(|l| l.nonce == nonce))
+                {
+                    *l = None;
+                }
+                match self.leases.iter().position(|l| l.is_none()) {
+                    Some(i) => {
+                        self.leases[i] = Some(Lease {
+                            nonce,
+                            missed: 0,
+                            revoked: false,
+                        });
+                        Some(EnrollmentMessage::Assign {
+                            nonce,
+                            address: i as u8 + 1,
+                        })
+                    }
+                    None => Some(EnrollmentMessage::Full { nonce }),
+                }
+            }
+            Contention::Heard(EnrollmentMessage::Release { nonce, address }) => {
+                self.release(nonce, address);
+                None
+            }
+            // only the controller sends anything else
+            Contention::Heard(_) => None,
+        }
+    }
+
+    // Call at the end of every frame with whether each address was seen in its slot. Returns how many addresses were
+    // taken back from minis that have gone.
+    pub fn end_frame(&mut self, seen: impl Fn(u8) -> bool) -> usize {
+        let mut released = 0;
+        for (i, lease) in self.leases.iter_mut().enumerate() {
+            let Some(l) = lease.as_mut().filter(|l| !l.revoked) else {
+                continue;
+            };
+            if seen(i as u8 + 1) {
+                l.missed = 0;
+            } else {
+                l.missed = l.missed.saturating_add(1);
+                if l.missed > self.max_missed {
+                    l.revoked = true;
+                    released += 1;
+                }
+            }
+        }
+        released
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    // Runs one frame of contention on a shared channel: a slot with one message in it is heard, more than one is
+    // garbled. Returns whether there was a collision.
+    fn run_frame<const N: usize>(
+        minis: &mut [MiniEnrollment],
+        controller: &mut Controller<N>,
+    ) -> bool {
+        let sent: Vec<EnrollmentMessage> = minis
+            .iter_mut()
+            .filter_map(|m| m.contention_slot())
+            .collect();
+        let heard = match sent.as_slice() {
+            [] => Contention::Idle,
+            [message] => Contention::Heard(*message),
+            _ => Contention::Garbled,
+        };
+        if let Some(answer) = controller.contention(heard) {
+            for mini in minis.iter_mut() {
+                mini.handle(answer);
+            }
+        }
+
+        let addresses: Vec<u8> = minis.iter().filter_map(|m| m.address()).collect();
+        controller.end_frame(|a| addresses.contains(&a));
+        heard == Contention::Garbled
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let messages = [
+            EnrollmentMessage::Request { nonce: 0xdeadbeef },
+            EnrollmentMessage::Assign {
+                nonce: 7,
+                address: 12,
+            },
+            EnrollmentMessage::Full { nonce: 1 },
+            EnrollmentMessage::Collision,
+            EnrollmentMessage::Release {
+                nonce: 0x1234,
+                address: 3,
+            },
+            EnrollmentMessage::Revoke {
+                nonce: 9,
+                address: 2,
+            },
+        ];
+
+        let mut buf = [0u8; MAX_MESSAGE_LEN];
+        for message in messages {
+            let len = message.encode(&mut buf).unwrap();
+            assert_eq!(EnrollmentMessage::decode(&buf[..len]), Ok((message, len)));
+            assert_eq!(
+                EnrollmentMessage::decode(&buf[..len - 1]),
+                Err(EnrollmentError::Truncated)
+            );
+        }
+
+        assert_eq!(
+            EnrollmentMessage::Collision.encode(&mut []),
+            Err(EnrollmentError::BufferTooSmall)
+        );
+        assert_eq!(
+            EnrollmentMessage::decode(&[0x42]),
+            Err(EnrollmentError::UnknownMessage(0x42))
+        );
+    }
+
+    #[test]
+    fn minis_join_together() {
+        let mut controller = Controller::<8>::new(3);
+        let mut minis: Vec<MiniEnrollment> = (0..6)
+            .map(|i| MiniEnrollment::new(0x1000 + i * 77))
+            .collect();
+
+        let mut collisions = 0;
+        let mut frames = 0;
+        while minis.iter().any(|m| m.address().is_none()) {
+            collisions += run_frame(&mut minis, &mut controller) as usize;
+            frames += 1;
+            assert!(frames < 500, "not everyone got an address");
+        }
+        assert!(collisions > 0);
+
+        let mut addresses: Vec<u8> = minis.iter().filter_map(|m| m.address()).collect();
+        addresses.sort();
+        assert_eq!(addresses, [1, 2, 3, 4, 5, 6]);
+        for mini in &minis {
+            assert_eq!(controller.address_of(mini.nonce), mini.address());
+        }
+    }
+
+    #[test]
+    fn neighbouring_nonces() {
+        for pair in [[0, 1], [2, 3]] {
+            let mut controller = Controller::<8>::new(3);
+            let mut minis = pair.map(MiniEnrollment::new);
+            let mut frames = 0;
+            while minis.iter().any(|m| m.address().is_none()) {
+                run_frame(&mut minis, &mut controller);
+                frames += 1;
+                assert!(frames < 500, "{pair:?} never both got an address");
+            }
+        }
+    }
+
+    #[test]
+    fn never_forgetting() {
+        let mut controller = Controller::<2>::new(u16::MAX);
+        let mut minis = [MiniEnrollment::new(9)];
+        while minis[0].address().is_none() {
+            run_frame(&mut minis, &mut controller);
+        }
+        for _ in 0..=u16::MAX as u32 + 10 {
+            assert_eq!(controller.end_frame(|_| false), 0);
+        }
+        assert_eq!(controller.address_of(9), minis[0].address());
+    }
+
+    #[test]
+    fn addresses_are_reused() {
+        let mut controller = Controller::<2>::new(3);
+        let mut minis = Vec::from([MiniEnrollment::new(1), MiniEnrollment::new(2)]);
+        while minis.iter().any(|m| m.address().is_none()) {
+            run_frame(&mut minis, &mut controller);
+        }
+
+        // the table is full
+        let mut late = MiniEnrollment::new(3);
+        while late.contention_slot().is_none() {}
+        assert_eq!(
+            controller.contention(Contention::Heard(EnrollmentMessage::Request { nonce: 3 })),
+            Some(EnrollmentMessage::Full { nonce: 3 })
+        );
+
+        // one leaves properly
+        let address = minis[0].address().unwrap();
+        let release = minis.remove(0).release().unwrap();
+        assert_eq!(controller.contention(Contention::Heard(release)), None);
+        assert!(!controller.enrolled().any(|a| a == address));
+
+        minis.push(MiniEnrollment::new(3));
+        while minis[1].address().is_none() {
+            run_frame(&mut minis, &mut controller);
+        }
+        assert_eq!(minis[1].address(), Some(address));
+
+        // and one is just picked up, its address comes back once it's been missing for long enough
+        let address = minis[0].address().unwrap();
+        minis.remove(0);
+        for _ in 0..3 {
+            run_frame(&mut minis, &mut controller);
+            assert!(controller.enrolled().any(|a| a == address));
+        }
+        run_frame(&mut minis, &mut controller);
+        assert!(!controller.enrolled().any(|a| a == address));
+        assert_eq!(
+            controller.contention(Contention::Idle),
+            Some(EnrollmentMessage::Revoke { nonce: 2, address })
+        );
+    }
+
+    #[test]
+    fn stale_releases_are_ignored() {
+        let mut controller = Controller::<2>::new(3);
+        let mut minis = [MiniEnrollment::new(1)];
+        while minis[0].address().is_none() {
+            run_frame(&mut minis, &mut controller);
+        }
+        let address = minis[0].address().unwrap();
+
+        // a release from whoever had the address before
+        let stale = EnrollmentMessage::Release { nonce: 2, address };
+        assert_eq!(controller.contention(Contention::Heard(stale)), None);
+        assert_eq!(controller.address_of(1), Some(address));
+    }
+
+    #[test]
+    fn revoked_minis_ask_again() {
+        let mut controller = Controller::<1>::new(3);
+        let mut minis = Vec::from([MiniEnrollment::new(1)]);
+        while minis[0].address().is_none() {
+            run_frame(&mut minis, &mut controller);
+        }
+        let address = minis[0].address().unwrap();
+
+        controller.revoke(address);
+        assert_eq!(controller.enrolled().count(), 0);
+
+        // the address isn't handed on until the mini has been told
+        assert_eq!(
+            controller.contention(Contention::Heard(EnrollmentMessage::Request { nonce: 2 })),
+            Some(EnrollmentMessage::Full { nonce: 2 })
+        );
+        let revoke = controller.contention(Contention::Idle).unwrap();
+        assert_eq!(revoke, EnrollmentMessage::Revoke { nonce: 1, address });
+        assert_eq!(controller.contention(Contention::Idle), None);
+
+        // revokes for someone else don't count
+        minis[0].handle(EnrollmentMessage::Revoke { nonce: 2, address });
+        assert_eq!(minis[0].address(), Some(address));
+        minis[0].handle(revoke);
+        assert_eq!(minis[0].address(), None);
+
+        // and it gets an address again the usual way
+        let mut frames = 0;
+        while minis[0].address().is_none() {
+            run_frame(&mut minis, &mut controller);
+            frames += 1;
+            assert!(frames < 100);
+        }
+        assert_eq!(controller.address_of(1), minis[0].address());
+    }
+
+    #[test]
+    fn unanswered_requests_back_off() {
+        let mut mini = MiniEnrollment::new(5);
+        while mini.contention_slot().is_none() {}
+        assert_eq!(mini.state(), EnrollmentState::Requested);
+
+        // nothing came back before the next slot
+        assert_eq!(mini.contention_slot(), None);
+        assert!(matches!(mini.state(), EnrollmentState::BackingOff { .. }));
+
+        // answers for someone else don't count
+        while mini.contention_slot().is_none() {}
+        mini.handle(EnrollmentMessage::Assign {
+            nonce: 6,
+            address: 1,
+        });
+        assert_eq!(mini.address(), None);
+
+        while mini.contention_slot().is_none() {}
+        mini.handle(EnrollmentMessage::Assign {
+            nonce: 5,
+            address: 4,
+        });
+        assert_eq!(mini.address(), Some(4));
+        assert_eq!(mini.contention_slot(), None);
+
+        // asking again gets the same address
+        let mut controller = Controller::<4>::new(3);
+        let request = Contention::Heard(EnrollmentMessage::Request { nonce: 9 });
+        assert_eq!(
+            controller.contention(request),
+            controller.contention(request)
+        );
+    }
+}
@@ -1,3 +1,9 @@
+#![no_std]
+
+pub mod burst;
+pub mod enrollment;
+pub mod schedule;
+
 pub struct Mini {
     address: u8,
     clock_state: u8,
@@ -20,3 +26,79 @@ impl Mini {
         self.clock_state == self.address
     }
 }
+
+// A mini with several emitters in a fixed arrangement on its base, each one flashing in its own slot so the table
+// can tell them apart and work out which way the mini is facing.
+pub struct MultiEmitterMini<const N: usize> {
+    addresses: [u8; N],
+    clock_state: u8,
+}
+
+impl<const N: usize> MultiEmitterMini<N> {
+    pub fn new(addresses: [u8; N]) -> Self {
+        for (i, address) in addresses.iter().enumerate() {
+            assert!(
+                !addresses[i + 1..].contains(address),
+                "emitters need their own slots"
+            );
+        }
+
+        Self {
+            addresses,
+            clock_state: 0,
+        }
+    }
+
+    pub fn addresses(&self) -> &[u8; N] {
+        &self.addresses
+    }
+
+    pub fn synchronize(&mut self) {
+        self.clock_state = 0;
+    }
+
+    // index of the emitter that should flash in this slot, if any
+    pub fn tick(&mut self) -> Option<usize> {
+        self.clock_state = self.clock_state.saturating_add(1);
+        self.addresses.iter().position(|a| *a == self.clock_state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn single_emitter_slot() {
+        let mut mini = Mini::new(3);
+        assert!(!mini.tick());
+        assert!(!mini.tick());
+        assert!(mini.tick());
+        assert!(!mini.tick());
+
+        mini.synchronize();
+        assert!(!mini.tick());
+        assert!(!mini.tick());
+        assert!(mini.tick());
+    }
+
+    #[test]
+    fn each_emitter_gets_its_slot() {
+        let mut mini = MultiEmitterMini::new([4, 2]);
+        let fired: Vec<Option<usize>> = (0..6).map(|_| mini.tick()).collect();
+        assert_eq!(fired, [None, Some(1), None, Some(0), None, None]);
+
+        mini.synchronize();
+        assert_eq!(mini.tick(), None);
+        assert_eq!(mini.tick(), Some(1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn shared_slot() {
+        MultiEmitterMini::new([1, 2, 1]);
+    }
+}
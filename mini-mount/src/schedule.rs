@@ -0,0 +1,352 @@
+// Time-division scheduling for the emitters. The table's sync pulse starts a frame, which is split into equal slots:
+// slot 0 is kept for the sync itself and every other slot belongs to the mini (or emitter) with that address. Frames
+// repeat back to back, so a slot comes round again every frame until the next sync.
+//
+// Each mini times its slots on its own clock, which drifts from the table's between syncs. An emitter stays dark for a
+// guard interval at each end of its slot, and a mini that has gone too long without a sync stops emitting, so a drifted
+// flash still lands inside its own slot. All times are in microseconds and allowed to wrap.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleError {
+    // a frame needs the sync slot and at least one for a mini
+    TooFewSlots,
+    // the guards at both ends leave nothing of the slot to emit in
+    GuardTooLong,
+    NoResync,
+    // the clocks can drift further than a guard before the next sync
+    DriftExceedsGuard,
+    // too long between syncs to tell apart with wrapping u32 timestamps
+    ResyncTooLong,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameConfig {
+    slots: u8,
+    slot_us: u32,
+    guard_us: u32,
+    resync_frames: u32,
+    max_drift_ppm: u32,
+}
+
+impl FrameConfig {
+    pub fn new(
+        slots: u8,
+        slot_us: u32,
+        guard_us: u32,
+        resync_frames: u32,
+        max_drift_ppm: u32,
+    ) -> Result<Self, ScheduleError> {
+        if slots < 2 {
+            return Err(ScheduleError::TooFewSlots);
+        }
+        if guard_us.saturating_mul(2) >= slot_us {
+            return Err(ScheduleError::GuardTooLong);
+        }
+        if resync_frames == 0 {
+            return Err(ScheduleError::NoResync);
+        }
+
+        let resync_us = slots as u64 * slot_us as u64 * resync_frames as u64;
+        if resync_us > (u32::MAX / 2) as u64 {
+            return Err(ScheduleError::ResyncTooLong);
+        }
+
+        let config = Self {
+            slots,
+            slot_us,
+            guard_us,
+            resync_frames,
+            max_drift_ppm,
+        };
+        if config.max_drift_us() > guard_us {
+            return Err(ScheduleError::DriftExceedsGuard);
+        }
+        Ok(config)
+    }
+
+    pub fn slots(&self) -> u8 {
+        self.slots
+    }
+
+    pub fn frame_us(&self) -> u32 {
+        self.slots as u32 * self.slot_us
+    }
+
+    // how long a sync is good for
+    pub fn resync_us(&self) -> u32 {
+        self.frame_us() * self.resync_frames
+    }
+
+    // the furthest two clocks can get apart between syncs
+    pub fn max_drift_us(&self) -> u32 {
+        (self.resync_us() as u64 * self.max_drift_ppm as u64).div_ceil(1_000_000) as u32
+    }
+
+    // which slot `elapsed` since the sync falls in, and how far into it
+    fn slot_at(&self, elapsed: u32) -> (u8, u32) {
+        let in_frame = elapsed % self.frame_us();
+        ((in_frame / self.slot_us) as u8, in_frame % self.slot_us)
+    }
+}
+
+// The mini side, one per mini. Runs on the mini's own clock.
+#[derive(Debug, Clone, Copy)]
+pub struct SlotClock {
+    config: FrameConfig,
+    synced_at: Option<u32>,
+}
+
+impl SlotClock {
+    pub fn new(config: FrameConfig) -> Self {
+        Self {
+            config,
+            synced_at: None,
+        }
+    }
+
+    // call when the sync pulse arrives, `now` is the start of slot 0
+    pub fn synchronize(&mut self, now: u32) {
+        self.synced_at = Some(now);
+    }
+
+    fn elapsed(&self, now: u32) -> Option<u32> {
+        let elapsed = now.wrapping_sub(self.synced_at?);
+        (elapsed < self.config.resync_us()).then_some(elapsed)
+    }
+
+    pub fn needs_resync(&self, now: u32) -> bool {
+        self.elapsed(now).is_none()
+    }
+
+    // The slot that can be emitted in at `now`. None in the sync slot, the guard intervals, and before the first
+    // sync or once the last one is too old to trust.
+    pub fn emit_slot(&self, now: u32) -> Option<u8> {
+        let (slot, into) = self.config.slot_at(self.elapsed(now)?);
+        let guard = self.config.guard_us;
+        (slot != 0 && into >= guard && into < self.config.slot_us - guard).then_some(slot)
+    }
+
+    pub fn can_emit(&self, now: u32, address: u8) -> bool {
+        self.emit_slot(now) == Some(address)
+    }
+
+    // when `address` can next start emitting, so the mini can sleep until then. None if that would be after the sync
+    // runs out.
+    pub fn next_emission(&self, now: u32, address: u8) -> Option<u32> {
+        if address == 0 || address >= self.config.slots {
+            return None;
+        }
+        let elapsed = self.elapsed(now)?;
+        if self.can_emit(now, address) {
+            return Some(now);
+        }
+
+        let frame_us = self.config.frame_us();
+        let offset = address as u32 * self.config.slot_us + self.config.guard_us;
+        let frame_start = elapsed - elapsed % frame_us;
+        let mut start = frame_start + offset;
+        if start < elapsed {
+            start += frame_us;
+        }
+        (start < self.config.resync_us()).then(|| self.synced_at.unwrap().wrapping_add(start))
+    }
+}
+
+// The table side, maps the timestamp of an observation back to the address of the mini that made it. The whole slot
+// counts, guards included, since that's where a drifted flash ends up.
+#[derive(Debug, Clone, Copy)]
+pub struct SlotDecoder {
+    config: FrameConfig,
+    synced_at: Option<u32>,
+}
+
+impl SlotDecoder {
+    pub fn new(config: FrameConfig) -> Self {
+        Self {
+            config,
+            synced_at: None,
+        }
+    }
+
+    // call when the sync pulse goes out
+    pub fn synchronize(&mut self, now: u32) {
+        self.synced_at = Some(now);
+    }
+
+    pub fn decode(&self, timestamp: u32) -> Option<u8> {
+        let elapsed = timestamp.wrapping_sub(self.synced_at?);
+        if elapsed >= self.config.resync_us() {
+            return None;
+        }
+        let (slot, _) = self.config.slot_at(elapsed);
+        (slot != 0).then_some(slot)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 8 slots of 1ms with 50us guards, resynced every 20 frames, clocks good to 200ppm
+    fn config() -> FrameConfig {
+        FrameConfig::new(8, 1000, 50, 20, 200).unwrap()
+    }
+
+    // a clock running `ppm` fast or slow, reading `offset` when the table's reads 0
+    #[derive(Clone, Copy)]
+    struct DriftingClock {
+        offset: u32,
+        ppm: i64,
+    }
+
+    impl DriftingClock {
+        fn at(&self, table_time: u32) -> u32 {
+            let drift = table_time as i64 * self.ppm / 1_000_000;
+            self.offset
+                .wrapping_add(table_time)
+                .wrapping_add(drift as u32)
+        }
+    }
+
+    #[test]
+    fn config_limits() {
+        assert_eq!(config().frame_us(), 8000);
+        assert_eq!(config().resync_us(), 160_000);
+        assert_eq!(config().max_drift_us(), 32);
+
+        assert_eq!(
+            FrameConfig::new(1, 1000, 50, 20, 200),
+            Err(ScheduleError::TooFewSlots)
+        );
+        assert_eq!(
+            FrameConfig::new(8, 100, 50, 20, 200),
+            Err(ScheduleError::GuardTooLong)
+        );
+        assert_eq!(
+            FrameConfig::new(8, 1000, 50, 0, 200),
+            Err(ScheduleError::NoResync)
+        );
+        // 200ppm over 50 frames is 80us, more than the guard
+        assert_eq!(
+            FrameConfig::new(8, 1000, 50, 50, 200),
+            Err(ScheduleError::DriftExceedsGuard)
+        );
+        assert_eq!(
+            FrameConfig::new(255, 1_000_000, 50, 100, 0),
+            Err(ScheduleError::ResyncTooLong)
+        );
+    }
+
+    #[test]
+    fn slots_repeat_every_frame() {
+        let mut clock = SlotClock::new(config());
+        assert_eq!(clock.emit_slot(0), None);
+        assert!(clock.needs_resync(0));
+
+        clock.synchronize(0);
+        assert_eq!(clock.emit_slot(500), None);
+        assert_eq!(clock.emit_slot(1049), None);
+        assert_eq!(clock.emit_slot(1050), Some(1));
+        assert_eq!(clock.emit_slot(1950), None);
+        assert_eq!(clock.emit_slot(7500), Some(7));
+        assert_eq!(clock.emit_slot(8000 * 3 + 3500), Some(3));
+        assert!(clock.can_emit(8000 * 19 + 2500, 2));
+
+        // the sync has run out
+        assert!(clock.needs_resync(160_000));
+        assert_eq!(clock.emit_slot(160_000 + 2500), None);
+    }
+
+    #[test]
+    fn sleeping_until_the_slot() {
+        let mut clock = SlotClock::new(config());
+        assert_eq!(clock.next_emission(0, 3), None);
+
+        clock.synchronize(100);
+        assert_eq!(clock.next_emission(100, 3), Some(100 + 3050));
+        assert_eq!(clock.next_emission(100 + 3500, 3), Some(100 + 3500));
+        assert_eq!(clock.next_emission(100 + 4000, 3), Some(100 + 8000 + 3050));
+        // nothing for the sync slot or addresses past the end of the frame
+        assert_eq!(clock.next_emission(100, 0), None);
+        assert_eq!(clock.next_emission(100, 8), None);
+        // the last frame before the sync runs out
+        assert_eq!(clock.next_emission(100 + 8000 * 19 + 4000, 3), None);
+    }
+
+    #[test]
+    fn drifting_clocks() {
+        let config = config();
+        let clocks = [
+            DriftingClock {
+                offset: 0,
+                ppm: 200,
+            },
+            DriftingClock {
+                offset: 12_345,
+                ppm: -200,
+            },
+            DriftingClock {
+                offset: 99,
+                ppm: 150,
+            },
+            // wraps round during the test
+            DriftingClock {
+                offset: u32::MAX - 50_000,
+                ppm: -75,
+            },
+        ];
+        let addresses = [1u8, 2, 5, 7];
+
+        let mut minis = [SlotClock::new(config); 4];
+        let mut decoder = SlotDecoder::new(config);
+
+        // the table syncs at 0 and 160ms, a bit of the sync period left over at the end to check the minis go quiet
+        let mut emissions = [0u32; 4];
+        let mut table_time = 0;
+        while table_time < 400_000 {
+            if table_time % 160_000 == 0 && table_time < 320_000 {
+                decoder.synchronize(table_time);
+                for (mini, clock) in minis.iter_mut().zip(&clocks) {
+                    mini.synchronize(clock.at(table_time));
+                }
+            }
+
+            let emitting: usize = (0..4)
+                .filter(|&i| {
+                    let emits = minis[i].can_emit(clocks[i].at(table_time), addresses[i]);
+                    if emits {
+                        emissions[i] += 1;
+                        assert_eq!(decoder.decode(table_time), Some(addresses[i]));
+                    }
+                    emits
+                })
+                .count();
+            assert!(emitting <= 1);
+
+            table_time += 10;
+        }
+
+        // every mini emitted in each of the 40 frames
+        for count in emissions {
+            assert!((40 * 89..=40 * 91).contains(&count), "{count}");
+        }
+        assert!(minis[0].needs_resync(clocks[0].at(table_time)));
+        assert_eq!(decoder.decode(table_time), None);
+    }
+
+    #[test]
+    fn decoding() {
+        let mut decoder = SlotDecoder::new(config());
+        assert_eq!(decoder.decode(1500), None);
+
+        // just before the timestamps wrap
+        let sync = u32::MAX - 2000;
+        decoder.synchronize(sync);
+        assert_eq!(decoder.decode(sync + 500), None);
+        assert_eq!(decoder.decode(sync + 1000), Some(1));
+        assert_eq!(decoder.decode(sync.wrapping_add(2999)), Some(2));
+        assert_eq!(decoder.decode(sync.wrapping_add(8000 * 7 + 6001)), Some(6));
+        // from before the sync
+        assert_eq!(decoder.decode(sync - 10), None);
+    }
+}
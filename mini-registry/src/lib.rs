@@ -0,0 +1,539 @@
+// Everything we know about each mini that outlives a session: what it's called, whose it is, how big its base is and
+// where it was last seen. Minis are known to the hardware by several kinds of identity, a slot address on the table,
+// a BLE address to the screen apps, the nonce it enrols with, and a record holds every one of them that belongs to
+// the same mini. Kept in one JSON file that the screen apps and the table controller both read and write, so changes
+// go through `Registry::update`, which holds a lock on the file while it reads, changes and writes it back.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use mini_tracker::orientation::EmitterArrangement;
+use mini_tracker::units::{Degrees, Mm, BASE_DIAMETER};
+use mini_tracker::Point;
+use serde::{Deserialize, Serialize};
+
+// where the registry lives unless MINI_REGISTRY says otherwise
+pub const DEFAULT_FILE: &str = "minis.json";
+
+pub fn default_path() -> PathBuf {
+    std::env::var_os("MINI_REGISTRY")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_FILE))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HardwareId {
+    // a fixed slot address, see mini_mount::Mini
+    Address { address: u8 },
+    // what btleplug reports as the peripheral address, like "E4:5F:01:2A:3B:4C"
+    Ble { address: String },
+    // what the mini enrols with, see mini_mount::enrollment
+    Nonce { nonce: u32 },
+}
+
+impl std::fmt::Display for HardwareId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HardwareId::Address { address } => write!(f, "address {address}"),
+            HardwareId::Ble { address } => write!(f, "BLE {address}"),
+            HardwareId::Nonce { nonce } => write!(f, "nonce {nonce:#010x}"),
+        }
+    }
+}
+
+// stored as "#rrggbb"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Colour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl TryFrom<String> for Colour {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let hex = s
+            .strip_prefix('#')
+            // ascii too, or slicing it into channels could split a character
+            .filter(|h| h.len() == 6 && h.is_ascii())
+            .ok_or_else(|| format!("expected a colour like #rrggbb, got '{s}'"))?;
+        let channel = |i: usize| {
+            u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| format!("'{s}' isn't hex"))
+        };
+        Ok(Self {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        })
+    }
+}
+
+impl From<Colour> for String {
+    fn from(c: Colour) -> Self {
+        format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b)
+    }
+}
+
+fn default_diameter() -> Mm {
+    BASE_DIAMETER
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BaseProfile {
+    #[serde(default = "default_diameter")]
+    pub diameter: Mm,
+    // where the emitters sit on the base for minis with more than one, see EmitterArrangement
+    #[serde(default)]
+    pub emitters: Vec<Point>,
+}
+
+impl Default for BaseProfile {
+    fn default() -> Self {
+        Self {
+            diameter: BASE_DIAMETER,
+            emitters: Vec::new(),
+        }
+    }
+}
+
+impl BaseProfile {
+    pub fn arrangement(&self) -> Option<EmitterArrangement> {
+        (!self.emitters.is_empty()).then(|| EmitterArrangement::new(&self.emitters))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LastPosition {
+    pub x: Mm,
+    pub y: Mm,
+    #[serde(default)]
+    pub facing: Option<Degrees>,
+    // seconds since the unix epoch
+    pub seen_at: u64,
+}
+
+impl LastPosition {
+    pub fn now(location: Point, facing: Option<Degrees>) -> Self {
+        Self {
+            x: Mm(location.x),
+            y: Mm(location.y),
+            facing,
+            seen_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+
+    pub fn location(&self) -> Point {
+        Point {
+            x: self.x.0,
+            y: self.y.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MiniRecord {
+    pub ids: Vec<HardwareId>,
+    pub name: String,
+    #[serde(default)]
+    pub base: BaseProfile,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub colour: Option<Colour>,
+    // relative paths are from the registry file
+    #[serde(default)]
+    pub token_image: Option<PathBuf>,
+    #[serde(default)]
+    pub last_position: Option<LastPosition>,
+}
+
+impl MiniRecord {
+    pub fn new(id: HardwareId, name: &str) -> Self {
+        Self {
+            ids: vec![id],
+            name: name.to_string(),
+            base: BaseProfile::default(),
+            owner: None,
+            colour: None,
+            token_image: None,
+            last_position: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RegistryError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    // the id is already on a record, another one or the same one
+    Duplicate(HardwareId),
+    Unknown(HardwareId),
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::Io(e) => write!(f, "couldn't read or write mini registry: {e}"),
+            RegistryError::Json(e) => write!(f, "bad mini registry file: {e}"),
+            RegistryError::Duplicate(id) => write!(f, "{id} is already registered"),
+            RegistryError::Unknown(id) => write!(f, "no mini with {id} is registered"),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+impl From<io::Error> for RegistryError {
+    fn from(e: io::Error) -> Self {
+        RegistryError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for RegistryError {
+    fn from(e: serde_json::Error) -> Self {
+        RegistryError::Json(e)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Registry {
+    #[serde(default)]
+    pub minis: Vec<MiniRecord>,
+}
+
+impl Registry {
+    pub fn find(&self, id: &HardwareId) -> Option<&MiniRecord> {
+        self.minis.iter().find(|m| m.ids.contains(id))
+    }
+
+    pub fn find_mut(&mut self, id: &HardwareId) -> Option<&mut MiniRecord> {
+        self.minis.iter_mut().find(|m| m.ids.contains(id))
+    }
+
+    pub fn register(&mut self, record: MiniRecord) -> Result<&mut MiniRecord, RegistryError> {
+        for (i, id) in record.ids.iter().enumerate() {
+            if record.ids[..i].contains(id) || self.find(id).is_some() {
+                return Err(RegistryError::Duplicate(id.clone()));
+            }
+        }
+        self.minis.push(record);
+        Ok(self.minis.last_mut().unwrap())
+    }
+
+    // another identity for a mini that's already registered, like the BLE address of one first seen on the table
+    pub fn add_id(&mut self, existing: &HardwareId, id: HardwareId) -> Result<(), RegistryError> {
+        // already on any record, this one included
+        if self.find(&id).is_some() {
+            return Err(RegistryError::Duplicate(id));
+        }
+        self.find_mut(existing)
+            .ok_or_else(|| RegistryError::Unknown(existing.clone()))?
+            .ids
+            .push(id);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &HardwareId) -> Option<MiniRecord> {
+        let i = self.minis.iter().position(|m| m.ids.contains(id))?;
+        Some(self.minis.remove(i))
+    }
+
+    pub fn update_position(
+        &mut self,
+        id: &HardwareId,
+        position: LastPosition,
+    ) -> Result<(), RegistryError> {
+        self.find_mut(id)
+            .ok_or_else(|| RegistryError::Unknown(id.clone()))?
+            .last_position = Some(position);
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), RegistryError> {
+        for (i, mini) in self.minis.iter().enumerate() {
+            for (j, id) in mini.ids.iter().enumerate() {
+                let elsewhere = self.minis[i + 1..].iter().any(|m| m.ids.contains(id));
+                if elsewhere || mini.ids[..j].contains(id) {
+                    return Err(RegistryError::Duplicate(id.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, RegistryError> {
+        let registry: Registry = serde_json::from_str(json)?;
+        registry.validate()?;
+        Ok(registry)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    // no file yet is an empty registry, nothing has been registered on this machine
+    pub fn load(path: &Path) -> Result<Self, RegistryError> {
+        match fs::read_to_string(path) {
+            Ok(json) => Self::from_json(&json),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Replaces whatever is in the file, including anything another app has saved since this was loaded. Use update
+    // to change the registry another app might be changing too.
+    pub fn save(&self, path: &Path) -> Result<(), RegistryError> {
+        let _lock = lock(path)?;
+        self.write(path)
+    }
+
+    // Loads the registry, changes it and saves it again, with the file locked against other apps doing the same so
+    // neither loses the other's changes. Nothing is saved if `change` fails.
+    pub fn update<T>(
+        path: &Path,
+        change: impl FnOnce(&mut Registry) -> Result<T, RegistryError>,
+    ) -> Result<T, RegistryError> {
+        let _lock = lock(path)?;
+        let mut registry = Self::load(path)?;
+        let result = change(&mut registry)?;
+        registry.write(path)?;
+        Ok(result)
+    }
+
+    // written next to the file and renamed over it, so the other app never reads half a registry
+    fn write(&self, path: &Path) -> Result<(), RegistryError> {
+        static WRITES: AtomicU32 = AtomicU32::new(0);
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(format!(
+            ".{}-{}.tmp",
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        if let Err(e) = fs::write(&temp, self.to_json()).and_then(|_| fs::rename(&temp, path)) {
+            let _ = fs::remove_file(&temp);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+// An advisory lock on a file beside the registry, held until the returned file is dropped. Not on the registry itself,
+// that gets replaced by every write.
+fn lock(path: &Path) -> Result<fs::File, RegistryError> {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)?;
+    file.lock()?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn knight() -> MiniRecord {
+        let mut record = MiniRecord::new(HardwareId::Address { address: 3 }, "Sir Roland");
+        record.owner = Some("Alex".to_string());
+        record.colour = Some(Colour {
+            r: 0x20,
+            g: 0x80,
+            b: 0xff,
+        });
+        record.token_image = Some(PathBuf::from("tokens/roland.png"));
+        record.base = BaseProfile {
+            diameter: Mm(32.0),
+            emitters: vec![Point { x: 8.0, y: 0.0 }, Point { x: -8.0, y: 0.0 }],
+        };
+        record
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut registry = Registry::default();
+        registry.register(knight()).unwrap();
+        registry
+            .register(MiniRecord::new(
+                HardwareId::Nonce { nonce: 0xbeef },
+                "Goblin",
+            ))
+            .unwrap();
+        registry
+            .update_position(
+                &HardwareId::Nonce { nonce: 0xbeef },
+                LastPosition::now(Point { x: 100.0, y: 50.0 }, Some(Degrees(90.0))),
+            )
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!("mini-registry-{}.json", std::process::id()));
+        registry.save(&path).unwrap();
+        let loaded = Registry::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, registry);
+
+        let json = loaded.to_json();
+        assert!(json.contains(r##""colour": "#2080ff""##));
+        assert!(json.contains(r#""kind": "nonce""#));
+
+        let goblin = loaded.find(&HardwareId::Nonce { nonce: 0xbeef }).unwrap();
+        assert_eq!(goblin.base.diameter, BASE_DIAMETER);
+        assert!(goblin.base.arrangement().is_none());
+        assert_eq!(
+            goblin.last_position.unwrap().location(),
+            Point { x: 100.0, y: 50.0 }
+        );
+        assert!(goblin.last_position.unwrap().seen_at > 0);
+        assert_eq!(loaded.minis[0].base.arrangement().unwrap().offsets.len(), 2);
+
+        // nothing saved yet
+        assert_eq!(
+            Registry::load(&path.with_extension("missing")).unwrap(),
+            Registry::default()
+        );
+    }
+
+    #[test]
+    fn identities() {
+        let mut registry = Registry::default();
+        registry.register(knight()).unwrap();
+
+        let ble = HardwareId::Ble {
+            address: "E4:5F:01:2A:3B:4C".to_string(),
+        };
+        registry
+            .add_id(&HardwareId::Address { address: 3 }, ble.clone())
+            .unwrap();
+        assert_eq!(registry.find(&ble).unwrap().name, "Sir Roland");
+
+        assert!(matches!(
+            registry.register(MiniRecord::new(ble.clone(), "Impostor")),
+            Err(RegistryError::Duplicate(_))
+        ));
+        // the same id twice on one record would save a file that can't be loaded
+        let mut twice = MiniRecord::new(HardwareId::Nonce { nonce: 7 }, "Twice");
+        twice.ids.push(HardwareId::Nonce { nonce: 7 });
+        assert!(matches!(
+            registry.register(twice),
+            Err(RegistryError::Duplicate(HardwareId::Nonce { nonce: 7 }))
+        ));
+        assert!(matches!(
+            registry.add_id(&ble, HardwareId::Address { address: 3 }),
+            Err(RegistryError::Duplicate(_))
+        ));
+        registry
+            .register(MiniRecord::new(
+                HardwareId::Address { address: 4 },
+                "Squire",
+            ))
+            .unwrap();
+        assert!(matches!(
+            registry.add_id(&HardwareId::Address { address: 4 }, ble.clone()),
+            Err(RegistryError::Duplicate(_))
+        ));
+        assert!(matches!(
+            registry.add_id(
+                &HardwareId::Address { address: 9 },
+                HardwareId::Nonce { nonce: 1 }
+            ),
+            Err(RegistryError::Unknown(_))
+        ));
+
+        assert_eq!(registry.remove(&ble).unwrap().name, "Sir Roland");
+        assert!(registry.find(&HardwareId::Address { address: 3 }).is_none());
+        assert!(registry.remove(&ble).is_none());
+    }
+
+    #[test]
+    fn concurrent_updates() {
+        let dir = std::env::temp_dir().join(format!("mini-registry-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("minis.json");
+
+        // each thread loads, adds one and saves, none of them lose the others' minis
+        let threads: Vec<_> = (0..8u8)
+            .map(|address| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    Registry::update(&path, |registry| {
+                        registry
+                            .register(MiniRecord::new(HardwareId::Address { address }, "mini"))
+                            .map(|_| ())
+                    })
+                    .unwrap();
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let registry = Registry::load(&path).unwrap();
+        assert_eq!(registry.minis.len(), 8);
+
+        // a change that fails isn't saved
+        let result = Registry::update(&path, |registry| {
+            registry.remove(&HardwareId::Address { address: 0 });
+            registry
+                .register(MiniRecord::new(HardwareId::Address { address: 1 }, "again"))
+                .map(|_| ())
+        });
+        assert!(matches!(result, Err(RegistryError::Duplicate(_))));
+        assert_eq!(Registry::load(&path).unwrap(), registry);
+
+        // nothing left behind but the registry and its lock
+        let mut files: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["minis.json", "minis.json.lock"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bad_files() {
+        assert!(matches!(
+            Registry::from_json(
+                r#"{ "minis": [
+                    { "ids": [{ "kind": "address", "address": 1 }], "name": "a" },
+                    { "ids": [{ "kind": "address", "address": 1 }], "name": "b" }
+                ] }"#
+            ),
+            Err(RegistryError::Duplicate(HardwareId::Address { address: 1 }))
+        ));
+        assert!(matches!(
+            Registry::from_json(r#"{ "minis": [{ "ids": [], "name": "a", "colour": "blue" }] }"#),
+            Err(RegistryError::Json(_))
+        ));
+        assert_eq!(
+            Colour::try_from("#00ff7f".to_string()),
+            Ok(Colour {
+                r: 0,
+                g: 0xff,
+                b: 0x7f
+            })
+        );
+        assert!(Colour::try_from("#00ff7".to_string()).is_err());
+        assert!(Colour::try_from("#00gg00".to_string()).is_err());
+        // six bytes, but not six characters
+        assert!(Colour::try_from("#aéaaa".to_string()).is_err());
+        assert!(matches!(
+            Registry::from_json(
+                r##"{ "minis": [{ "ids": [], "name": "a", "colour": "#aéaaa" }] }"##
+            ),
+            Err(RegistryError::Json(_))
+        ));
+    }
+}
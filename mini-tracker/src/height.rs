@@ -0,0 +1,271 @@
+use crate::units::{Degrees, Mm, Radians};
+use crate::{Line, Point, Polygon, Receiver};
+
+// A receiver that sits above the table surface, e.g. on a riser along the table edge, and may be tilted up or down.
+// The horizontal field of view is the one of the wrapped receiver, the vertical one is centred on the tilt.
+#[derive(Clone, Copy, Debug)]
+pub struct ElevatedReceiver {
+    pub receiver: Receiver,
+    pub height: Mm,
+    // above horizontal
+    pub tilt: Degrees,
+    pub vertical_view_angle: Degrees,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeightEstimate {
+    pub z: f32,
+    // z is known to within +/- this much
+    pub uncertainty: f32,
+}
+
+impl ElevatedReceiver {
+    pub fn new(
+        receiver: Receiver,
+        height: Mm,
+        tilt: Degrees,
+        vertical_view_angle: Degrees,
+    ) -> Self {
+        Self {
+            receiver,
+            height,
+            tilt,
+            vertical_view_angle,
+        }
+    }
+
+    pub fn can_see(&self, point: &Point, z: f32) -> bool {
+        if !self.receiver.can_see(point) {
+            return false;
+        }
+
+        let distance = self.receiver.location.distance(point);
+        let elevation = Radians::atan2(z - self.height.0, distance).to_degrees();
+
+        elevation >= self.tilt - self.vertical_view_angle / 2.0 - Degrees(0.01)
+            && elevation <= self.tilt + self.vertical_view_angle / 2.0 + Degrees(0.01)
+    }
+
+    // range of z this receiver would see at the given distance
+    fn visible_band(&self, distance: f32) -> (f32, f32) {
+        let low = (self.tilt - self.vertical_view_angle / 2.0).tan();
+        let high = (self.tilt + self.vertical_view_angle / 2.0).tan();
+
+        (
+            self.height.0 + distance * low,
+            self.height.0 + distance * high,
+        )
+    }
+
+    // z bands for the nearest and farthest the emitter can be, returned as (loosest, tightest). Anything the receiver
+    // sees has to be inside the loose band, anything inside the tight band would have been seen for sure.
+    fn bands(&self, footprint: &Polygon) -> ((f32, f32), Option<(f32, f32)>) {
+        let (near, far) = distance_range(&self.receiver.location, footprint);
+        let (near_low, near_high) = self.visible_band(near);
+        let (far_low, far_high) = self.visible_band(far);
+
+        let loose = (near_low.min(far_low), near_high.max(far_high));
+        let tight_low = near_low.max(far_low);
+        let tight_high = near_high.min(far_high);
+        let tight = if tight_low < tight_high {
+            Some((tight_low, tight_high))
+        } else {
+            None
+        };
+
+        (loose, tight)
+    }
+}
+
+fn distance_range(from: &Point, footprint: &Polygon) -> (f32, f32) {
+    let far = footprint
+        .points
+        .iter()
+        .map(|p| from.distance(p))
+        .fold(0.0, f32::max);
+
+    let near = if footprint.points.len() > 2 && footprint.contains(from) {
+        0.0
+    } else {
+        footprint
+            .lines
+            .iter()
+            .map(|line| segment_distance(from, line))
+            .fold(f32::INFINITY, f32::min)
+            .min(far)
+    };
+
+    (near, far)
+}
+
+fn segment_distance(point: &Point, line: &Line) -> f32 {
+    let (dx, dy) = (line.point2.x - line.point1.x, line.point2.y - line.point1.y);
+    let len_squared = dx * dx + dy * dy;
+    if len_squared == 0.0 {
+        return point.distance(&line.point1);
+    }
+
+    let t = (((point.x - line.point1.x) * dx + (point.y - line.point1.y) * dy) / len_squared)
+        .clamp(0.0, 1.0);
+    point.distance(&Point {
+        x: line.point1.x + t * dx,
+        y: line.point1.y + t * dy,
+    })
+}
+
+// Each receiver that saw the emitter limits z to the band it can see, each one that didn't (while the whole footprint
+// is inside its horizontal view) rules out the band it would have certainly seen. What is left of [0, max_height] is
+// reported as its midpoint and half width.
+pub fn estimate_height(
+    receivers: &[(ElevatedReceiver, bool)],
+    footprint: &Polygon,
+    max_height: Mm,
+) -> Option<HeightEstimate> {
+    let mut feasible = vec![(0.0, max_height.0)];
+
+    for (receiver, seen) in receivers {
+        let (loose, tight) = receiver.bands(footprint);
+
+        if *seen {
+            feasible = feasible
+                .into_iter()
+                .filter_map(|(low, high): (f32, f32)| {
+                    let low = low.max(loose.0);
+                    let high = high.min(loose.1);
+                    if low <= high {
+                        Some((low, high))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+        } else if footprint
+            .points
+            .iter()
+            .all(|p| receiver.receiver.can_see(p))
+        {
+            if let Some((cut_low, cut_high)) = tight {
+                feasible = feasible
+                    .into_iter()
+                    .flat_map(|(low, high)| {
+                        let mut remaining = Vec::new();
+                        if low < cut_low {
+                            remaining.push((low, high.min(cut_low)));
+                        }
+                        if high > cut_high {
+                            remaining.push((low.max(cut_high), high));
+                        }
+                        remaining
+                    })
+                    .collect();
+            }
+        }
+    }
+
+    let low = feasible.iter().map(|(low, _)| *low).reduce(f32::min)?;
+    let high = feasible.iter().map(|(_, high)| *high).reduce(f32::max)?;
+
+    Some(HeightEstimate {
+        z: (low + high) / 2.0,
+        uncertainty: (high - low) / 2.0,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Direction;
+
+    const TABLE_WIDTH: Mm = Mm(400.0);
+    const TABLE_HEIGHT: Mm = Mm(300.0);
+
+    // a column of receivers every 5mm up a 100mm riser on the left edge, all looking straight across the table
+    fn riser() -> Vec<ElevatedReceiver> {
+        (0..20)
+            .map(|i| {
+                let receiver = Receiver::new(
+                    TABLE_WIDTH,
+                    TABLE_HEIGHT,
+                    Degrees(30.0),
+                    Point { x: 0.0, y: 150.0 },
+                    Direction::Right,
+                );
+                ElevatedReceiver::new(receiver, Mm(5.0 * i as f32), Degrees(0.0), Degrees(4.0))
+            })
+            .collect()
+    }
+
+    fn footprint(center: Point, size: f32) -> Polygon {
+        Polygon::new(&[
+            Point {
+                x: center.x - size,
+                y: center.y - size,
+            },
+            Point {
+                x: center.x + size,
+                y: center.y - size,
+            },
+            Point {
+                x: center.x + size,
+                y: center.y + size,
+            },
+            Point {
+                x: center.x - size,
+                y: center.y + size,
+            },
+        ])
+    }
+
+    #[test]
+    fn can_see_height() {
+        let receiver = riser()[4];
+        let point = Point { x: 200.0, y: 150.0 };
+        assert!(receiver.can_see(&point, 20.0));
+        assert!(receiver.can_see(&point, 25.0));
+        assert!(!receiver.can_see(&point, 40.0));
+        assert!(!receiver.can_see(&point, 0.0));
+    }
+
+    #[test]
+    fn estimate_matches_true_height() {
+        let mini = Point { x: 200.0, y: 150.0 };
+        let footprint = footprint(mini, 5.0);
+
+        for z in [0.0, 12.0, 37.5, 60.0] {
+            let observations: Vec<(ElevatedReceiver, bool)> = riser()
+                .into_iter()
+                .map(|r| (r, r.can_see(&mini, z)))
+                .collect();
+
+            let estimate = estimate_height(&observations, &footprint, Mm(100.0)).unwrap();
+            assert!(
+                (estimate.z - z).abs() <= estimate.uncertainty,
+                "{z} {estimate:?}"
+            );
+            assert!(estimate.uncertainty < 10.0, "{z} {estimate:?}");
+        }
+    }
+
+    #[test]
+    fn nothing_seen_on_the_riser() {
+        let mini = Point { x: 200.0, y: 150.0 };
+        let footprint = footprint(mini, 5.0);
+
+        // way above every receiver's vertical view, so only the negatives constrain it
+        let observations: Vec<(ElevatedReceiver, bool)> = riser()
+            .into_iter()
+            .map(|r| (r, r.can_see(&mini, 150.0)))
+            .collect();
+        assert!(observations.iter().all(|(_, seen)| !seen));
+
+        let estimate = estimate_height(&observations, &footprint, Mm(200.0)).unwrap();
+        assert!((estimate.z - 150.0).abs() <= estimate.uncertainty);
+        assert!(estimate.z - estimate.uncertainty > 95.0);
+
+        // a receiver that saw something no other one agrees with leaves nothing feasible
+        let mut contradiction = observations.clone();
+        contradiction[0].1 = true;
+        contradiction[19].1 = true;
+        assert!(estimate_height(&contradiction, &footprint, Mm(200.0)).is_none());
+    }
+}
@@ -0,0 +1,711 @@
+// Export of a table layout to the receiver board modules in KiCad (schematics_and_boards/table IR receivers and
+// emitters), and import of the receivers on a routed board back into a table layout.
+//
+// Receivers are split into boards per table edge, in the same counter clockwise chain order topology uses. Inside a
+// board the KiCad x axis runs along the chain (J1 in, J2 out) and every receiver sits on the same row looking off the
+// board edge, so only the x position changes between footprints.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::topology::edge_position;
+use crate::units::{Degrees, Mm};
+use crate::{Direction, Point, Receiver, Table};
+
+#[derive(Clone, Debug)]
+pub struct KicadExportOptions {
+    pub board_length: Mm,
+    // from the board start to the centre of its first receiver
+    pub first_receiver_offset: Mm,
+    // KiCad page position of the board start and of the receiver row
+    pub origin_x: Mm,
+    pub receiver_y: Mm,
+    // footprint rotation of a receiver looking straight off the board edge
+    pub facing_rotation: Degrees,
+    pub reference_prefix: String,
+    pub value: String,
+    pub package: String,
+}
+
+impl Default for KicadExportOptions {
+    // matches the 80mm receiver board as routed, Q1-Q8 on a 10mm pitch
+    fn default() -> Self {
+        Self {
+            board_length: Mm(80.0),
+            first_receiver_offset: Mm(5.0),
+            origin_x: Mm(100.33),
+            receiver_y: Mm(120.52),
+            facing_rotation: Degrees(90.0),
+            reference_prefix: "Q".to_string(),
+            value: "B17L1PT".to_string(),
+            package: "D_0805_2012Metric".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FootprintPlacement {
+    pub reference: String,
+    pub x: Mm,
+    pub y: Mm,
+    pub rotation: Degrees,
+    pub table_location: Point,
+    pub view_angle: Degrees,
+}
+
+#[derive(Clone, Debug)]
+pub struct BoardPlacement {
+    // position in the chain, same numbering as discovery
+    pub index: usize,
+    pub facing: Direction,
+    // how far along its edge the board starts, counter clockwise
+    pub start: Mm,
+    pub footprints: Vec<FootprintPlacement>,
+}
+
+// which edge (0 bottom, 1 right, 2 top, 3 left) the receiver is on and how far along it, counter clockwise
+fn edge_and_along(table: &Table, receiver: &Receiver) -> (usize, Mm) {
+    let width = table.table_bottom.point2.x;
+    let height = table.table_left.point2.y;
+    let location = receiver.location;
+
+    match receiver.facing {
+        Direction::Up => (0, Mm(location.x)),
+        Direction::Left => (1, Mm(location.y)),
+        Direction::Down => (2, Mm(width - location.x)),
+        Direction::Right => (3, Mm(height - location.y)),
+    }
+}
+
+pub fn board_placements(table: &Table, options: &KicadExportOptions) -> Vec<BoardPlacement> {
+    let mut receivers: Vec<(usize, Mm, &Receiver)> = table
+        .receivers
+        .iter()
+        .map(|r| {
+            let (edge, along) = edge_and_along(table, r);
+            (edge, along, r)
+        })
+        .collect();
+    receivers.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.partial_cmp(&b.1).unwrap()));
+
+    let usable = options.board_length - options.first_receiver_offset;
+    let mut boards: Vec<(usize, BoardPlacement)> = Vec::new();
+    for (edge, along, receiver) in receivers {
+        let needs_board = match boards.last() {
+            Some((board_edge, board)) => *board_edge != edge || along - board.start > usable,
+            None => true,
+        };
+        if needs_board {
+            boards.push((
+                edge,
+                BoardPlacement {
+                    index: boards.len(),
+                    facing: receiver.facing,
+                    start: along - options.first_receiver_offset,
+                    footprints: Vec::new(),
+                },
+            ));
+        }
+
+        let board = &mut boards.last_mut().unwrap().1;
+        board.footprints.push(FootprintPlacement {
+            reference: format!("{}{}", options.reference_prefix, board.footprints.len() + 1),
+            x: options.origin_x + (along - board.start),
+            y: options.receiver_y,
+            rotation: options.facing_rotation,
+            table_location: receiver.location,
+            view_angle: receiver.view_angle,
+        });
+    }
+
+    boards.into_iter().map(|(_, board)| board).collect()
+}
+
+// One line per receiver in the usual KiCad position file columns, plus where it ends up on the table
+pub fn placement_csv(boards: &[BoardPlacement], options: &KicadExportOptions) -> String {
+    let mut csv =
+        String::from("Board,Ref,Val,Package,PosX,PosY,Rot,Side,TableX,TableY,ViewAngle\n");
+    for board in boards {
+        for footprint in &board.footprints {
+            writeln!(
+                csv,
+                "{},{},{},{},{:.4},{:.4},{:.4},top,{:.4},{:.4},{:.4}",
+                board.index,
+                footprint.reference,
+                options.value,
+                options.package,
+                footprint.x.0,
+                footprint.y.0,
+                footprint.rotation.0,
+                footprint.table_location.x,
+                footprint.table_location.y,
+                footprint.view_angle.0,
+            )
+            .unwrap();
+        }
+    }
+
+    csv
+}
+
+// pcbnew script that moves the receiver footprints of an open board to where this layout wants them
+pub fn position_script(board: &BoardPlacement) -> String {
+    let mut script = String::new();
+    writeln!(script, "# board {} facing {:?}", board.index, board.facing).unwrap();
+    writeln!(
+        script,
+        "# run from the KiCad scripting console with the receiver board open"
+    )
+    .unwrap();
+    writeln!(script, "import pcbnew\n").unwrap();
+    writeln!(script, "positions = {{").unwrap();
+    for footprint in &board.footprints {
+        writeln!(
+            script,
+            "    \"{}\": ({:.4}, {:.4}, {:.4}),",
+            footprint.reference, footprint.x.0, footprint.y.0, footprint.rotation.0
+        )
+        .unwrap();
+    }
+    writeln!(script, "}}\n").unwrap();
+    script.push_str(
+        "board = pcbnew.GetBoard()
+for reference, (x, y, rotation) in positions.items():
+    footprint = board.FindFootprintByReference(reference)
+    if footprint is None:
+        print(\"missing footprint\", reference)
+        continue
+    footprint.SetPosition(pcbnew.VECTOR2I(pcbnew.FromMM(x), pcbnew.FromMM(y)))
+    footprint.SetOrientationDegrees(rotation)
+pcbnew.Refresh()
+",
+    );
+
+    script
+}
+
+// Writes placements.csv and one board_<n>.py per board into dir
+pub fn export(table: &Table, options: &KicadExportOptions, dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let boards = board_placements(table, options);
+    fs::write(dir.join("placements.csv"), placement_csv(&boards, options))?;
+    for board in &boards {
+        fs::write(
+            dir.join(format!("board_{}.py", board.index)),
+            position_script(board),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum KicadError {
+    UnexpectedEnd,
+    // byte offset into the file
+    UnexpectedClose(usize),
+    UnterminatedString(usize),
+    NotAPcb,
+    MissingField(&'static str),
+    BadNumber(String),
+    // receivers can only look straight off the board edge
+    UnsupportedRotation(String, f32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+impl Sexp {
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Sexp::Atom(atom) => Some(atom),
+            Sexp::List(_) => None,
+        }
+    }
+
+    // the items of a list that starts with the given keyword, e.g. (at 1 2 90)
+    fn keyword(&self, keyword: &str) -> Option<&[Sexp]> {
+        match self {
+            Sexp::List(items) if items.first()?.atom()? == keyword => Some(&items[1..]),
+            _ => None,
+        }
+    }
+
+    fn children<'a>(&'a self, keyword: &'a str) -> impl Iterator<Item = &'a [Sexp]> + 'a {
+        let items: &[Sexp] = match self {
+            Sexp::List(items) => items,
+            Sexp::Atom(_) => &[],
+        };
+        items.iter().filter_map(move |item| item.keyword(keyword))
+    }
+}
+
+fn parse_sexp(text: &str) -> Result<Sexp, KicadError> {
+    let bytes = text.as_bytes();
+    let mut stack: Vec<Vec<Sexp>> = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'(' => {
+                stack.push(Vec::new());
+                i += 1;
+            }
+            b')' => {
+                let list = stack.pop().ok_or(KicadError::UnexpectedClose(i))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.push(Sexp::List(list)),
+                    None => return Ok(Sexp::List(list)),
+                }
+                i += 1;
+            }
+            b'"' => {
+                let start = i;
+                let mut atom = Vec::new();
+                i += 1;
+                loop {
+                    match bytes.get(i) {
+                        None => return Err(KicadError::UnterminatedString(start)),
+                        Some(b'"') => break,
+                        Some(b'\\') if i + 1 < bytes.len() => {
+                            atom.push(bytes[i + 1]);
+                            i += 2;
+                        }
+                        Some(b) => {
+                            atom.push(*b);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                let atom = String::from_utf8_lossy(&atom).into_owned();
+                stack
+                    .last_mut()
+                    .ok_or(KicadError::NotAPcb)?
+                    .push(Sexp::Atom(atom));
+            }
+            b if b.is_ascii_whitespace() => i += 1,
+            _ => {
+                let start = i;
+                while i < bytes.len()
+                    && !bytes[i].is_ascii_whitespace()
+                    && !matches!(bytes[i], b'(' | b')' | b'"')
+                {
+                    i += 1;
+                }
+                stack
+                    .last_mut()
+                    .ok_or(KicadError::NotAPcb)?
+                    .push(Sexp::Atom(text[start..i].to_string()));
+            }
+        }
+    }
+
+    Err(KicadError::UnexpectedEnd)
+}
+
+fn parse_number(atom: Option<&Sexp>, field: &'static str) -> Result<f32, KicadError> {
+    let atom = atom
+        .and_then(Sexp::atom)
+        .ok_or(KicadError::MissingField(field))?;
+    atom.parse()
+        .map_err(|_| KicadError::BadNumber(atom.to_string()))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KicadFootprint {
+    pub reference: String,
+    // library id, e.g. "Diode_SMD:D_0805_2012Metric"
+    pub footprint: String,
+    pub layer: String,
+    pub x: Mm,
+    pub y: Mm,
+    pub rotation: Degrees,
+}
+
+// Every footprint on the board. Handles both the KiCad 6/7 layout (fp_text reference) and the KiCad 8 one (property
+// "Reference"), older files call footprints modules.
+pub fn parse_footprints(pcb: &str) -> Result<Vec<KicadFootprint>, KicadError> {
+    let root = parse_sexp(pcb)?;
+    if root.keyword("kicad_pcb").is_none() {
+        return Err(KicadError::NotAPcb);
+    }
+
+    let mut footprints = Vec::new();
+    for items in root.children("footprint").chain(root.children("module")) {
+        let node = Sexp::List(items.to_vec());
+
+        let footprint = items
+            .first()
+            .and_then(Sexp::atom)
+            .ok_or(KicadError::MissingField("footprint"))?
+            .to_string();
+        let layer = node
+            .children("layer")
+            .next()
+            .and_then(|layer| layer.first()?.atom())
+            .unwrap_or("F.Cu")
+            .to_string();
+
+        let reference = node
+            .children("fp_text")
+            .find(|text| text.first().and_then(Sexp::atom) == Some("reference"))
+            .and_then(|text| text.get(1)?.atom())
+            .or_else(|| {
+                node.children("property")
+                    .find(|property| property.first().and_then(Sexp::atom) == Some("Reference"))
+                    .and_then(|property| property.get(1)?.atom())
+            })
+            .ok_or(KicadError::MissingField("reference"))?
+            .to_string();
+
+        let at = node
+            .children("at")
+            .next()
+            .ok_or(KicadError::MissingField("at"))?;
+        let rotation = match at.get(2) {
+            Some(rotation) => parse_number(Some(rotation), "at rotation")?,
+            None => 0.0,
+        };
+
+        footprints.push(KicadFootprint {
+            reference,
+            footprint,
+            layer,
+            x: Mm(parse_number(at.first(), "at x")?),
+            y: Mm(parse_number(at.get(1), "at y")?),
+            rotation: Degrees(rotation),
+        });
+    }
+
+    Ok(footprints)
+}
+
+// reference prefix followed by the number, so "Q" picks up Q1 and Q12 but not QR1
+fn reference_number(reference: &str, prefix: &str) -> Option<u32> {
+    reference.strip_prefix(prefix)?.parse().ok()
+}
+
+// The receiver footprints on a board, in reference order
+pub fn receiver_footprints(pcb: &str, prefix: &str) -> Result<Vec<KicadFootprint>, KicadError> {
+    let mut footprints: Vec<(u32, KicadFootprint)> = parse_footprints(pcb)?
+        .into_iter()
+        .filter_map(|footprint| Some((reference_number(&footprint.reference, prefix)?, footprint)))
+        .collect();
+    footprints.sort_by_key(|(number, _)| *number);
+
+    Ok(footprints
+        .into_iter()
+        .map(|(_, footprint)| footprint)
+        .collect())
+}
+
+// Where one receiver board module is mounted: the edge it looks in from and how far along that edge (counter
+// clockwise, same as BoardPlacement::start) the board starts
+#[derive(Clone, Copy, Debug)]
+pub struct BoardMount {
+    pub facing: Direction,
+    pub start: Mm,
+}
+
+fn edge_index(facing: Direction) -> usize {
+    match facing {
+        Direction::Up => 0,
+        Direction::Left => 1,
+        Direction::Down => 2,
+        Direction::Right => 3,
+    }
+}
+
+// The inverse of board_placements: receivers for the footprints of one routed board, mounted on the table as given
+pub fn board_receivers(
+    footprints: &[KicadFootprint],
+    mount: BoardMount,
+    table_width: Mm,
+    table_height: Mm,
+    view_angle: Degrees,
+    options: &KicadExportOptions,
+) -> Result<Vec<Receiver>, KicadError> {
+    footprints
+        .iter()
+        .map(|footprint| {
+            let skew = (footprint.rotation - options.facing_rotation).normalized();
+            if skew.0 > 0.01 && skew.0 < 359.99 {
+                return Err(KicadError::UnsupportedRotation(
+                    footprint.reference.clone(),
+                    footprint.rotation.0,
+                ));
+            }
+
+            let along = mount.start + (footprint.x - options.origin_x);
+            let (location, facing) =
+                edge_position(table_width, table_height, edge_index(mount.facing), along);
+            Ok(Receiver::new(
+                table_width,
+                table_height,
+                view_angle,
+                location,
+                facing,
+            ))
+        })
+        .collect()
+}
+
+// A table with the same routed board at every mount
+pub fn table_from_pcb(
+    pcb: &str,
+    mounts: &[BoardMount],
+    table_width: Mm,
+    table_height: Mm,
+    view_angle: Degrees,
+    options: &KicadExportOptions,
+) -> Result<Table, KicadError> {
+    let footprints = receiver_footprints(pcb, &options.reference_prefix)?;
+
+    let mut receivers = Vec::new();
+    for mount in mounts {
+        receivers.extend(board_receivers(
+            &footprints,
+            *mount,
+            table_width,
+            table_height,
+            view_angle,
+            options,
+        )?);
+    }
+
+    Ok(Table::new(table_width, table_height, receivers))
+}
+
+#[cfg(test)]
+mod test {
+    use float_cmp::assert_approx_eq;
+
+    use super::*;
+
+    const TABLE_WIDTH: Mm = Mm(200.0);
+    const TABLE_HEIGHT: Mm = Mm(100.0);
+
+    // 16 receivers on a 10mm pitch along the bottom, 4 down the left side
+    fn table() -> Table {
+        let mut receivers = Vec::new();
+        for i in 0..16 {
+            receivers.push(Receiver::new(
+                TABLE_WIDTH,
+                TABLE_HEIGHT,
+                Degrees(20.0),
+                Point {
+                    x: 5.0 + 10.0 * i as f32,
+                    y: 0.0,
+                },
+                Direction::Up,
+            ));
+        }
+        for i in 0..4 {
+            receivers.push(Receiver::new(
+                TABLE_WIDTH,
+                TABLE_HEIGHT,
+                Degrees(30.0),
+                Point {
+                    x: 0.0,
+                    y: 20.0 + 20.0 * i as f32,
+                },
+                Direction::Right,
+            ));
+        }
+
+        Table::new(TABLE_WIDTH, TABLE_HEIGHT, receivers)
+    }
+
+    #[test]
+    fn split_into_boards() {
+        let options = KicadExportOptions::default();
+        let boards = board_placements(&table(), &options);
+
+        assert_eq!(boards.len(), 3);
+        assert_eq!(boards[0].footprints.len(), 8);
+        assert_eq!(boards[1].footprints.len(), 8);
+        assert!(matches!(boards[2].facing, Direction::Right));
+        assert_eq!(boards[2].footprints.len(), 4);
+
+        // a full board lands exactly where the footprints are on the routed board
+        for (i, footprint) in boards[1].footprints.iter().enumerate() {
+            assert_eq!(footprint.reference, format!("Q{}", i + 1));
+            assert_approx_eq!(
+                Mm,
+                footprint.x,
+                Mm(105.33 + 10.0 * i as f32),
+                epsilon = 0.001
+            );
+            assert_eq!(footprint.y, Mm(120.52));
+            assert_eq!(footprint.rotation, Degrees(90.0));
+        }
+
+        // the left edge runs top to bottom in chain order
+        let left = &boards[2].footprints;
+        assert_eq!(left[0].table_location, Point { x: 0.0, y: 80.0 });
+        assert_approx_eq!(Mm, left[1].x - left[0].x, Mm(20.0), epsilon = 0.001);
+    }
+
+    #[test]
+    fn csv_and_script() {
+        let options = KicadExportOptions::default();
+        let boards = board_placements(&table(), &options);
+
+        let csv = placement_csv(&boards, &options);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 1 + 20);
+        assert!(lines[0].starts_with("Board,Ref,Val,Package,PosX,PosY,Rot"));
+        assert_eq!(
+            lines[1],
+            "0,Q1,B17L1PT,D_0805_2012Metric,105.3300,120.5200,90.0000,top,5.0000,0.0000,20.0000"
+        );
+
+        let script = position_script(&boards[2]);
+        assert!(script.contains("import pcbnew"));
+        assert!(script.contains("\"Q4\": ("));
+        assert!(!script.contains("\"Q5\""));
+    }
+
+    fn board_file(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../schematics_and_boards")
+            .join(name)
+            .join(format!("{name}.kicad_pcb"));
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn parse_sexp_lists() {
+        let parsed = parse_sexp(r#"(a "b c" (d 1.5) "e \"f\"")"#).unwrap();
+        assert_eq!(
+            parsed,
+            Sexp::List(vec![
+                Sexp::Atom("a".to_string()),
+                Sexp::Atom("b c".to_string()),
+                Sexp::List(vec![
+                    Sexp::Atom("d".to_string()),
+                    Sexp::Atom("1.5".to_string())
+                ]),
+                Sexp::Atom("e \"f\"".to_string()),
+            ])
+        );
+
+        assert_eq!(parse_sexp("(a (b)"), Err(KicadError::UnexpectedEnd));
+        assert_eq!(parse_sexp(")"), Err(KicadError::UnexpectedClose(0)));
+        assert_eq!(parse_sexp("(a \"b"), Err(KicadError::UnterminatedString(3)));
+        assert_eq!(parse_footprints("(other)"), Err(KicadError::NotAPcb));
+        assert_eq!(
+            parse_footprints(
+                r#"(kicad_pcb (footprint "A:B" (layer "F.Cu") (at 1.5) (fp_text reference "Q1")))"#
+            ),
+            Err(KicadError::MissingField("at y"))
+        );
+    }
+
+    #[test]
+    fn receivers_on_routed_board() {
+        let pcb = board_file("table IR receivers and emitters");
+        let footprints = receiver_footprints(&pcb, "Q").unwrap();
+
+        assert_eq!(footprints.len(), 8);
+        for (i, footprint) in footprints.iter().enumerate() {
+            assert_eq!(footprint.reference, format!("Q{}", i + 1));
+            assert_eq!(footprint.footprint, "Diode_SMD:D_0805_2012Metric");
+            assert_eq!(footprint.layer, "F.Cu");
+            assert_approx_eq!(
+                Mm,
+                footprint.x,
+                Mm(105.33 + 10.0 * i as f32),
+                epsilon = 0.001
+            );
+            assert_eq!(footprint.y, Mm(120.52));
+            assert_eq!(footprint.rotation, Degrees(90.0));
+        }
+
+        // the emitters are on the same board under D
+        assert_eq!(receiver_footprints(&pcb, "D").unwrap().len(), 4);
+    }
+
+    #[test]
+    fn every_board_parses() {
+        for name in [
+            "table IR receivers and emitters",
+            "table board joiners",
+            "table board joiners panel",
+            "mini base",
+            "mini base framed",
+            "mini base v2",
+            "battery_mount",
+        ] {
+            let footprints = parse_footprints(&board_file(name)).unwrap();
+            assert!(!footprints.is_empty(), "{name}");
+        }
+
+        // KiCad 8 keeps the reference in a property
+        let footprints = parse_footprints(&board_file("mini base v2")).unwrap();
+        let u3 = footprints.iter().find(|f| f.reference == "U3").unwrap();
+        assert_eq!(
+            (u3.x, u3.y, u3.rotation),
+            (Mm(96.8), Mm(35.0), Degrees(-45.0))
+        );
+    }
+
+    #[test]
+    fn import_matches_export() {
+        let options = KicadExportOptions::default();
+        let pcb = board_file("table IR receivers and emitters");
+        let mounts = [
+            BoardMount {
+                facing: Direction::Up,
+                start: Mm(0.0),
+            },
+            BoardMount {
+                facing: Direction::Up,
+                start: Mm(80.0),
+            },
+        ];
+        let imported = table_from_pcb(
+            &pcb,
+            &mounts,
+            TABLE_WIDTH,
+            TABLE_HEIGHT,
+            Degrees(20.0),
+            &options,
+        )
+        .unwrap();
+
+        // two routed boards end to end are the 16 receivers along the bottom of the test table
+        let expected = table();
+        assert_eq!(imported.receivers.len(), 16);
+        for (imported, expected) in imported.receivers.iter().zip(expected.receivers.iter()) {
+            assert_approx_eq!(Point, imported.location, expected.location, epsilon = 0.001);
+            assert!(matches!(imported.facing, Direction::Up));
+        }
+
+        let boards = board_placements(&imported, &options);
+        assert_eq!(boards.len(), 2);
+        assert_eq!(boards[1].start, Mm(80.0));
+
+        let mut rotated = receiver_footprints(&pcb, "Q").unwrap();
+        rotated[3].rotation = Degrees(45.0);
+        assert_eq!(
+            board_receivers(
+                &rotated,
+                mounts[0],
+                TABLE_WIDTH,
+                TABLE_HEIGHT,
+                Degrees(20.0),
+                &options
+            )
+            .err(),
+            Some(KicadError::UnsupportedRotation("Q4".to_string(), 45.0))
+        );
+    }
+}
@@ -0,0 +1,175 @@
+// A table layout on disk: the table size and where every receiver sits. Stored as JSON so the simulator, optimizer and
+// visualizer can hand layouts to each other.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::units::{Degrees, Mm};
+use crate::{Direction, Point, Receiver, Table};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayoutFile {
+    pub width: Mm,
+    pub height: Mm,
+    #[serde(default)]
+    pub receivers: Vec<ReceiverLayout>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReceiverLayout {
+    pub x: Mm,
+    pub y: Mm,
+    pub facing: Direction,
+    pub view_angle: Degrees,
+}
+
+#[derive(Debug)]
+pub enum LayoutError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl std::fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutError::Io(e) => write!(f, "couldn't read or write layout: {e}"),
+            LayoutError::Json(e) => write!(f, "bad layout file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+impl From<io::Error> for LayoutError {
+    fn from(e: io::Error) -> Self {
+        LayoutError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for LayoutError {
+    fn from(e: serde_json::Error) -> Self {
+        LayoutError::Json(e)
+    }
+}
+
+impl LayoutFile {
+    pub fn from_table(table: &Table) -> Self {
+        Self {
+            width: Mm(table.table_bottom.point2.x),
+            height: Mm(table.table_left.point2.y),
+            receivers: table
+                .receivers
+                .iter()
+                .map(|r| ReceiverLayout {
+                    x: Mm(r.location.x),
+                    y: Mm(r.location.y),
+                    facing: r.facing,
+                    view_angle: r.view_angle,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_table(&self) -> Table {
+        let receivers = self
+            .receivers
+            .iter()
+            .map(|r| {
+                Receiver::new(
+                    self.width,
+                    self.height,
+                    r.view_angle,
+                    Point { x: r.x.0, y: r.y.0 },
+                    r.facing,
+                )
+            })
+            .collect();
+
+        Table::new(self.width, self.height, receivers)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, LayoutError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> String {
+        // nothing in a layout can fail to serialize
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LayoutError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), LayoutError> {
+        Ok(fs::write(path, self.to_json())?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let table = Table::new(
+            Mm(300.0),
+            Mm(200.0),
+            vec![
+                Receiver::new(
+                    Mm(300.0),
+                    Mm(200.0),
+                    Degrees(30.0),
+                    Point { x: 50.0, y: 0.0 },
+                    Direction::Up,
+                ),
+                Receiver::new(
+                    Mm(300.0),
+                    Mm(200.0),
+                    Degrees(45.0),
+                    Point { x: 0.0, y: 120.0 },
+                    Direction::Right,
+                ),
+            ],
+        );
+
+        let layout = LayoutFile::from_table(&table);
+        assert_eq!(layout.width, Mm(300.0));
+        assert_eq!(layout.height, Mm(200.0));
+
+        let loaded = LayoutFile::from_json(&layout.to_json()).unwrap();
+        assert_eq!(loaded, layout);
+
+        let rebuilt = loaded.to_table();
+        assert_eq!(rebuilt.receivers.len(), 2);
+        assert_eq!(rebuilt.receivers[1].location, Point { x: 0.0, y: 120.0 });
+        assert_eq!(rebuilt.receivers[1].view_angle, Degrees(45.0));
+        assert_eq!(
+            rebuilt.receivers[0].view_bound1,
+            table.receivers[0].view_bound1
+        );
+    }
+
+    #[test]
+    fn dimensions_only() {
+        let layout = LayoutFile::from_json(r#"{ "width": 980.8, "height": 573.8 }"#).unwrap();
+        assert!(layout.receivers.is_empty());
+        assert_eq!(layout.to_table().table_right.point1.x, 980.8);
+
+        let layout = LayoutFile::from_json(
+            r#"{ "width": 100, "height": 100, "receivers": [
+                { "x": 0, "y": 50, "facing": "Right", "view_angle": 20 }
+            ] }"#,
+        )
+        .unwrap();
+        assert!(matches!(layout.receivers[0].facing, Direction::Right));
+
+        assert!(matches!(
+            LayoutFile::from_json(r#"{ "width": 100 }"#),
+            Err(LayoutError::Json(_))
+        ));
+    }
+}
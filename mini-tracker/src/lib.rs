@@ -1,6 +1,20 @@
 use float_cmp::ApproxEq;
-
-#[derive(Clone, Copy, Debug)]
+use serde::{Deserialize, Serialize};
+use table_protocol::ObservationFrame;
+
+pub mod height;
+pub mod kicad;
+pub mod layout;
+pub mod orientation;
+pub mod polygon_ops;
+pub mod recording;
+pub mod strip_solver;
+pub mod topology;
+pub mod units;
+
+use units::{Degrees, Mm, Radians, BASE_DIAMETER};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
@@ -9,17 +23,17 @@ pub enum Direction {
 }
 
 impl Direction {
-    pub fn to_degrees(&self) -> f32 {
+    pub fn to_degrees(&self) -> Degrees {
         match self {
-            Direction::Up => 90.0,
-            Direction::Down => 270.0,
-            Direction::Left => 180.0,
-            Direction::Right => 0.0,
+            Direction::Up => Degrees(90.0),
+            Direction::Down => Degrees(270.0),
+            Direction::Left => Degrees(180.0),
+            Direction::Right => Degrees(0.0),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -55,6 +69,8 @@ pub struct Polygon {
 #[derive(Clone, Debug)]
 pub struct Table {
     pub receivers: Vec<Receiver>,
+    // how many receivers each strip has, in strip id order, see with_strips
+    pub strips: Vec<usize>,
     pub table_top: Line,
     pub table_bottom: Line,
     pub table_left: Line,
@@ -63,7 +79,7 @@ pub struct Table {
 
 #[derive(Clone, Copy, Debug)]
 pub struct Receiver {
-    pub view_angle: f32,
+    pub view_angle: Degrees,
     pub location: Point,
     pub facing: Direction,
     pub view_bound1: Line, // guaranteed to be longer than the bounds of the table
@@ -76,7 +92,8 @@ pub struct Receiver {
 }
 
 impl Table {
-    pub fn new(table_width: f32, table_height: f32, receivers: Vec<Receiver>) -> Self {
+    pub fn new(table_width: Mm, table_height: Mm, receivers: Vec<Receiver>) -> Self {
+        let (table_width, table_height) = (table_width.0, table_height.0);
         let table_top = Line::new(
             Point {
                 x: 0.0,
@@ -113,6 +130,7 @@ impl Table {
         );
 
         Self {
+            strips: vec![receivers.len()],
             receivers,
             table_top,
             table_bottom,
@@ -120,6 +138,14 @@ impl Table {
             table_right,
         }
     }
+    // The receivers are stored strip by strip and these are how many each strip has, in strip id order. Until this is
+    // called the whole table counts as one strip.
+    pub fn with_strips(mut self, strips: Vec<usize>) -> Self {
+        assert_eq!(strips.iter().sum::<usize>(), self.receivers.len());
+        self.strips = strips;
+        self
+    }
+
     pub fn send_sync(&self) {}
 
     fn receivers_can_see_estimated(&self, receivers: &[&Receiver], point: &Point) -> bool {
@@ -213,6 +239,43 @@ impl Table {
         Some(bounds)
     }
 
+    // The frames from every strip for one slot, laid end to end in strip id order to line up with self.receivers. None
+    // unless there's exactly one frame for each strip, each the size of its strip.
+    pub fn observations_from_frames(
+        &self,
+        frames: &[ObservationFrame],
+    ) -> Option<Vec<(Receiver, bool)>> {
+        let slot = frames.first()?.slot;
+        if frames.iter().any(|f| f.slot != slot) {
+            return None;
+        }
+
+        let mut frames = frames.to_vec();
+        frames.sort_by_key(|f| f.strip_id);
+
+        if frames.len() != self.strips.len() {
+            return None;
+        }
+        for (id, (frame, count)) in frames.iter().zip(&self.strips).enumerate() {
+            if frame.strip_id as usize != id || frame.receiver_count() != *count {
+                return None;
+            }
+        }
+
+        Some(
+            self.receivers
+                .iter()
+                .copied()
+                .zip(frames.iter().flat_map(|f| f.observations()))
+                .collect(),
+        )
+    }
+
+    pub fn get_bounding_polygon_from_frames(&self, frames: &[ObservationFrame]) -> Option<Polygon> {
+        let receivers = self.observations_from_frames(frames)?;
+        self.get_bounding_polygon(&receivers)
+    }
+
     pub fn get_location(&self, receivers: &[(&Receiver, bool)]) -> (Point, f32) {
         let point_margin = float_cmp::F32Margin::default().epsilon(0.0001);
 
@@ -307,15 +370,34 @@ impl Table {
 
 impl Receiver {
     pub fn new(
-        table_width: f32,
-        table_height: f32,
-        view_angle: f32,
+        table_width: Mm,
+        table_height: Mm,
+        view_angle: Degrees,
+        location: Point,
+        facing: Direction,
+    ) -> Self {
+        Self::with_footprint(
+            table_width,
+            table_height,
+            view_angle,
+            location,
+            facing,
+            BASE_DIAMETER,
+        )
+    }
+
+    // the expanded view bounds leave room for a base of the given diameter
+    pub fn with_footprint(
+        table_width: Mm,
+        table_height: Mm,
+        view_angle: Degrees,
         location: Point,
         facing: Direction,
+        footprint: Mm,
     ) -> Self {
-        let angle1 = (facing.to_degrees() + (view_angle) / 2.0).to_radians();
-        let angle2 = (facing.to_degrees() - (view_angle) / 2.0).to_radians();
-        let distance = (table_width + table_height) * 2.0;
+        let angle1 = (facing.to_degrees() + view_angle / 2.0).to_radians();
+        let angle2 = (facing.to_degrees() - view_angle / 2.0).to_radians();
+        let distance = (table_width + table_height).0 * 2.0;
         let far_point1 = Point {
             x: (distance * angle1.cos()) + location.x,
             y: (distance * angle1.sin()) + location.y,
@@ -328,8 +410,8 @@ impl Receiver {
         let too_long1 = Line::new(location, far_point1);
         let too_long2 = Line::new(location, far_point2);
 
-        let expanded_view_bound1 = too_long1.parallel_line(25.4, true);
-        let expanded_view_bound2 = too_long2.parallel_line(25.4, false);
+        let expanded_view_bound1 = too_long1.parallel_line(footprint, true);
+        let expanded_view_bound2 = too_long2.parallel_line(footprint, false);
         let expanded_view_location = expanded_view_bound1
             .intersection(&expanded_view_bound2, false)
             .unwrap();
@@ -351,13 +433,13 @@ impl Receiver {
 
         match self.facing {
             Direction::Up | Direction::Left | Direction::Down => {
-                if angle < 0.0 {
-                    angle += 360.0;
+                if angle < Degrees(0.0) {
+                    angle += Degrees(360.0);
                 }
-                angle >= self.facing.to_degrees() - (self.view_angle / 2.0) - 0.01
-                    && angle <= self.facing.to_degrees() + (self.view_angle / 2.0) + 0.01
+                angle >= self.facing.to_degrees() - (self.view_angle / 2.0) - Degrees(0.01)
+                    && angle <= self.facing.to_degrees() + (self.view_angle / 2.0) + Degrees(0.01)
             }
-            Direction::Right => angle.abs() <= (self.view_angle / 2.0) + 0.01,
+            Direction::Right => angle.abs() <= (self.view_angle / 2.0) + Degrees(0.01),
         }
     }
 
@@ -366,13 +448,13 @@ impl Receiver {
 
         match self.facing {
             Direction::Up | Direction::Left | Direction::Down => {
-                if angle < 0.0 {
-                    angle += 360.0;
+                if angle < Degrees(0.0) {
+                    angle += Degrees(360.0);
                 }
-                angle >= self.facing.to_degrees() - (self.view_angle / 2.0) - 0.01
-                    && angle <= self.facing.to_degrees() + (self.view_angle / 2.0) + 0.01
+                angle >= self.facing.to_degrees() - (self.view_angle / 2.0) - Degrees(0.01)
+                    && angle <= self.facing.to_degrees() + (self.view_angle / 2.0) + Degrees(0.01)
             }
-            Direction::Right => angle.abs() <= (self.view_angle / 2.0) + 0.01,
+            Direction::Right => angle.abs() <= (self.view_angle / 2.0) + Degrees(0.01),
         }
     }
 
@@ -381,13 +463,13 @@ impl Receiver {
 
         match self.facing {
             Direction::Up | Direction::Left | Direction::Down => {
-                if angle < 0.0 {
-                    angle += 360.0;
+                if angle < Degrees(0.0) {
+                    angle += Degrees(360.0);
                 }
-                !(angle >= self.facing.to_degrees() - (self.view_angle / 2.0) + 0.01
-                    && angle <= self.facing.to_degrees() + (self.view_angle / 2.0) - 0.01)
+                !(angle >= self.facing.to_degrees() - (self.view_angle / 2.0) + Degrees(0.01)
+                    && angle <= self.facing.to_degrees() + (self.view_angle / 2.0) - Degrees(0.01))
             }
-            Direction::Right => !(angle.abs() <= (self.view_angle / 2.0) - 0.01),
+            Direction::Right => !(angle.abs() <= (self.view_angle / 2.0) - Degrees(0.01)),
         }
     }
 }
@@ -397,7 +479,7 @@ impl Point {
         ((self.x - other.x).powf(2.0) + (self.y - other.y).powf(2.0)).sqrt()
     }
 
-    pub fn rotate_around_origin(&self, angle: f32) -> Self {
+    pub fn rotate_around_origin(&self, angle: Radians) -> Self {
         let orig_x = self.x;
         let orig_y = self.y;
         let x = orig_x * angle.cos() - orig_y * angle.sin();
@@ -405,18 +487,18 @@ impl Point {
         Point { x, y }
     }
 
-    pub fn angle(&self, other: &Self) -> f32 {
+    pub fn angle(&self, other: &Self) -> Degrees {
         let x = other.x - self.x;
         let y = other.y - self.y;
 
-        y.atan2(x).to_degrees()
+        Radians::atan2(y, x).to_degrees()
     }
 
-    pub fn angle_from_origin(&self) -> f32 {
+    pub fn angle_from_origin(&self) -> Degrees {
         let x = self.x;
         let y = self.y;
 
-        y.atan2(x).to_degrees()
+        Radians::atan2(y, x).to_degrees()
     }
 }
 
@@ -497,17 +579,18 @@ impl Line {
         Some(Point { x, y })
     }
 
-    pub fn parallel_line(&self, distance: f32, left: bool) -> Line {
+    pub fn parallel_line(&self, distance: Mm, left: bool) -> Line {
         let mut angle = self.point1.angle(&self.point2);
-        if angle < 0.0 {
-            angle += 360.0;
+        if angle < Degrees(0.0) {
+            angle += Degrees(360.0);
         }
         if left {
-            angle += 90.0
+            angle += Degrees(90.0)
         } else {
-            angle -= 90.0
+            angle -= Degrees(90.0)
         }
 
+        let distance = distance.0;
         let rad_angle = angle.to_radians();
         let new_point1 = Point {
             x: (distance * rad_angle.cos()) + self.point1.x,
@@ -532,14 +615,8 @@ fn order_points_clockwise(points: &mut [Point]) {
     };
 
     points.sort_by(|a, b| {
-        let mut angle_a = centroid.angle(a);
-        if angle_a < 0.0 {
-            angle_a += 360.0;
-        }
-        let mut angle_b = centroid.angle(b);
-        if angle_b < 0.0 {
-            angle_b += 360.0;
-        }
+        let angle_a = centroid.angle(a).normalized();
+        let angle_b = centroid.angle(b).normalized();
         angle_b.partial_cmp(&angle_a).unwrap().then_with(|| {
             b.distance(&centroid)
                 .partial_cmp(&a.distance(&centroid))
@@ -632,7 +709,7 @@ impl Polygon {
         }
     }
 
-    pub fn get_shrink_lines(&self, size: f32) -> Self {
+    pub fn get_shrink_lines(&self, size: Mm) -> Self {
         let mut tmp_lines = Vec::new();
         for line in self.lines.iter() {
             tmp_lines.push(line.parallel_line(size, false));
@@ -658,7 +735,7 @@ impl Polygon {
         }
     }
 
-    pub fn shrink(&self, size: f32) -> Option<Self> {
+    pub fn shrink(&self, size: Mm) -> Option<Self> {
         let mut tmp_lines = Vec::new();
         for line in self.lines.iter() {
             tmp_lines.push(line.parallel_line(size, false));
@@ -747,7 +824,7 @@ impl Polygon {
             };
 
             let angle = prev_point.angle(point) - point.angle(next_point);
-            if angle.approx_eq(0.0, float_cmp::F32Margin::default().epsilon(0.01)) {
+            if angle.approx_eq(Degrees(0.0), float_cmp::F32Margin::default().epsilon(0.01)) {
                 self.points.remove(i);
             } else {
                 i += 1;
@@ -807,6 +884,22 @@ impl Polygon {
         Some((Self::new(&poly1), Self::new(&poly2)))
     }
 
+    // https://en.wikipedia.org/wiki/Point_in_polygon#Ray_casting_algorithm
+    pub fn contains(&self, point: &Point) -> bool {
+        let mut inside = false;
+        for line in self.lines.iter() {
+            let (a, b) = (line.point1, line.point2);
+            if (a.y > point.y) != (b.y > point.y) {
+                let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+                if point.x < x {
+                    inside = !inside;
+                }
+            }
+        }
+
+        inside
+    }
+
     pub fn above_line(&self, line: &Line) -> bool {
         for point in self.points.iter() {
             let line_y = line.m * point.x + line.b;
@@ -826,6 +919,7 @@ mod test {
     use float_cmp::assert_approx_eq;
 
     use super::*;
+    use crate::units::MM_PER_INCH;
 
     #[test]
     fn distance() {
@@ -841,36 +935,36 @@ mod test {
         let point = Point { x: 100.0, y: 100.0 };
 
         let up = Receiver::new(
-            200.0,
-            200.0,
-            45.0,
+            Mm(200.0),
+            Mm(200.0),
+            Degrees(45.0),
             Point { x: 100.0, y: 0.0 },
             Direction::Up,
         );
         assert!(up.can_see(&point));
 
         let right = Receiver::new(
-            200.0,
-            200.0,
-            45.0,
+            Mm(200.0),
+            Mm(200.0),
+            Degrees(45.0),
             Point { x: 0.0, y: 100.0 },
             Direction::Right,
         );
         assert!(right.can_see(&point));
 
         let down = Receiver::new(
-            200.0,
-            200.0,
-            45.0,
+            Mm(200.0),
+            Mm(200.0),
+            Degrees(45.0),
             Point { x: 100.0, y: 200.0 },
             Direction::Down,
         );
         assert!(down.can_see(&point));
 
         let left = Receiver::new(
-            200.0,
-            200.0,
-            45.0,
+            Mm(200.0),
+            Mm(200.0),
+            Degrees(45.0),
             Point { x: 200.0, y: 100.0 },
             Direction::Left,
         );
@@ -882,36 +976,36 @@ mod test {
         let point = Point { x: 100.0, y: 100.0 };
 
         let up = Receiver::new(
-            200.0,
-            200.0,
-            45.0,
+            Mm(200.0),
+            Mm(200.0),
+            Degrees(45.0),
             Point { x: 202.0, y: 0.0 },
             Direction::Up,
         );
         assert!(!up.can_see(&point));
 
         let right = Receiver::new(
-            200.0,
-            200.0,
-            45.0,
+            Mm(200.0),
+            Mm(200.0),
+            Degrees(45.0),
             Point { x: 0.0, y: 202.0 },
             Direction::Right,
         );
         assert!(!right.can_see(&point));
 
         let down = Receiver::new(
-            200.0,
-            200.0,
-            45.0,
+            Mm(200.0),
+            Mm(200.0),
+            Degrees(45.0),
             Point { x: 202.0, y: 200.0 },
             Direction::Down,
         );
         assert!(!down.can_see(&point));
 
         let left = Receiver::new(
-            200.0,
-            200.0,
-            45.0,
+            Mm(200.0),
+            Mm(200.0),
+            Degrees(45.0),
             Point { x: 200.0, y: 202.0 },
             Direction::Left,
         );
@@ -922,7 +1016,7 @@ mod test {
     fn rotate_around_origin() {
         let point = Point { x: 2.0, y: 2.0 };
 
-        let rotated = point.rotate_around_origin(90.0 * (std::f32::consts::PI / 180.0));
+        let rotated = point.rotate_around_origin(Degrees(90.0).to_radians());
         assert_approx_eq!(f32, rotated.x, -2.0, epsilon = 0.00001);
         assert_approx_eq!(f32, rotated.y, 2.0, epsilon = 0.00001);
     }
@@ -932,14 +1026,14 @@ mod test {
         let origin = Point { x: 0.0, y: 0.0 };
         let point = Point { x: 8.0, y: 8.0 };
 
-        assert_eq!(origin.angle(&point), 45.0)
+        assert_eq!(origin.angle(&point), Degrees(45.0))
     }
 
     #[test]
     fn angle_from_origin() {
         let point = Point { x: 8.0, y: 8.0 };
 
-        assert_eq!(point.angle_from_origin(), 45.0)
+        assert_eq!(point.angle_from_origin(), Degrees(45.0))
     }
 
     #[test]
@@ -971,9 +1065,8 @@ mod test {
 
     #[test]
     fn receivers_can_see_own_intersections() {
-        const MM_PER_INCH: f32 = 25.4;
-        const TABLE_WIDTH: f32 = 930.0;
-        const TABLE_HEIGHT: f32 = 523.0;
+        const TABLE_WIDTH: Mm = Mm(930.0);
+        const TABLE_HEIGHT: Mm = Mm(523.0);
 
         let point_margin = float_cmp::F32Margin::default().epsilon(0.0001);
 
@@ -982,19 +1075,22 @@ mod test {
 
         // top/bottom
         let mut x = MM_PER_INCH / 2.0;
-        while x < TABLE_WIDTH {
+        while x < TABLE_WIDTH.0 {
             receivers.push(Receiver::new(
                 TABLE_WIDTH,
                 TABLE_HEIGHT,
-                30.0,
+                Degrees(30.0),
                 Point { x, y: 0.0 },
                 Direction::Up,
             ));
             receivers.push(Receiver::new(
                 TABLE_WIDTH,
                 TABLE_HEIGHT,
-                30.0,
-                Point { x, y: TABLE_HEIGHT },
+                Degrees(30.0),
+                Point {
+                    x,
+                    y: TABLE_HEIGHT.0,
+                },
                 Direction::Down,
             ));
 
@@ -1003,19 +1099,22 @@ mod test {
 
         // left/right
         let mut y = MM_PER_INCH / 2.0;
-        while y < TABLE_HEIGHT {
+        while y < TABLE_HEIGHT.0 {
             receivers.push(Receiver::new(
                 TABLE_WIDTH,
                 TABLE_HEIGHT,
-                20.0,
+                Degrees(20.0),
                 Point { x: 0.0, y },
                 Direction::Right,
             ));
             receivers.push(Receiver::new(
                 TABLE_WIDTH,
                 TABLE_HEIGHT,
-                20.0,
-                Point { x: TABLE_WIDTH, y },
+                Degrees(20.0),
+                Point {
+                    x: TABLE_WIDTH.0,
+                    y,
+                },
                 Direction::Left,
             ));
 
@@ -1058,6 +1157,90 @@ mod test {
         }
     }
 
+    #[test]
+    fn bounding_polygon_from_frames() {
+        const TABLE_WIDTH: Mm = Mm(400.0);
+        const TABLE_HEIGHT: Mm = Mm(300.0);
+
+        // bottom strip then left strip
+        let mut receivers = Vec::new();
+        let mut x = 10.0;
+        while x < TABLE_WIDTH.0 {
+            receivers.push(Receiver::new(
+                TABLE_WIDTH,
+                TABLE_HEIGHT,
+                Degrees(20.0),
+                Point { x, y: 0.0 },
+                Direction::Up,
+            ));
+            x += 20.0;
+        }
+        let bottom_count = receivers.len();
+        let mut y = 10.0;
+        while y < TABLE_HEIGHT.0 {
+            receivers.push(Receiver::new(
+                TABLE_WIDTH,
+                TABLE_HEIGHT,
+                Degrees(20.0),
+                Point { x: 0.0, y },
+                Direction::Right,
+            ));
+            y += 20.0;
+        }
+
+        let strips = vec![bottom_count, receivers.len() - bottom_count];
+        let table = Table::new(TABLE_WIDTH, TABLE_HEIGHT, receivers).with_strips(strips);
+        let mini = Point { x: 150.0, y: 120.0 };
+        let observations: Vec<(Receiver, bool)> = table
+            .receivers
+            .iter()
+            .map(|r| (*r, r.can_see(&mini)))
+            .collect();
+
+        let seen: Vec<bool> = observations.iter().map(|(_, v)| *v).collect();
+        let left =
+            ObservationFrame::from_observations(1, 7, 0, seen[bottom_count..].iter().copied())
+                .unwrap();
+        let bottom =
+            ObservationFrame::from_observations(0, 7, 0, seen[..bottom_count].iter().copied())
+                .unwrap();
+
+        // frame order on the wire shouldn't matter
+        let from_frames = table.observations_from_frames(&[left, bottom]).unwrap();
+        assert!(from_frames
+            .iter()
+            .zip(observations.iter())
+            .all(|((a, a_seen), (b, b_seen))| a.location == b.location && a_seen == b_seen));
+
+        let expected = table.get_bounding_polygon(&observations).unwrap();
+        let polygon = table
+            .get_bounding_polygon_from_frames(&[left, bottom])
+            .unwrap();
+        assert_eq!(polygon.points, expected.points);
+
+        // missing strip or mixed slots can't be lined up with the table
+        assert!(table.observations_from_frames(&[bottom]).is_none());
+        let mut other_slot = left;
+        other_slot.slot = 8;
+        assert!(table
+            .observations_from_frames(&[bottom, other_slot])
+            .is_none());
+
+        // the same strip twice
+        let mut again = bottom;
+        again.strip_id = 1;
+        assert!(table.observations_from_frames(&[bottom, again]).is_none());
+        assert!(table.observations_from_frames(&[bottom, bottom]).is_none());
+
+        // the right total, split differently between the strips
+        let split = bottom_count + 1;
+        let bottom =
+            ObservationFrame::from_observations(0, 7, 0, seen[..split].iter().copied()).unwrap();
+        let left =
+            ObservationFrame::from_observations(1, 7, 0, seen[split..].iter().copied()).unwrap();
+        assert!(table.observations_from_frames(&[bottom, left]).is_none());
+    }
+
     #[test]
     fn area_of_square() {
         let points = [
@@ -1072,6 +1255,20 @@ mod test {
         assert_approx_eq!(f32, polygon.area(), 100.0, epsilon = 0.00001);
     }
 
+    #[test]
+    fn polygon_contains() {
+        let polygon = Polygon::new(&[
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 10.0, y: 0.0 },
+            Point { x: 10.0, y: 10.0 },
+            Point { x: 0.0, y: 10.0 },
+        ]);
+
+        assert!(polygon.contains(&Point { x: 5.0, y: 5.0 }));
+        assert!(!polygon.contains(&Point { x: 15.0, y: 5.0 }));
+        assert!(!polygon.contains(&Point { x: 5.0, y: -1.0 }));
+    }
+
     #[test]
     fn intersect() {
         let line1 = Line {
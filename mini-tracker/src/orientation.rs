@@ -0,0 +1,283 @@
+use crate::units::{Degrees, Mm, Radians};
+use crate::{Point, Polygon};
+
+// Where the emitters sit on a mini's base, in mm from the centre of the base with the mini facing along +x. The order
+// matches the emitter order of mini_mount::MultiEmitterMini, so offset i flashes in address i's slot.
+#[derive(Clone, Debug)]
+pub struct EmitterArrangement {
+    pub offsets: Vec<Point>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pose {
+    pub location: Point,
+    // counter clockwise from +x, same convention as Direction::to_degrees
+    pub facing: Degrees,
+    pub location_uncertainty: Mm,
+    pub facing_uncertainty: Degrees,
+}
+
+impl EmitterArrangement {
+    pub fn new(offsets: &[Point]) -> Self {
+        Self {
+            offsets: offsets.to_vec(),
+        }
+    }
+
+    // where each emitter ends up for a mini at location facing the given way
+    pub fn emitter_locations(&self, location: Point, facing: Degrees) -> Vec<Point> {
+        self.offsets
+            .iter()
+            .map(|offset| {
+                let rotated = offset.rotate_around_origin(facing.to_radians());
+                Point {
+                    x: location.x + rotated.x,
+                    y: location.y + rotated.y,
+                }
+            })
+            .collect()
+    }
+
+    // Fits the arrangement to the regions solved for each emitter (None where an emitter wasn't located) with a
+    // weighted 2D Procrustes fit, emitters with tighter regions count for more. Needs at least two located emitters
+    // since one on its own says nothing about facing.
+    pub fn solve(&self, regions: &[Option<Polygon>]) -> Option<Pose> {
+        assert_eq!(regions.len(), self.offsets.len());
+
+        let located: Vec<(Point, Point, f32)> = self
+            .offsets
+            .iter()
+            .zip(regions.iter())
+            .filter_map(|(offset, region)| {
+                let region = region.as_ref()?;
+                let center = region.center();
+                let radius = region
+                    .points
+                    .iter()
+                    .map(|p| center.distance(p))
+                    .fold(0.0, f32::max);
+                Some((*offset, center, radius))
+            })
+            .collect();
+
+        if located.len() < 2 {
+            return None;
+        }
+
+        // keep a perfect fix from taking all of the weight
+        let weights: Vec<f32> = located
+            .iter()
+            .map(|(_, _, radius)| 1.0 / (radius * radius + 1.0))
+            .collect();
+        let total_weight: f32 = weights.iter().sum();
+
+        let weighted_mean = |points: &mut dyn Iterator<Item = Point>| {
+            let (mut x, mut y) = (0.0, 0.0);
+            for (point, weight) in points.zip(weights.iter()) {
+                x += point.x * weight;
+                y += point.y * weight;
+            }
+            Point {
+                x: x / total_weight,
+                y: y / total_weight,
+            }
+        };
+        let offset_mean = weighted_mean(&mut located.iter().map(|(o, _, _)| *o));
+        let estimate_mean = weighted_mean(&mut located.iter().map(|(_, e, _)| *e));
+
+        let mut dot = 0.0;
+        let mut cross = 0.0;
+        for ((offset, estimate, _), weight) in located.iter().zip(weights.iter()) {
+            let (ox, oy) = (offset.x - offset_mean.x, offset.y - offset_mean.y);
+            let (ex, ey) = (estimate.x - estimate_mean.x, estimate.y - estimate_mean.y);
+            dot += weight * (ox * ex + oy * ey);
+            cross += weight * (ox * ey - oy * ex);
+        }
+        let facing = Radians::atan2(cross, dot);
+
+        let rotated_mean = offset_mean.rotate_around_origin(facing);
+        let location = Point {
+            x: estimate_mean.x - rotated_mean.x,
+            y: estimate_mean.y - rotated_mean.y,
+        };
+
+        // facing is only as good as the widest baseline between two located emitters can pin it down
+        let mut facing_uncertainty = std::f32::consts::PI;
+        for (i, (offset_a, _, radius_a)) in located.iter().enumerate() {
+            for (offset_b, _, radius_b) in located.iter().skip(i + 1) {
+                let baseline = offset_a.distance(offset_b);
+                if baseline > 0.0 {
+                    let uncertainty = ((radius_a + radius_b) / baseline).atan();
+                    facing_uncertainty = facing_uncertainty.min(uncertainty);
+                }
+            }
+        }
+
+        // the centre of the base swings with the facing error around the emitters it was fit to
+        let max_radius = located.iter().map(|(_, _, r)| *r).fold(0.0, f32::max);
+        let lever = located
+            .iter()
+            .map(|(offset, _, _)| offset.distance(&Point { x: 0.0, y: 0.0 }))
+            .fold(0.0, f32::max);
+
+        Some(Pose {
+            location,
+            facing: facing.to_degrees().normalized(),
+            location_uncertainty: Mm(max_radius + lever * facing_uncertainty.tan().min(1.0)),
+            facing_uncertainty: Radians(facing_uncertainty).to_degrees(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::units::BASE_DIAMETER;
+    use crate::{Direction, Receiver, Table};
+
+    fn square(center: Point, size: f32) -> Polygon {
+        Polygon::new(&[
+            Point {
+                x: center.x - size,
+                y: center.y - size,
+            },
+            Point {
+                x: center.x + size,
+                y: center.y - size,
+            },
+            Point {
+                x: center.x + size,
+                y: center.y + size,
+            },
+            Point {
+                x: center.x - size,
+                y: center.y + size,
+            },
+        ])
+    }
+
+    fn angle_difference(a: Degrees, b: Degrees) -> Degrees {
+        let difference = (a - b).normalized();
+        difference.min(Degrees(360.0) - difference)
+    }
+
+    #[test]
+    fn exact_regions() {
+        let arrangement = EmitterArrangement::new(&[
+            Point { x: 20.0, y: 0.0 },
+            Point { x: -10.0, y: 10.0 },
+            Point { x: -10.0, y: -10.0 },
+        ]);
+        let location = Point { x: 300.0, y: 200.0 };
+
+        for facing in [0.0, 45.0, 135.0, 270.0, 350.0].map(Degrees) {
+            let regions: Vec<Option<Polygon>> = arrangement
+                .emitter_locations(location, facing)
+                .into_iter()
+                .map(|p| Some(square(p, 1.0)))
+                .collect();
+
+            let pose = arrangement.solve(&regions).unwrap();
+            assert!(pose.location.distance(&location) < 0.1, "{pose:?}");
+            assert!(
+                angle_difference(pose.facing, facing) < Degrees(0.5),
+                "{pose:?}"
+            );
+            assert!(pose.facing_uncertainty < Degrees(10.0));
+        }
+    }
+
+    #[test]
+    fn missing_emitters() {
+        let arrangement =
+            EmitterArrangement::new(&[Point { x: 15.0, y: 0.0 }, Point { x: -15.0, y: 0.0 }]);
+        let location = Point { x: 100.0, y: 100.0 };
+        let emitters = arrangement.emitter_locations(location, Degrees(90.0));
+
+        assert!(arrangement
+            .solve(&[Some(square(emitters[0], 2.0)), None])
+            .is_none());
+
+        let pose = arrangement
+            .solve(&[
+                Some(square(emitters[0], 2.0)),
+                Some(square(emitters[1], 2.0)),
+            ])
+            .unwrap();
+        assert!(angle_difference(pose.facing, Degrees(90.0)) < Degrees(0.5));
+    }
+
+    #[test]
+    fn solve_from_table() {
+        const TABLE_WIDTH: Mm = Mm(600.0);
+        const TABLE_HEIGHT: Mm = Mm(400.0);
+
+        let mut receivers = Vec::new();
+        let mut x = 5.0;
+        while x < TABLE_WIDTH.0 {
+            for (y, facing) in [(0.0, Direction::Up), (TABLE_HEIGHT.0, Direction::Down)] {
+                receivers.push(Receiver::new(
+                    TABLE_WIDTH,
+                    TABLE_HEIGHT,
+                    Degrees(10.0),
+                    Point { x, y },
+                    facing,
+                ));
+            }
+            x += 10.0;
+        }
+        let mut y = 5.0;
+        while y < TABLE_HEIGHT.0 {
+            for (x, facing) in [(0.0, Direction::Right), (TABLE_WIDTH.0, Direction::Left)] {
+                receivers.push(Receiver::new(
+                    TABLE_WIDTH,
+                    TABLE_HEIGHT,
+                    Degrees(10.0),
+                    Point { x, y },
+                    facing,
+                ));
+            }
+            y += 10.0;
+        }
+        let table = Table::new(TABLE_WIDTH, TABLE_HEIGHT, receivers);
+
+        // emitters at the front and back of a 50mm base
+        let arrangement =
+            EmitterArrangement::new(&[Point { x: 20.0, y: 0.0 }, Point { x: -20.0, y: 0.0 }]);
+        let location = Point { x: 250.0, y: 180.0 };
+        let facing = Degrees(60.0);
+
+        let regions: Vec<Option<Polygon>> = arrangement
+            .emitter_locations(location, facing)
+            .iter()
+            .map(|emitter| {
+                // the table solver assumes the light comes off a one inch disc, same as the simulator
+                let edge_points: Vec<Point> = (0..360)
+                    .map(|angle| {
+                        let angle = (angle as f32).to_radians();
+                        Point {
+                            x: emitter.x + BASE_DIAMETER.0 / 2.0 * angle.cos(),
+                            y: emitter.y + BASE_DIAMETER.0 / 2.0 * angle.sin(),
+                        }
+                    })
+                    .collect();
+                let observations: Vec<_> = table
+                    .receivers
+                    .iter()
+                    .map(|r| (*r, edge_points.iter().any(|p| r.can_see(p))))
+                    .collect();
+                table.get_bounding_polygon(&observations)
+            })
+            .collect();
+
+        let pose = arrangement.solve(&regions).unwrap();
+        assert!(
+            Mm(pose.location.distance(&location)) <= pose.location_uncertainty,
+            "{pose:?}"
+        );
+        assert!(
+            angle_difference(pose.facing, facing) <= pose.facing_uncertainty,
+            "{pose:?}"
+        );
+    }
+}
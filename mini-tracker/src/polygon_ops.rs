@@ -0,0 +1,495 @@
+// Boolean operations and offsetting for the convex regions the solvers produce.
+//
+// Everything here works on the convex hull of a polygon's points, which is what every bounding polygon is anyway, and
+// is built out of one primitive: clipping a convex polygon against a half plane. That keeps the results well behaved
+// for colinear, duplicate and touching inputs, where general polygon clippers tend to fall over. Operations whose
+// result isn't convex (difference, union) hand back disjoint convex pieces instead of a single polygon.
+
+use crate::units::Mm;
+use crate::{Point, Polygon};
+
+// Points closer than this (in mm) are treated as the same point, and a point this close to a line is on it
+const EPSILON: f32 = 1e-3;
+// Much tighter than EPSILON so the hull doesn't eat the corners of finely stepped curves, like the approximated disc
+// in minkowski_disc
+const COLINEAR_EPSILON: f32 = 1e-5;
+
+fn cross(o: &Point, a: &Point, b: &Point) -> f32 {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+// signed distance of p from the line through a and b, positive on the left
+fn side(a: &Point, b: &Point, p: &Point) -> f32 {
+    let length = a.distance(b);
+    if length == 0.0 {
+        return 0.0;
+    }
+    cross(a, b, p) / length
+}
+
+// Andrew's monotone chain, counter clockwise with colinear and duplicate points dropped
+fn hull(points: &[Point]) -> Vec<Point> {
+    let mut sorted = points.to_vec();
+    sorted.retain(|p| p.x.is_finite() && p.y.is_finite());
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup_by(|a, b| a.distance(b) < EPSILON);
+
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    let mut lower: Vec<Point> = Vec::new();
+    for point in sorted.iter() {
+        while lower.len() >= 2
+            && side(&lower[lower.len() - 2], &lower[lower.len() - 1], point) <= COLINEAR_EPSILON
+        {
+            lower.pop();
+        }
+        lower.push(*point);
+    }
+
+    let mut upper: Vec<Point> = Vec::new();
+    for point in sorted.iter().rev() {
+        while upper.len() >= 2
+            && side(&upper[upper.len() - 2], &upper[upper.len() - 1], point) <= COLINEAR_EPSILON
+        {
+            upper.pop();
+        }
+        upper.push(*point);
+    }
+
+    lower.pop();
+    upper.pop();
+    lower.append(&mut upper);
+    lower
+}
+
+// Sutherland-Hodgman against a single edge, keeps whatever is on the left of a -> b
+fn clip(points: &[Point], a: &Point, b: &Point) -> Vec<Point> {
+    let mut clipped = Vec::with_capacity(points.len() + 1);
+
+    for (i, current) in points.iter().enumerate() {
+        let previous = &points[(i + points.len() - 1) % points.len()];
+        let current_side = side(a, b, current);
+        let previous_side = side(a, b, previous);
+
+        let crossing = || {
+            let t = previous_side / (previous_side - current_side);
+            Point {
+                x: previous.x + t * (current.x - previous.x),
+                y: previous.y + t * (current.y - previous.y),
+            }
+        };
+
+        if current_side >= -EPSILON {
+            if previous_side < -EPSILON && current_side > EPSILON {
+                clipped.push(crossing());
+            }
+            clipped.push(*current);
+        } else if previous_side > EPSILON {
+            clipped.push(crossing());
+        }
+    }
+
+    clipped
+}
+
+// None if what's left has no area to speak of
+fn to_polygon(points: &[Point]) -> Option<Polygon> {
+    let points = hull(points);
+    if points.len() < 3 || crate::area(&points) < EPSILON * EPSILON {
+        return None;
+    }
+
+    Some(Polygon::new(&points))
+}
+
+impl Polygon {
+    pub fn convex_hull(points: &[Point]) -> Option<Polygon> {
+        to_polygon(points)
+    }
+
+    pub fn is_convex(&self) -> bool {
+        let n = self.points.len();
+        if n < 3 {
+            return false;
+        }
+
+        let mut sign = 0.0;
+        for i in 0..n {
+            let turn = side(
+                &self.points[i],
+                &self.points[(i + 1) % n],
+                &self.points[(i + 2) % n],
+            );
+            if turn.abs() <= EPSILON {
+                continue;
+            }
+            if sign == 0.0 {
+                sign = turn.signum();
+            } else if turn.signum() != sign {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn intersection(&self, other: &Polygon) -> Option<Polygon> {
+        let mut clipped = hull(&self.points);
+        let clip_edges = hull(&other.points);
+        if clip_edges.len() < 3 {
+            return None;
+        }
+
+        for (i, a) in clip_edges.iter().enumerate() {
+            let b = &clip_edges[(i + 1) % clip_edges.len()];
+            clipped = clip(&clipped, a, b);
+            if clipped.len() < 3 {
+                return None;
+            }
+        }
+
+        to_polygon(&clipped)
+    }
+
+    // self with other cut out of it, as disjoint convex pieces. Empty if other covers all of self.
+    pub fn difference(&self, other: &Polygon) -> Vec<Polygon> {
+        let mut remaining = hull(&self.points);
+        let cut = hull(&other.points);
+        if cut.len() < 3 {
+            return to_polygon(&remaining).into_iter().collect();
+        }
+
+        // peel off the part of self outside each edge of other in turn, whatever survives every edge is inside other
+        let mut pieces = Vec::new();
+        for (i, a) in cut.iter().enumerate() {
+            let b = &cut[(i + 1) % cut.len()];
+
+            if let Some(outside) = to_polygon(&clip(&remaining, b, a)) {
+                pieces.push(outside);
+            }
+
+            remaining = clip(&remaining, a, b);
+            if remaining.len() < 3 {
+                break;
+            }
+        }
+
+        pieces
+    }
+
+    // self and other as disjoint convex pieces, self first
+    pub fn union(&self, other: &Polygon) -> Vec<Polygon> {
+        let mut pieces: Vec<Polygon> = to_polygon(&self.points).into_iter().collect();
+        pieces.append(&mut other.difference(self));
+        pieces
+    }
+
+    // Grows the polygon by a disc of the given radius, e.g. to go from the region a mini's centre can be in to the
+    // region its base covers. The disc is approximated by a polygon with the given number of sides that sits just
+    // outside the circle, so the result always covers the exact Minkowski sum.
+    pub fn minkowski_disc(&self, radius: Mm, sides: usize) -> Option<Polygon> {
+        let sides = sides.max(3);
+        let circumradius = radius.0 / (std::f32::consts::PI / sides as f32).cos();
+
+        let mut points = Vec::with_capacity(self.points.len() * sides);
+        for point in hull(&self.points).iter() {
+            for i in 0..sides {
+                let angle = i as f32 * std::f32::consts::TAU / sides as f32;
+                points.push(Point {
+                    x: point.x + circumradius * angle.cos(),
+                    y: point.y + circumradius * angle.sin(),
+                });
+            }
+        }
+
+        to_polygon(&points)
+    }
+
+    // How far a guess at the centre of a base `footprint` across can be off, when this is the bounding polygon round
+    // the whole base. Pulling it in by the base radius leaves where the centre can be, and if nothing is left the
+    // centre is pinned down. Every tool reports this as the error, so their numbers can be compared.
+    pub fn centre_error(&self, footprint: Mm) -> f32 {
+        self.offset(-footprint / 2.0)
+            .map(|centre_region| centre_region.max_width())
+            .unwrap_or(0.0)
+    }
+
+    // Moves every edge out (positive distance) or in (negative distance). Growing rounds the corners off, shrinking
+    // keeps them sharp, and None means the polygon shrank away to nothing.
+    pub fn offset(&self, distance: Mm) -> Option<Polygon> {
+        if distance >= Mm(0.0) {
+            return self.minkowski_disc(distance, 32);
+        }
+        let distance = distance.0;
+
+        let points = hull(&self.points);
+        if points.len() < 3 {
+            return None;
+        }
+
+        let mut shrunk = points.clone();
+        for (i, a) in points.iter().enumerate() {
+            let b = &points[(i + 1) % points.len()];
+            let length = a.distance(b);
+            // inward normal of a counter clockwise edge is on its left
+            let (nx, ny) = (
+                -(b.y - a.y) / length * -distance,
+                (b.x - a.x) / length * -distance,
+            );
+            let a = Point {
+                x: a.x + nx,
+                y: a.y + ny,
+            };
+            let b = Point {
+                x: b.x + nx,
+                y: b.y + ny,
+            };
+
+            shrunk = clip(&shrunk, &a, &b);
+            if shrunk.len() < 3 {
+                return None;
+            }
+        }
+
+        to_polygon(&shrunk)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use float_cmp::assert_approx_eq;
+
+    use super::*;
+
+    fn rect(x1: f32, y1: f32, x2: f32, y2: f32) -> Polygon {
+        Polygon::new(&[
+            Point { x: x1, y: y1 },
+            Point { x: x2, y: y1 },
+            Point { x: x2, y: y2 },
+            Point { x: x1, y: y2 },
+        ])
+    }
+
+    fn total_area(pieces: &[Polygon]) -> f32 {
+        pieces.iter().map(|p| p.area()).sum()
+    }
+
+    #[test]
+    fn hull_drops_colinear_and_duplicates() {
+        let hull = Polygon::convex_hull(&[
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 5.0, y: 0.0 },
+            Point { x: 10.0, y: 0.0 },
+            Point { x: 10.0, y: 0.0 },
+            Point { x: 10.0, y: 10.0 },
+            Point { x: 5.0, y: 5.0 },
+            Point { x: 0.0, y: 10.0 },
+            Point { x: 0.0, y: 5.0 },
+        ])
+        .unwrap();
+        assert_eq!(hull.points.len(), 4);
+        assert_approx_eq!(f32, hull.area(), 100.0, epsilon = 0.001);
+        assert!(hull.is_convex());
+
+        // all on one line, or all the same point
+        assert!(Polygon::convex_hull(&[
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 1.0, y: 1.0 },
+            Point { x: 2.0, y: 2.0 },
+        ])
+        .is_none());
+        assert!(Polygon::convex_hull(&[Point { x: 3.0, y: 3.0 }; 4]).is_none());
+    }
+
+    #[test]
+    fn intersection() {
+        let a = rect(0.0, 0.0, 10.0, 10.0);
+        let b = rect(5.0, 5.0, 15.0, 15.0);
+        assert_approx_eq!(
+            f32,
+            a.intersection(&b).unwrap().area(),
+            25.0,
+            epsilon = 0.001
+        );
+        assert_approx_eq!(
+            f32,
+            a.intersection(&a).unwrap().area(),
+            100.0,
+            epsilon = 0.001
+        );
+
+        // contained
+        let inner = rect(2.0, 2.0, 4.0, 4.0);
+        assert_approx_eq!(
+            f32,
+            a.intersection(&inner).unwrap().area(),
+            4.0,
+            epsilon = 0.001
+        );
+        assert_approx_eq!(
+            f32,
+            inner.intersection(&a).unwrap().area(),
+            4.0,
+            epsilon = 0.001
+        );
+
+        // disjoint, sharing an edge, sharing a corner
+        assert!(a.intersection(&rect(20.0, 0.0, 30.0, 10.0)).is_none());
+        assert!(a.intersection(&rect(10.0, 0.0, 20.0, 10.0)).is_none());
+        assert!(a.intersection(&rect(10.0, 10.0, 20.0, 20.0)).is_none());
+    }
+
+    #[test]
+    fn intersection_with_colinear_edges() {
+        // b's bottom edge lies along a's bottom edge and has extra points on it
+        let a = rect(0.0, 0.0, 10.0, 10.0);
+        let b = Polygon::new(&[
+            Point { x: 2.0, y: 0.0 },
+            Point { x: 4.0, y: 0.0 },
+            Point { x: 6.0, y: 0.0 },
+            Point { x: 6.0, y: 4.0 },
+            Point { x: 2.0, y: 4.0 },
+        ]);
+
+        let intersection = a.intersection(&b).unwrap();
+        assert_approx_eq!(f32, intersection.area(), 16.0, epsilon = 0.001);
+        assert_eq!(intersection.points.len(), 4);
+    }
+
+    #[test]
+    fn difference() {
+        let a = rect(0.0, 0.0, 10.0, 10.0);
+
+        let hole = rect(3.0, 3.0, 7.0, 7.0);
+        let pieces = a.difference(&hole);
+        assert_approx_eq!(f32, total_area(&pieces), 84.0, epsilon = 0.001);
+        assert!(pieces.iter().all(|p| p.is_convex()));
+        // nothing left over overlaps the hole
+        assert!(pieces.iter().all(|p| p.intersection(&hole).is_none()));
+
+        let overlap = rect(5.0, -5.0, 15.0, 5.0);
+        assert_approx_eq!(
+            f32,
+            total_area(&a.difference(&overlap)),
+            75.0,
+            epsilon = 0.001
+        );
+
+        // disjoint leaves a alone, covering it leaves nothing
+        assert_approx_eq!(
+            f32,
+            total_area(&a.difference(&rect(20.0, 20.0, 30.0, 30.0))),
+            100.0,
+            epsilon = 0.001
+        );
+        assert!(a.difference(&rect(-1.0, -1.0, 11.0, 11.0)).is_empty());
+        assert!(a.difference(&a).is_empty());
+
+        // sharing an edge doesn't take anything away
+        assert_approx_eq!(
+            f32,
+            total_area(&a.difference(&rect(10.0, 0.0, 20.0, 10.0))),
+            100.0,
+            epsilon = 0.001
+        );
+    }
+
+    #[test]
+    fn union() {
+        let a = rect(0.0, 0.0, 10.0, 10.0);
+        let b = rect(5.0, 5.0, 15.0, 15.0);
+
+        let pieces = a.union(&b);
+        assert_approx_eq!(f32, total_area(&pieces), 175.0, epsilon = 0.001);
+        for (i, p) in pieces.iter().enumerate() {
+            for q in pieces.iter().skip(i + 1) {
+                assert!(p.intersection(q).is_none());
+            }
+        }
+
+        assert_approx_eq!(f32, total_area(&a.union(&a)), 100.0, epsilon = 0.001);
+    }
+
+    #[test]
+    fn minkowski_disc() {
+        let square = rect(0.0, 0.0, 10.0, 10.0);
+        let grown = square.minkowski_disc(Mm(1.0), 64).unwrap();
+
+        // exact answer is the square plus four 10x1 strips plus a unit circle, the approximated disc pokes out past
+        // the circle by a fraction of a percent
+        let exact = 100.0 + 40.0 + std::f32::consts::PI;
+        assert!(grown.area() >= exact);
+        assert!(grown.area() < exact + 0.1);
+
+        // a point grows into a disc, a segment into a stadium
+        let dot = Polygon::new(&[Point { x: 5.0, y: 5.0 }]);
+        assert_approx_eq!(
+            f32,
+            dot.minkowski_disc(Mm(2.0), 256).unwrap().area(),
+            std::f32::consts::PI * 4.0,
+            epsilon = 0.01
+        );
+        let segment = Polygon::new(&[Point { x: 0.0, y: 0.0 }, Point { x: 10.0, y: 0.0 }]);
+        assert_approx_eq!(
+            f32,
+            segment.minkowski_disc(Mm(1.0), 256).unwrap().area(),
+            20.0 + std::f32::consts::PI,
+            epsilon = 0.01
+        );
+    }
+
+    #[test]
+    fn offset() {
+        let square = rect(0.0, 0.0, 10.0, 10.0);
+
+        let shrunk = square.offset(Mm(-2.0)).unwrap();
+        assert_approx_eq!(f32, shrunk.area(), 36.0, epsilon = 0.001);
+        assert_approx_eq!(f32, shrunk.min_x_point.x, 2.0, epsilon = 0.001);
+
+        // shrinking by half the width or more leaves nothing
+        assert!(square.offset(Mm(-5.0)).is_none());
+        assert_approx_eq!(
+            f32,
+            square.centre_error(Mm(4.0)),
+            shrunk.max_width(),
+            epsilon = 0.001
+        );
+        assert_eq!(square.centre_error(Mm(10.0)), 0.0);
+        assert!(square.offset(Mm(-6.0)).is_none());
+
+        assert!(square.offset(Mm(1.0)).unwrap().area() > 140.0);
+        assert_approx_eq!(
+            f32,
+            square.offset(Mm(0.0)).unwrap().area(),
+            100.0,
+            epsilon = 0.01
+        );
+
+        // a thin sliver collapses long before its long side would
+        let sliver = Polygon::new(&[
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 100.0, y: 0.0 },
+            Point { x: 50.0, y: 1.0 },
+        ]);
+        assert!(sliver.offset(Mm(-0.5)).is_none());
+        assert!(sliver.offset(Mm(-0.2)).is_some());
+
+        // colinear points along the edges don't change the result
+        let noisy = Polygon::new(&[
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 5.0, y: 0.0 },
+            Point { x: 10.0, y: 0.0 },
+            Point { x: 10.0, y: 5.0 },
+            Point { x: 10.0, y: 10.0 },
+            Point { x: 0.0, y: 10.0 },
+        ]);
+        assert_approx_eq!(
+            f32,
+            noisy.offset(Mm(-2.0)).unwrap().area(),
+            36.0,
+            epsilon = 0.001
+        );
+    }
+}
@@ -0,0 +1,278 @@
+// A session at the table written down as it happened: every time the minis were solved, which receivers saw each one
+// and what the solver made of it. Kept alongside the layout it was recorded on, so a fix that went wrong can be
+// stepped through in the visualizer afterwards.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::LayoutFile;
+use crate::units::{Mm, BASE_DIAMETER};
+use crate::{Point, Polygon, Receiver, Table};
+
+fn default_footprint() -> Mm {
+    BASE_DIAMETER
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub layout: LayoutFile,
+    // the base size the receivers' expanded view bounds were worked out for
+    #[serde(default = "default_footprint")]
+    pub footprint: Mm,
+    #[serde(default)]
+    pub frames: Vec<RecordedFrame>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    // seconds since the recording started
+    pub time: f32,
+    pub minis: Vec<RecordedMini>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedMini {
+    pub name: String,
+    // indices into the layout's receivers of the ones that saw this mini
+    pub seen: Vec<usize>,
+    // corners of the region the solver narrowed the mini down to, empty if it couldn't
+    #[serde(default)]
+    pub region: Vec<Point>,
+    #[serde(default)]
+    pub estimate: Option<Point>,
+    // where the mini really was, when that's known
+    #[serde(default)]
+    pub actual: Option<Point>,
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl std::fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordingError::Io(e) => write!(f, "couldn't read or write recording: {e}"),
+            RecordingError::Json(e) => write!(f, "bad recording file: {e}"),
+            RecordingError::Invalid(e) => write!(f, "bad recording: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+impl From<io::Error> for RecordingError {
+    fn from(e: io::Error) -> Self {
+        RecordingError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for RecordingError {
+    fn from(e: serde_json::Error) -> Self {
+        RecordingError::Json(e)
+    }
+}
+
+impl RecordedMini {
+    pub fn new(
+        name: &str,
+        observed: &[(Receiver, bool)],
+        region: Option<&Polygon>,
+        estimate: Option<Point>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            seen: observed
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, can_see))| *can_see)
+                .map(|(i, _)| i)
+                .collect(),
+            region: region.map(|p| p.points.clone()).unwrap_or_default(),
+            estimate,
+            actual: None,
+        }
+    }
+
+    // every receiver on the table, and whether it saw this mini
+    pub fn observations(&self, table: &Table) -> Vec<(Receiver, bool)> {
+        let mut observed: Vec<(Receiver, bool)> =
+            table.receivers.iter().map(|r| (*r, false)).collect();
+        for &i in &self.seen {
+            observed[i].1 = true;
+        }
+        observed
+    }
+
+    pub fn region(&self) -> Option<Polygon> {
+        if self.region.len() < 3 {
+            return None;
+        }
+        Some(Polygon::new(&self.region))
+    }
+}
+
+impl Recording {
+    pub fn new(table: &Table, footprint: Mm) -> Self {
+        Self {
+            layout: LayoutFile::from_table(table),
+            footprint,
+            frames: Vec::new(),
+        }
+    }
+
+    // the table as it was during the recording, receivers built for the recorded base size
+    pub fn to_table(&self) -> Table {
+        let receivers = self
+            .layout
+            .receivers
+            .iter()
+            .map(|r| {
+                Receiver::with_footprint(
+                    self.layout.width,
+                    self.layout.height,
+                    r.view_angle,
+                    Point { x: r.x.0, y: r.y.0 },
+                    r.facing,
+                    self.footprint,
+                )
+            })
+            .collect();
+
+        Table::new(self.layout.width, self.layout.height, receivers)
+    }
+
+    fn validate(&self) -> Result<(), RecordingError> {
+        let receivers = self.layout.receivers.len();
+        for (i, frame) in self.frames.iter().enumerate() {
+            if i > 0 && frame.time < self.frames[i - 1].time {
+                return Err(RecordingError::Invalid(format!(
+                    "frame {i} at {}s is before the one ahead of it",
+                    frame.time
+                )));
+            }
+            for mini in &frame.minis {
+                if let Some(seen) = mini.seen.iter().find(|&&r| r >= receivers) {
+                    return Err(RecordingError::Invalid(format!(
+                        "{} in frame {i} was seen by receiver {seen}, the layout only has {receivers}",
+                        mini.name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, RecordingError> {
+        let recording: Recording = serde_json::from_str(json)?;
+        recording.validate()?;
+        Ok(recording)
+    }
+
+    // recordings get long, so unlike layouts they aren't pretty printed
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, RecordingError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), RecordingError> {
+        Ok(fs::write(path, self.to_json())?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::units::Degrees;
+    use crate::Direction;
+
+    fn table() -> Table {
+        let receiver =
+            |location, facing| Receiver::new(Mm(300.0), Mm(200.0), Degrees(30.0), location, facing);
+        Table::new(
+            Mm(300.0),
+            Mm(200.0),
+            vec![
+                receiver(Point { x: 50.0, y: 0.0 }, Direction::Up),
+                receiver(Point { x: 150.0, y: 0.0 }, Direction::Up),
+                receiver(Point { x: 0.0, y: 100.0 }, Direction::Right),
+            ],
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        let table = table();
+        let observed: Vec<(Receiver, bool)> = table
+            .receivers
+            .iter()
+            .zip([true, false, true])
+            .map(|(r, v)| (*r, v))
+            .collect();
+        let region = Polygon::new(&[
+            Point { x: 40.0, y: 80.0 },
+            Point { x: 60.0, y: 80.0 },
+            Point { x: 60.0, y: 120.0 },
+            Point { x: 40.0, y: 120.0 },
+        ]);
+
+        let mut mini = RecordedMini::new("knight", &observed, Some(&region), Some(region.center()));
+        mini.actual = Some(Point { x: 52.0, y: 98.0 });
+        assert_eq!(mini.seen, vec![0, 2]);
+
+        let mut recording = Recording::new(&table, BASE_DIAMETER);
+        recording.frames.push(RecordedFrame {
+            time: 0.5,
+            minis: vec![mini],
+        });
+
+        let loaded = Recording::from_json(&recording.to_json()).unwrap();
+        assert_eq!(loaded, recording);
+
+        let table = loaded.to_table();
+        let mini = &loaded.frames[0].minis[0];
+        let seen: Vec<bool> = mini.observations(&table).iter().map(|(_, v)| *v).collect();
+        assert_eq!(seen, vec![true, false, true]);
+        assert_eq!(mini.region().unwrap().points.len(), 4);
+        assert_eq!(mini.estimate, Some(Point { x: 50.0, y: 100.0 }));
+    }
+
+    #[test]
+    fn rejects_bad_recordings() {
+        let layout = LayoutFile::from_table(&table()).to_json();
+
+        let recording = Recording::from_json(&format!(r#"{{ "layout": {layout} }}"#)).unwrap();
+        assert!(recording.frames.is_empty());
+        assert_eq!(recording.footprint, BASE_DIAMETER);
+
+        let out_of_range = format!(
+            r#"{{ "layout": {layout}, "frames": [
+                {{ "time": 0, "minis": [{{ "name": "knight", "seen": [3] }}] }}
+            ] }}"#
+        );
+        assert!(matches!(
+            Recording::from_json(&out_of_range),
+            Err(RecordingError::Invalid(_))
+        ));
+
+        let backwards = format!(
+            r#"{{ "layout": {layout}, "frames": [
+                {{ "time": 1, "minis": [] }},
+                {{ "time": 0, "minis": [] }}
+            ] }}"#
+        );
+        assert!(matches!(
+            Recording::from_json(&backwards),
+            Err(RecordingError::Invalid(_))
+        ));
+    }
+}
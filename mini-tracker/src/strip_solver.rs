@@ -0,0 +1,337 @@
+// A much cheaper solver than Table::get_bounding_polygon, small enough for the MCU. Each edge of the table is a strip
+// of receivers in order along it, and only the outermost receivers on each strip that can see the mini, plus the next
+// receiver out from each of them that can't, are used. Every one of those cuts the table down once, so the work
+// doesn't grow with the number of intersections like the general solver.
+
+use crate::units::Mm;
+use crate::{Direction, Line, Point, Polygon, Receiver, Table};
+
+// the receivers along each edge, ordered by x for the top and bottom, by y for the left and right
+#[derive(Clone, Debug, Default)]
+pub struct Strips {
+    pub top: Vec<(Receiver, bool)>,
+    pub bottom: Vec<(Receiver, bool)>,
+    pub left: Vec<(Receiver, bool)>,
+    pub right: Vec<(Receiver, bool)>,
+}
+
+impl Strips {
+    pub fn new(receivers: &[(Receiver, bool)]) -> Self {
+        let strip = |facing: Direction| {
+            let mut strip: Vec<(Receiver, bool)> = receivers
+                .iter()
+                .filter(|(r, _)| r.facing == facing)
+                .copied()
+                .collect();
+            strip.sort_by(|(a, _), (b, _)| match facing {
+                Direction::Up | Direction::Down => a.location.x.partial_cmp(&b.location.x).unwrap(),
+                Direction::Left | Direction::Right => {
+                    a.location.y.partial_cmp(&b.location.y).unwrap()
+                }
+            });
+            strip
+        };
+
+        Self {
+            top: strip(Direction::Down),
+            bottom: strip(Direction::Up),
+            left: strip(Direction::Right),
+            right: strip(Direction::Left),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct StripEnd {
+    // the outermost receiver that can see the mini
+    seeing: Receiver,
+    // the next receiver out, which can't, and how far it is from `seeing`
+    blind: Option<(Receiver, Mm)>,
+    // which of the view bounds faces away from the rest of the strip
+    outer_bound: u8,
+    // which side of the blind receiver's outer bound the mini is on
+    above: bool,
+}
+
+impl StripEnd {
+    fn find(
+        strip: &[(Receiver, bool)],
+        forward: bool,
+        outer_bound: u8,
+        above: bool,
+    ) -> Option<Self> {
+        let idx = if forward {
+            strip.iter().position(|(_, v)| *v)
+        } else {
+            strip.iter().rposition(|(_, v)| *v)
+        }?;
+
+        let blind_idx = if forward {
+            idx.checked_sub(1)
+        } else {
+            Some(idx + 1).filter(|i| *i < strip.len())
+        };
+        let seeing = strip[idx].0;
+
+        Some(Self {
+            seeing,
+            blind: blind_idx.map(|i| {
+                let blind = strip[i].0;
+                (blind, Mm(blind.location.distance(&seeing.location)))
+            }),
+            outer_bound,
+            above,
+        })
+    }
+
+    fn view_bound(&self, receiver: &Receiver) -> Line {
+        if self.outer_bound == 1 {
+            receiver.view_bound1
+        } else {
+            receiver.view_bound2
+        }
+    }
+
+    fn expanded_view_bound(&self, receiver: &Receiver) -> Line {
+        if self.outer_bound == 1 {
+            receiver.expanded_view_bound1
+        } else {
+            receiver.expanded_view_bound2
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MiniBounds {
+    table_bounds: Polygon,
+    radius: Mm,
+    ends: Vec<StripEnd>,
+}
+
+// keeps the part of `polygon` above or below `line`, or all of it if the line misses
+fn cut(polygon: Polygon, (line, keep_above): (Line, bool)) -> Polygon {
+    match polygon.bisect(line) {
+        Some((poly1, poly2)) => {
+            if poly1.above_line(&line) == keep_above {
+                poly1
+            } else {
+                poly2
+            }
+        }
+        None => polygon,
+    }
+}
+
+impl MiniBounds {
+    pub fn new(table: &Table, footprint: Mm, strips: &Strips) -> Self {
+        let table_bounds = Polygon::new(&[
+            table
+                .table_top
+                .intersection(&table.table_left, true)
+                .unwrap(),
+            table
+                .table_top
+                .intersection(&table.table_right, true)
+                .unwrap(),
+            table
+                .table_bottom
+                .intersection(&table.table_right, true)
+                .unwrap(),
+            table
+                .table_bottom
+                .intersection(&table.table_left, true)
+                .unwrap(),
+        ]);
+
+        let ends = [
+            StripEnd::find(&strips.top, true, 1, true),
+            StripEnd::find(&strips.top, false, 2, true),
+            StripEnd::find(&strips.bottom, true, 2, false),
+            StripEnd::find(&strips.bottom, false, 1, false),
+            StripEnd::find(&strips.left, false, 2, false),
+            StripEnd::find(&strips.left, true, 1, true),
+            StripEnd::find(&strips.right, false, 1, false),
+            StripEnd::find(&strips.right, true, 2, true),
+        ];
+
+        Self {
+            table_bounds,
+            radius: footprint / 2.0,
+            ends: ends.into_iter().flatten().collect(),
+        }
+    }
+
+    // the lines worst_case cuts along, and which side of each it keeps
+    pub fn worst_case_cuts(&self) -> Vec<(Line, bool)> {
+        self.ends
+            .iter()
+            .map(|end| (end.expanded_view_bound(&end.seeing), !end.above))
+            .collect()
+    }
+
+    // Everywhere the outermost seeing receivers could be seeing any part of the mini from.
+    pub fn worst_case(&self) -> Polygon {
+        self.worst_case_cuts()
+            .into_iter()
+            .fold(self.table_bounds.clone(), cut)
+    }
+
+    pub fn best_case_cuts(&self) -> Vec<(Line, bool)> {
+        let mut cuts = Vec::new();
+        for end in &self.ends {
+            if let Some((blind, spacing)) = end.blind {
+                let bound = end.view_bound(&blind);
+                cuts.push((bound, end.above));
+                cuts.push((
+                    bound.parallel_line(spacing + self.radius, end.outer_bound == 1),
+                    !end.above,
+                ));
+            }
+        }
+        cuts
+    }
+
+    // Between each blind receiver's view and one receiver spacing plus the base radius in from it, assuming nothing
+    // is shadowing the mini.
+    pub fn best_case(&self) -> Polygon {
+        self.best_case_cuts()
+            .into_iter()
+            .fold(self.table_bounds.clone(), cut)
+    }
+}
+
+// the centre of the best case, None if no receiver saw the mini
+pub fn get_location(table: &Table, receivers: &[(Receiver, bool)], footprint: Mm) -> Option<Point> {
+    let bounds = MiniBounds::new(table, footprint, &Strips::new(receivers));
+    if bounds.ends.is_empty() {
+        return None;
+    }
+    Some(bounds.best_case().center())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::units::{Degrees, BASE_DIAMETER, MM_PER_INCH};
+
+    const WIDTH: Mm = Mm(400.0);
+    const HEIGHT: Mm = Mm(300.0);
+
+    fn table(view_angle: Degrees) -> Table {
+        let receiver =
+            |location, facing| Receiver::new(WIDTH, HEIGHT, view_angle, location, facing);
+        let mut receivers = Vec::new();
+        let mut x = MM_PER_INCH / 2.0;
+        while x < WIDTH.0 {
+            receivers.push(receiver(Point { x, y: 0.0 }, Direction::Up));
+            receivers.push(receiver(Point { x, y: HEIGHT.0 }, Direction::Down));
+            x += MM_PER_INCH;
+        }
+        let mut y = MM_PER_INCH / 2.0;
+        while y < HEIGHT.0 {
+            receivers.push(receiver(Point { x: 0.0, y }, Direction::Right));
+            receivers.push(receiver(Point { x: WIDTH.0, y }, Direction::Left));
+            y += MM_PER_INCH;
+        }
+        Table::new(WIDTH, HEIGHT, receivers)
+    }
+
+    fn observe(table: &Table, location: Point) -> Vec<(Receiver, bool)> {
+        let radius = BASE_DIAMETER.0 / 2.0;
+        table
+            .receivers
+            .iter()
+            .map(|receiver| {
+                let can_see = (0..360).any(|angle| {
+                    let angle = (angle as f32).to_radians();
+                    receiver.can_see(&Point {
+                        x: location.x + radius * angle.cos(),
+                        y: location.y + radius * angle.sin(),
+                    })
+                });
+                (*receiver, can_see)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn strips_are_ordered() {
+        let table = table(Degrees(30.0));
+        let observed: Vec<(Receiver, bool)> =
+            table.receivers.iter().rev().map(|r| (*r, false)).collect();
+        let strips = Strips::new(&observed);
+
+        assert_eq!(strips.top.len(), 16);
+        assert_eq!(strips.left.len(), 12);
+        assert!(strips.top.iter().all(|(r, _)| r.facing == Direction::Down));
+        assert!(strips
+            .bottom
+            .windows(2)
+            .all(|w| w[0].0.location.x < w[1].0.location.x));
+        assert!(strips
+            .right
+            .windows(2)
+            .all(|w| w[0].0.location.y < w[1].0.location.y));
+
+        // nothing seen, nothing to solve
+        assert_eq!(get_location(&table, &observed, BASE_DIAMETER), None);
+    }
+
+    #[test]
+    fn agrees_with_general_solver() {
+        for view_angle in [10.0, 30.0, 60.0] {
+            let table = table(Degrees(view_angle));
+            let mut positions = 0;
+            let mut missed = 0;
+            let mut y = 30.0;
+            while y < HEIGHT.0 - 20.0 {
+                let mut x = 30.0;
+                while x < WIDTH.0 - 20.0 {
+                    let location = Point { x, y };
+                    let observed = observe(&table, location);
+                    let bounds = MiniBounds::new(&table, BASE_DIAMETER, &Strips::new(&observed));
+                    let general = table.get_bounding_polygon(&observed).unwrap();
+                    let worst_case = bounds.worst_case();
+                    positions += 1;
+
+                    // wherever the general solver puts the mini, the strips have to agree
+                    if general.contains(&location) {
+                        assert!(worst_case.contains(&location), "{location:?}");
+                        assert!(bounds.best_case().contains(&location), "{location:?}");
+                    } else {
+                        missed += 1;
+                    }
+
+                    // the worst case only ever uses some of the cuts, so it can't be much smaller
+                    assert!(worst_case.contains(&general.center()), "{location:?}");
+                    assert!(
+                        worst_case.area() >= 0.99 * general.area(),
+                        "{location:?} {} {}",
+                        worst_case.area(),
+                        general.area()
+                    );
+
+                    let estimate = get_location(&table, &observed, BASE_DIAMETER).unwrap();
+                    assert!(
+                        estimate.distance(&location) < MM_PER_INCH,
+                        "{location:?} {estimate:?}"
+                    );
+                    assert!(
+                        estimate.distance(&general.center()) < MM_PER_INCH,
+                        "{location:?} {estimate:?} {:?}",
+                        general.center()
+                    );
+
+                    x += 20.0;
+                }
+                y += 20.0;
+            }
+
+            // the general solver misses the mini now and then at the edge of a view, but not often
+            assert!(
+                missed * 20 <= positions,
+                "{view_angle} {missed}/{positions}"
+            );
+        }
+    }
+}
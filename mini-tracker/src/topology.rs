@@ -0,0 +1,309 @@
+use std::collections::VecDeque;
+
+use table_protocol::discovery::{
+    BoardReport, BoardType, ChainNode, DiscoveryError, DiscoveryMessage, MAX_MESSAGE_LEN,
+};
+
+use crate::units::{Degrees, Mm};
+use crate::{Direction, Point, Receiver, Table};
+
+// The chain starts at the bottom left corner of the table and runs counter clockwise: along the bottom edge to the
+// right, up the right edge, along the top edge to the left and back down the left edge. Every corner board moves the
+// chain onto the next edge.
+#[derive(Clone, Copy, Debug)]
+pub struct TableShape {
+    pub width: Mm,
+    pub height: Mm,
+    pub view_angle: Degrees,
+    pub receiver_spacing: Mm,
+    pub joiner_length: Mm,
+    // how far along the new edge the first board after a corner starts
+    pub corner_length: Mm,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TopologyError {
+    EmptyChain,
+    MissingBoard(u8),
+    DuplicateBoard(u8),
+    UnterminatedChain,
+    // a board before the end of the chain says it's the last one
+    TerminatedEarly(u8),
+    TooManyCorners(u8),
+    EdgeOverrun(u8),
+}
+
+// Put the reports from a discovery run in chain order and make sure every board answered
+pub fn collect_chain(reports: &[BoardReport]) -> Result<Vec<BoardReport>, TopologyError> {
+    if reports.is_empty() {
+        return Err(TopologyError::EmptyChain);
+    }
+
+    let mut chain = reports.to_vec();
+    chain.sort_by_key(|r| r.position);
+
+    for (i, report) in chain.iter().enumerate() {
+        if (report.position as usize) < i {
+            return Err(TopologyError::DuplicateBoard(report.position));
+        }
+        if report.position as usize > i {
+            return Err(TopologyError::MissingBoard(i as u8));
+        }
+        if report.last_in_chain && i + 1 < chain.len() {
+            return Err(TopologyError::TerminatedEarly(report.position));
+        }
+    }
+
+    if !chain.last().unwrap().last_in_chain {
+        return Err(TopologyError::UnterminatedChain);
+    }
+
+    Ok(chain)
+}
+
+pub fn build_table(chain: &[BoardReport], shape: &TableShape) -> Result<Table, TopologyError> {
+    let mut edge = 0;
+    let mut distance = Mm(0.0);
+    let mut receivers = Vec::new();
+    // every receiver board is a strip, numbered along the chain
+    let mut strips = Vec::new();
+
+    for board in chain {
+        match board.board_type {
+            BoardType::ReceiverStrip => {
+                strips.push(board.receiver_count as usize);
+                for i in 0..board.receiver_count {
+                    let along = distance + shape.receiver_spacing * (i as f32 + 0.5);
+                    let (location, facing) = edge_position(shape.width, shape.height, edge, along);
+                    receivers.push(Receiver::new(
+                        shape.width,
+                        shape.height,
+                        shape.view_angle,
+                        location,
+                        facing,
+                    ));
+                }
+                distance += shape.receiver_spacing * board.receiver_count as f32;
+            }
+            BoardType::Joiner => distance += shape.joiner_length,
+            BoardType::Corner => {
+                if edge == 3 {
+                    return Err(TopologyError::TooManyCorners(board.position));
+                }
+                edge += 1;
+                distance = shape.corner_length;
+            }
+        }
+
+        let edge_length = if edge % 2 == 0 {
+            shape.width
+        } else {
+            shape.height
+        };
+        if distance > edge_length + Mm(0.01) {
+            return Err(TopologyError::EdgeOverrun(board.position));
+        }
+    }
+
+    Ok(Table::new(shape.width, shape.height, receivers).with_strips(strips))
+}
+
+// where a receiver the given distance along an edge (0 bottom, 1 right, 2 top, 3 left) sits and which way it faces
+pub fn edge_position(width: Mm, height: Mm, edge: usize, along: Mm) -> (Point, Direction) {
+    let (width, height, along) = (width.0, height.0, along.0);
+    match edge {
+        0 => (Point { x: along, y: 0.0 }, Direction::Up),
+        1 => (Point { x: width, y: along }, Direction::Left),
+        2 => (
+            Point {
+                x: width - along,
+                y: height,
+            },
+            Direction::Down,
+        ),
+        _ => (
+            Point {
+                x: 0.0,
+                y: height - along,
+            },
+            Direction::Right,
+        ),
+    }
+}
+
+// A chain of boards that only exists in memory. Messages are passed between the nodes as encoded bytes, the same way
+// they would go over the joiners.
+pub struct SimulatedChain {
+    nodes: Vec<ChainNode>,
+}
+
+enum Hop {
+    FromUpstream(usize),
+    FromDownstream(usize),
+    Controller,
+}
+
+impl SimulatedChain {
+    pub fn new(boards: &[(BoardType, u8)]) -> Self {
+        let nodes = boards
+            .iter()
+            .enumerate()
+            .map(|(i, (board_type, receiver_count))| {
+                ChainNode::new(*board_type, *receiver_count, i < boards.len() - 1)
+            })
+            .collect();
+
+        Self { nodes }
+    }
+
+    pub fn enumerate(&mut self) -> Result<Vec<BoardReport>, DiscoveryError> {
+        let mut reports = Vec::new();
+        let mut in_flight = VecDeque::new();
+
+        let mut buf = [0u8; MAX_MESSAGE_LEN];
+        let len = DiscoveryMessage::Enumerate { position: 0 }.encode(&mut buf)?;
+        if !self.nodes.is_empty() {
+            in_flight.push_back((Hop::FromUpstream(0), buf, len));
+        }
+
+        while let Some((hop, buf, len)) = in_flight.pop_front() {
+            let (message, _) = DiscoveryMessage::decode(&buf[..len])?;
+
+            let (idx, action) = match hop {
+                Hop::Controller => {
+                    if let DiscoveryMessage::Report(report) = message {
+                        reports.push(report);
+                    }
+                    continue;
+                }
+                Hop::FromUpstream(idx) => (idx, self.nodes[idx].handle_from_upstream(message)?),
+                Hop::FromDownstream(idx) => (idx, self.nodes[idx].handle_from_downstream(message)),
+            };
+
+            if let Some(message) = action.to_upstream {
+                let mut buf = [0u8; MAX_MESSAGE_LEN];
+                let len = message.encode(&mut buf)?;
+                let hop = if idx == 0 {
+                    Hop::Controller
+                } else {
+                    Hop::FromDownstream(idx - 1)
+                };
+                in_flight.push_back((hop, buf, len));
+            }
+
+            if let Some(message) = action.to_downstream {
+                if idx + 1 < self.nodes.len() {
+                    let mut buf = [0u8; MAX_MESSAGE_LEN];
+                    let len = message.encode(&mut buf)?;
+                    in_flight.push_back((Hop::FromUpstream(idx + 1), buf, len));
+                }
+            }
+        }
+
+        Ok(reports)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SHAPE: TableShape = TableShape {
+        width: Mm(400.0),
+        height: Mm(200.0),
+        view_angle: Degrees(20.0),
+        receiver_spacing: Mm(25.0),
+        joiner_length: Mm(50.0),
+        corner_length: Mm(25.0),
+    };
+
+    #[test]
+    fn discover_and_build() {
+        let mut chain = SimulatedChain::new(&[
+            (BoardType::ReceiverStrip, 8),
+            (BoardType::Joiner, 0),
+            (BoardType::ReceiverStrip, 6),
+            (BoardType::Corner, 0),
+            (BoardType::ReceiverStrip, 6),
+            (BoardType::Corner, 0),
+            (BoardType::ReceiverStrip, 14),
+            (BoardType::Corner, 0),
+            (BoardType::ReceiverStrip, 6),
+        ]);
+
+        let reports = chain.enumerate().unwrap();
+        assert_eq!(reports.len(), 9);
+        assert!(reports.last().unwrap().last_in_chain);
+
+        let chain = collect_chain(&reports).unwrap();
+        let table = build_table(&chain, &SHAPE).unwrap();
+        assert_eq!(table.receivers.len(), 8 + 6 + 6 + 14 + 6);
+        assert_eq!(table.strips, [8, 6, 6, 14, 6]);
+
+        // first receiver of each edge
+        let first = &table.receivers[0];
+        assert_eq!(first.location, Point { x: 12.5, y: 0.0 });
+        assert!(matches!(first.facing, Direction::Up));
+
+        // after the joiner the bottom edge picks up 50mm further along
+        assert_eq!(table.receivers[8].location, Point { x: 262.5, y: 0.0 });
+
+        let right = &table.receivers[14];
+        assert_eq!(right.location, Point { x: 400.0, y: 37.5 });
+        assert!(matches!(right.facing, Direction::Left));
+
+        let top = &table.receivers[20];
+        assert_eq!(top.location, Point { x: 362.5, y: 200.0 });
+        assert!(matches!(top.facing, Direction::Down));
+
+        let left = &table.receivers[34];
+        assert_eq!(left.location, Point { x: 0.0, y: 162.5 });
+        assert!(matches!(left.facing, Direction::Right));
+    }
+
+    #[test]
+    fn chain_errors() {
+        let report = |position, last_in_chain| BoardReport {
+            position,
+            board_type: BoardType::ReceiverStrip,
+            receiver_count: 4,
+            last_in_chain,
+        };
+
+        assert_eq!(collect_chain(&[]), Err(TopologyError::EmptyChain));
+        assert_eq!(
+            collect_chain(&[report(0, false), report(2, true)]),
+            Err(TopologyError::MissingBoard(1))
+        );
+        assert_eq!(
+            collect_chain(&[report(0, false), report(0, false), report(1, true)]),
+            Err(TopologyError::DuplicateBoard(0))
+        );
+        assert_eq!(
+            collect_chain(&[report(1, false), report(0, false)]),
+            Err(TopologyError::UnterminatedChain)
+        );
+        assert_eq!(
+            collect_chain(&[report(0, false), report(1, true), report(2, true)]),
+            Err(TopologyError::TerminatedEarly(1))
+        );
+
+        // 20 receivers at 25mm don't fit along a 400mm edge
+        let mut chain = SimulatedChain::new(&[
+            (BoardType::ReceiverStrip, 10),
+            (BoardType::ReceiverStrip, 10),
+        ]);
+        let chain = collect_chain(&chain.enumerate().unwrap()).unwrap();
+        assert_eq!(
+            build_table(&chain, &SHAPE).err(),
+            Some(TopologyError::EdgeOverrun(1))
+        );
+
+        let mut chain = SimulatedChain::new(&[(BoardType::Corner, 0); 4]);
+        let chain = collect_chain(&chain.enumerate().unwrap()).unwrap();
+        assert_eq!(
+            build_table(&chain, &SHAPE).err(),
+            Some(TopologyError::TooManyCorners(3))
+        );
+    }
+}
@@ -0,0 +1,313 @@
+// Unit newtypes for table geometry. Table coordinates (Point) are always mm, screen coordinates are px and only exist
+// on the far side of a Transform. Angles are Degrees unless they are about to go into a trig function.
+
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Point;
+
+pub const MM_PER_INCH: f32 = 25.4;
+
+macro_rules! unit {
+    ($name:ident) => {
+        #[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(pub f32);
+
+        impl $name {
+            pub fn abs(self) -> Self {
+                Self(self.0.abs())
+            }
+
+            pub fn min(self, other: Self) -> Self {
+                Self(self.0.min(other.0))
+            }
+
+            pub fn max(self, other: Self) -> Self {
+                Self(self.0.max(other.0))
+            }
+        }
+
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self {
+                Self(self.0 + rhs.0)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, rhs: Self) {
+                self.0 += rhs.0;
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                Self(self.0 - rhs.0)
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, rhs: Self) {
+                self.0 -= rhs.0;
+            }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                Self(-self.0)
+            }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = Self;
+
+            fn mul(self, rhs: f32) -> Self {
+                Self(self.0 * rhs)
+            }
+        }
+
+        impl Mul<$name> for f32 {
+            type Output = $name;
+
+            fn mul(self, rhs: $name) -> $name {
+                $name(self * rhs.0)
+            }
+        }
+
+        impl Div<f32> for $name {
+            type Output = Self;
+
+            fn div(self, rhs: f32) -> Self {
+                Self(self.0 / rhs)
+            }
+        }
+
+        // a ratio of two lengths or two angles has no unit
+        impl Div for $name {
+            type Output = f32;
+
+            fn div(self, rhs: Self) -> f32 {
+                self.0 / rhs.0
+            }
+        }
+
+        impl float_cmp::ApproxEq for $name {
+            type Margin = float_cmp::F32Margin;
+
+            fn approx_eq<M: Into<Self::Margin>>(self, other: Self, margin: M) -> bool {
+                self.0.approx_eq(other.0, margin)
+            }
+        }
+    };
+}
+
+unit!(Mm);
+unit!(Inches);
+unit!(Px);
+unit!(Degrees);
+unit!(Radians);
+
+// 2 in min distance from mini to edge
+pub const STANDOFF_DISTANCE: Mm = Inches(2.0).to_mm();
+// the solvers assume a one inch base
+pub const BASE_DIAMETER: Mm = Inches(1.0).to_mm();
+// how big the visualizers draw the table
+pub const PX_PER_MM: f32 = 3.0;
+
+impl Mm {
+    pub const fn to_inches(self) -> Inches {
+        Inches(self.0 / MM_PER_INCH)
+    }
+}
+
+impl Inches {
+    pub const fn to_mm(self) -> Mm {
+        Mm(self.0 * MM_PER_INCH)
+    }
+}
+
+impl From<Inches> for Mm {
+    fn from(inches: Inches) -> Self {
+        inches.to_mm()
+    }
+}
+
+impl From<Mm> for Inches {
+    fn from(mm: Mm) -> Self {
+        mm.to_inches()
+    }
+}
+
+impl Degrees {
+    pub fn to_radians(self) -> Radians {
+        Radians(self.0.to_radians())
+    }
+
+    // wrapped into [0, 360)
+    pub fn normalized(self) -> Self {
+        Self(self.0.rem_euclid(360.0))
+    }
+
+    pub fn sin(self) -> f32 {
+        self.to_radians().sin()
+    }
+
+    pub fn cos(self) -> f32 {
+        self.to_radians().cos()
+    }
+
+    pub fn tan(self) -> f32 {
+        self.to_radians().tan()
+    }
+}
+
+impl Radians {
+    pub fn to_degrees(self) -> Degrees {
+        Degrees(self.0.to_degrees())
+    }
+
+    pub fn atan2(y: f32, x: f32) -> Self {
+        Self(y.atan2(x))
+    }
+
+    pub fn sin(self) -> f32 {
+        self.0.sin()
+    }
+
+    pub fn cos(self) -> f32 {
+        self.0.cos()
+    }
+
+    pub fn tan(self) -> f32 {
+        self.0.tan()
+    }
+}
+
+impl From<Degrees> for Radians {
+    fn from(degrees: Degrees) -> Self {
+        degrees.to_radians()
+    }
+}
+
+impl From<Radians> for Degrees {
+    fn from(radians: Radians) -> Self {
+        radians.to_degrees()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenPoint {
+    pub x: Px,
+    pub y: Px,
+}
+
+impl From<ScreenPoint> for (f32, f32) {
+    fn from(point: ScreenPoint) -> Self {
+        (point.x.0, point.y.0)
+    }
+}
+
+// Table mm to screen px. The table origin is the bottom left corner with y going up, the screen origin is the top
+// left corner with y going down.
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    px_per_mm: f32,
+    table_height: Mm,
+}
+
+impl Transform {
+    pub const fn new(px_per_mm: f32, table_height: Mm) -> Self {
+        Self {
+            px_per_mm,
+            table_height,
+        }
+    }
+
+    pub fn length_to_screen(&self, length: Mm) -> Px {
+        Px(length.0 * self.px_per_mm)
+    }
+
+    pub fn length_to_table(&self, length: Px) -> Mm {
+        Mm(length.0 / self.px_per_mm)
+    }
+
+    pub fn to_screen(&self, point: Point) -> ScreenPoint {
+        ScreenPoint {
+            x: self.length_to_screen(Mm(point.x)),
+            y: self.length_to_screen(self.table_height - Mm(point.y)),
+        }
+    }
+
+    pub fn to_table(&self, point: ScreenPoint) -> Point {
+        Point {
+            x: self.length_to_table(point.x).0,
+            y: (self.table_height - self.length_to_table(point.y)).0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use float_cmp::assert_approx_eq;
+
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_approx_eq!(Mm, Inches(2.0).to_mm(), Mm(50.8));
+        assert_approx_eq!(Inches, Mm::from(Inches(3.0)).to_inches(), Inches(3.0));
+        assert_approx_eq!(Mm, STANDOFF_DISTANCE, Mm(2.0 * MM_PER_INCH));
+
+        assert_approx_eq!(
+            Radians,
+            Degrees(180.0).to_radians(),
+            Radians(std::f32::consts::PI)
+        );
+        assert_approx_eq!(Degrees, Radians::atan2(1.0, 1.0).into(), Degrees(45.0));
+        assert_approx_eq!(Degrees, Degrees(-90.0).normalized(), Degrees(270.0));
+        assert_approx_eq!(Degrees, Degrees(720.0).normalized(), Degrees(0.0));
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(Mm(3.0) + Mm(4.0), Mm(7.0));
+        assert_eq!(Mm(3.0) - Mm(4.0), Mm(-1.0));
+        assert_eq!(-Degrees(10.0), Degrees(-10.0));
+        assert_eq!(Degrees(10.0) * 2.0, Degrees(20.0));
+        assert_eq!(2.0 * Px(10.0), Px(20.0));
+        assert_eq!(Mm(10.0) / 4.0, Mm(2.5));
+        assert_eq!(Mm(10.0) / Mm(4.0), 2.5);
+        assert!(Degrees(10.0) < Degrees(20.0));
+    }
+
+    #[test]
+    fn transform_round_trip() {
+        let transform = Transform::new(3.0, Mm(500.0));
+
+        let screen = transform.to_screen(Point { x: 100.0, y: 0.0 });
+        assert_eq!(
+            screen,
+            ScreenPoint {
+                x: Px(300.0),
+                y: Px(1500.0)
+            }
+        );
+
+        let table = transform.to_table(ScreenPoint {
+            x: Px(30.0),
+            y: Px(0.0),
+        });
+        assert_eq!(table, Point { x: 10.0, y: 500.0 });
+
+        let point = Point { x: 123.4, y: 321.0 };
+        let round_trip = transform.to_table(transform.to_screen(point));
+        assert_approx_eq!(Point, round_trip, point, epsilon = 0.001);
+    }
+}
@@ -0,0 +1,270 @@
+// Emitter slot timing over time. Every mini runs a mini_mount SlotClock off its own crystal, which is off from the
+// table's by up to the crystal tolerance, and only gets back in step when it hears a sync broadcast. A mini that keeps
+// missing them drifts further and further out, until its flash crosses the guard into the next slot over, where the
+// table puts it down to the wrong mini or it lands on top of the neighbour's.
+
+use anyhow::{anyhow, bail};
+use mini_mount::schedule::{FrameConfig, SlotClock, SlotDecoder};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug)]
+pub struct DriftConfig {
+    // each gets its own slot, after the sync slot
+    pub minis: u8,
+    pub slot_us: u32,
+    pub guard_us: u32,
+    // how far each mini's crystal can be off, either way
+    pub crystal_ppm: f32,
+    // chance a mini misses any one sync broadcast
+    pub sync_loss: f32,
+    pub frames: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DriftResult {
+    pub sync_interval: u32,
+    pub emissions: usize,
+    // flashes that overlapped another mini's
+    pub collisions: usize,
+    // times a mini wasn't seen cleanly in its own slot in a frame
+    pub missed_slots: usize,
+    // flashes the table put down, at least partly, to another slot
+    pub misdecoded: usize,
+    // the most frames any mini went without a sync
+    pub longest_unsynced: u32,
+}
+
+impl DriftResult {
+    pub fn safe(&self) -> bool {
+        self.collisions == 0 && self.misdecoded == 0
+    }
+}
+
+impl DriftConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.minis == 0 || self.minis == u8::MAX {
+            bail!("need between 1 and {} minis", u8::MAX - 1);
+        }
+        if !(0.0..=1.0).contains(&self.sync_loss) {
+            bail!("sync loss must be between 0 and 1");
+        }
+        if self.crystal_ppm < 0.0 {
+            bail!("crystal tolerance can't be negative");
+        }
+        // anywhere near a million ppm a crystal could stop or run backwards
+        if !self.crystal_ppm.is_finite() || self.crystal_ppm >= 100_000.0 {
+            bail!("crystal tolerance must be under 100,000 ppm");
+        }
+        self.schedule()?;
+        Ok(())
+    }
+
+    // The schedule's own drift check is left out, so the simulation shows what happens past it, and the minis trust a
+    // sync for the whole run however old it is.
+    fn schedule(&self) -> anyhow::Result<FrameConfig> {
+        FrameConfig::new(
+            self.minis + 1,
+            self.slot_us,
+            self.guard_us,
+            self.frames + 1,
+            0,
+        )
+        .map_err(|e| anyhow!("can't schedule that: {e:?}"))
+    }
+
+    // how far the worst crystal gets out each frame, in us
+    pub fn drift_per_frame(&self) -> f64 {
+        (self.minis as f64 + 1.0) * self.slot_us as f64 * self.crystal_ppm as f64 * 1e-6
+    }
+
+    // The most frames between syncs before the worst crystal can drift past the guard, if no sync is ever missed.
+    // None if the crystals are perfect.
+    pub fn safe_interval(&self) -> Option<u32> {
+        let drift = self.drift_per_frame();
+        (drift > 0.0).then(|| (self.guard_us as f64 / drift).floor() as u32)
+    }
+}
+
+struct Crystal {
+    // how fast it runs against the table's clock
+    rate: f64,
+    // what it reads when the table's reads 0
+    offset: u32,
+}
+
+impl Crystal {
+    fn at(&self, table_time: f64) -> u32 {
+        self.offset
+            .wrapping_add((table_time * self.rate).round() as u64 as u32)
+    }
+}
+
+pub fn run(config: &DriftConfig, sync_interval: u32, seed: u64) -> anyhow::Result<DriftResult> {
+    if sync_interval == 0 {
+        bail!("sync interval must be at least a frame");
+    }
+    let schedule = config.schedule()?;
+    let frame_us = schedule.frame_us() as f64;
+    let flash_us = (config.slot_us - 2 * config.guard_us) as f64;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let crystals: Vec<Crystal> = (0..config.minis)
+        .map(|_| Crystal {
+            rate: 1.0 + rng.gen_range(-config.crystal_ppm..=config.crystal_ppm) as f64 * 1e-6,
+            offset: rng.gen(),
+        })
+        .collect();
+    let mut clocks = vec![SlotClock::new(schedule); config.minis as usize];
+    let mut last_sync: Vec<Option<u32>> = vec![None; config.minis as usize];
+    let mut decoder = SlotDecoder::new(schedule);
+
+    let mut result = DriftResult {
+        sync_interval,
+        emissions: 0,
+        collisions: 0,
+        missed_slots: 0,
+        misdecoded: 0,
+        longest_unsynced: 0,
+    };
+
+    for frame in 0..config.frames {
+        let start = frame as f64 * frame_us;
+        if frame % sync_interval == 0 {
+            decoder.synchronize(start as u32);
+            for (i, clock) in clocks.iter_mut().enumerate() {
+                if rng.gen::<f32>() >= config.sync_loss {
+                    clock.synchronize(crystals[i].at(start));
+                    last_sync[i] = Some(frame);
+                }
+            }
+        }
+
+        // when each mini flashes this frame, in table time
+        let mut flashes = Vec::new();
+        for (i, (clock, crystal)) in clocks.iter().zip(&crystals).enumerate() {
+            let address = i as u8 + 1;
+            if let Some(synced) = last_sync[i] {
+                result.longest_unsynced = result.longest_unsynced.max(frame - synced);
+            }
+            let now = crystal.at(start);
+            match clock.next_emission(now, address) {
+                Some(local) => {
+                    let from = start + local.wrapping_sub(now) as f64 / crystal.rate;
+                    flashes.push((address, from, from + flash_us / crystal.rate));
+                }
+                None => result.missed_slots += 1,
+            }
+        }
+
+        for &(address, from, to) in &flashes {
+            result.emissions += 1;
+            let clean = decoder.decode(from.floor() as u32) == Some(address)
+                && decoder.decode(to.ceil() as u32 - 1) == Some(address);
+            if !clean {
+                result.misdecoded += 1;
+                result.missed_slots += 1;
+            }
+            if flashes
+                .iter()
+                .any(|&(other, f, t)| other != address && f < to && from < t)
+            {
+                result.collisions += 1;
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+// every interval with the same crystals, so they can be compared
+pub fn sweep(
+    config: &DriftConfig,
+    intervals: &[u32],
+    seed: u64,
+) -> anyhow::Result<Vec<DriftResult>> {
+    intervals
+        .par_iter()
+        .map(|&interval| run(config, interval, seed))
+        .collect()
+}
+
+// the longest interval in `results` that, along with every shorter one, never put a flash in the wrong slot
+pub fn largest_safe(results: &[DriftResult]) -> Option<u32> {
+    let mut sorted: Vec<&DriftResult> = results.iter().collect();
+    sorted.sort_by_key(|r| r.sync_interval);
+    sorted
+        .iter()
+        .take_while(|r| r.safe())
+        .last()
+        .map(|r| r.sync_interval)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 8 minis in 1ms slots, 9ms frames
+    fn config(crystal_ppm: f32, sync_loss: f32) -> DriftConfig {
+        DriftConfig {
+            minis: 8,
+            slot_us: 1000,
+            guard_us: 50,
+            crystal_ppm,
+            sync_loss,
+            frames: 2000,
+        }
+    }
+
+    #[test]
+    fn perfect_crystals() {
+        let config = config(0.0, 0.0);
+        assert_eq!(config.safe_interval(), None);
+
+        let result = run(&config, 500, 0).unwrap();
+        assert_eq!(result.emissions, 8 * 2000);
+        assert_eq!(result.missed_slots, 0);
+        assert!(result.safe());
+        assert_eq!(result.longest_unsynced, 499);
+    }
+
+    #[test]
+    fn drifting_past_the_guard() {
+        let config = config(100.0, 0.0);
+        // 0.9us a frame against a 50us guard
+        assert_eq!(config.safe_interval(), Some(55));
+
+        let results = sweep(&config, &[10, 50, 400], 1).unwrap();
+        assert!(results[0].safe());
+        assert_eq!(results[0].missed_slots, 0);
+        assert!(results[1].safe());
+        assert!(results[2].misdecoded > 0);
+        assert!(results[2].missed_slots > 0);
+        assert_eq!(largest_safe(&results), Some(50));
+
+        // a mini running fast against one running slow in the next slot
+        let collided = (0..10).any(|seed| run(&config, 1500, seed).unwrap().collisions > 0);
+        assert!(collided);
+    }
+
+    #[test]
+    fn lost_syncs() {
+        let never = run(&config(50.0, 1.0), 10, 0).unwrap();
+        assert_eq!(never.emissions, 0);
+        assert_eq!(never.missed_slots, 8 * 2000);
+
+        // missing syncs stretches how long the minis go on an old one
+        let lossy = run(&config(50.0, 0.5), 10, 0).unwrap();
+        assert!(lossy.longest_unsynced >= 20);
+        assert!(lossy.missed_slots > 0);
+
+        assert!(config(50.0, 1.5).validate().is_err());
+        assert!(config(1e6, 0.0).validate().is_err());
+        assert!(config(f32::INFINITY, 0.0).validate().is_err());
+        assert!(config(f32::NAN, 0.0).validate().is_err());
+        assert!(config(50.0, 0.0).validate().is_ok());
+        assert!(run(&config(50.0, 0.0), 0, 0).is_err());
+    }
+}
@@ -1,270 +1,914 @@
-use mini_tracker::{self, Point, Receiver, Table};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use anyhow::{bail, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
+use mini_tracker::layout::LayoutFile;
+use mini_tracker::recording::Recording;
+use mini_tracker::units::{Degrees, Mm, BASE_DIAMETER, MM_PER_INCH, STANDOFF_DISTANCE};
+use mini_tracker::Table;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use drift::DriftConfig;
+use noise::{Perturbation, RobustnessSummary, TrialResult};
+use optimize::{OptimizeOptions, Placement};
+use scenario::Scenario;
+use sim::{Config, TableSize, TestResult};
+use sweep::SweepRow;
 
 // mod receiver_placements;
+mod drift;
+mod noise;
+mod optimize;
+mod scenario;
+mod sim;
+mod sweep;
+
+const TABLE_WIDTH: Mm = Mm(930.0 + STANDOFF_DISTANCE.0);
+const TABLE_HEIGHT: Mm = Mm(523.0 + STANDOFF_DISTANCE.0);
+
+#[derive(Parser)]
+#[command(about = "Simulate how well receiver layouts can locate a mini")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-// 1 px == 1 mm
-// 25mm == ~1 in
-const MM_PER_INCH: f32 = 25.4;
-
-// grid is
-// |
-// |
-// |
-// .--------
-
-// 2 in min distance from mini to edge
-const STANDOFF_DISTANCE: f32 = 2.0 * MM_PER_INCH;
-const TABLE_WIDTH: f32 = 930.0 + STANDOFF_DISTANCE;
-const TABLE_HEIGHT: f32 = 523.0 + STANDOFF_DISTANCE;
-
-const GRID_SIZE: f32 = MM_PER_INCH;
-
-fn get_mini_edge_points(mini_center: Point) -> [Point; 360] {
-    let mut points = [Point { x: 0.0, y: 0.0 }; 360];
-    let distance = MM_PER_INCH / 2.0;
-    for (i, point) in points.iter_mut().enumerate() {
-        let angle = i as f32;
-        point.x = mini_center.x + distance * angle.to_radians().cos();
-        point.y = mini_center.y + distance * angle.to_radians().sin();
-    }
-    points
+#[derive(Subcommand)]
+enum Command {
+    /// Try every combination of densities, view angles and footprints and report the ones that locate the mini
+    /// correctly everywhere, or all of them with --all
+    Sweep(SweepArgs),
+    /// Evaluate one layout and report every grid position
+    Evaluate(EvaluateArgs),
+    /// Search for a cheaper non-uniform layout that keeps the error under a limit everywhere
+    Optimize(OptimizeArgs),
+    /// Run one layout many times with imperfect receivers and report how the accuracy holds up
+    Robustness(RobustnessArgs),
+    /// Play a scenario of several minis over one layout and report how well each is located
+    Scenario(ScenarioArgs),
+    /// Run the emitter slot timing over time with imperfect crystals and lost syncs, for a range of sync intervals
+    Drift(DriftArgs),
 }
 
-fn place_vertical_receivers(view_angle: f32, receivers_per_mm: f32) -> Vec<Receiver> {
-    let mut receivers = Vec::new();
-    let mut y = MM_PER_INCH / 2.0;
-    while y < TABLE_HEIGHT {
-        receivers.push(Receiver::new(
-            TABLE_WIDTH,
-            TABLE_HEIGHT,
-            view_angle,
-            Point { x: 0.0, y },
-            mini_tracker::Direction::Right,
-        ));
-        receivers.push(Receiver::new(
-            TABLE_WIDTH,
-            TABLE_HEIGHT,
-            view_angle,
-            Point { x: TABLE_WIDTH, y },
-            mini_tracker::Direction::Left,
-        ));
+#[derive(Args)]
+struct TableArgs {
+    /// Layout file to take the table size (and for evaluate, the receivers) from
+    #[arg(long, conflicts_with_all = ["width", "height"])]
+    layout: Option<PathBuf>,
+    /// Table width in mm
+    #[arg(long, default_value_t = TABLE_WIDTH.0)]
+    width: f32,
+    /// Table height in mm
+    #[arg(long, default_value_t = TABLE_HEIGHT.0)]
+    height: f32,
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /// Sweeps write json as one object per line so they can be appended to
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+    /// Where to write results, stdout if not given
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Args)]
+struct SweepArgs {
+    #[command(flatten)]
+    table: TableArgs,
+    /// Receivers per inch on the left and right edges, as min:max:step or a single value
+    #[arg(long, default_value = "0.5:3:0.5")]
+    vert_density: SweepRange,
+    /// Receivers per inch on the top and bottom edges
+    #[arg(long, default_value = "0.5:3:0.5")]
+    horiz_density: SweepRange,
+    /// View angle in degrees of the left and right receivers
+    #[arg(long, default_value = "10:90:10")]
+    vert_view_angle: SweepRange,
+    /// View angle in degrees of the top and bottom receivers
+    #[arg(long, default_value = "10:90:10")]
+    horiz_view_angle: SweepRange,
+    /// Base diameter in mm of the mini being tracked
+    #[arg(long, default_value_t = SweepRange::single(BASE_DIAMETER.0))]
+    footprint: SweepRange,
+    /// Spacing in mm of the grid of mini locations, also how far off a guess can be and still count
+    #[arg(long, default_value_t = MM_PER_INCH)]
+    grid_step: f32,
+    /// Carry on from the configurations already in --output instead of starting over
+    #[arg(long)]
+    resume: bool,
+    /// Report every configuration, not just the ones that are correct everywhere
+    #[arg(long)]
+    all: bool,
+    /// Number of threads to run configurations on, all cores if not given
+    #[arg(long, short)]
+    jobs: Option<usize>,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+// one layout: either the receivers in a layout file or uniform ones from densities and view angles
+#[derive(Args)]
+struct LayoutArgs {
+    #[command(flatten)]
+    table: TableArgs,
+    /// Receivers per inch on the left and right edges, ignored if the layout file has receivers
+    #[arg(long, default_value_t = 1.0)]
+    vert_density: f32,
+    #[arg(long, default_value_t = 1.0)]
+    horiz_density: f32,
+    #[arg(long, default_value_t = 30.0)]
+    vert_view_angle: f32,
+    #[arg(long, default_value_t = 30.0)]
+    horiz_view_angle: f32,
+    #[arg(long, default_value_t = BASE_DIAMETER.0)]
+    footprint: f32,
+    #[arg(long, default_value_t = MM_PER_INCH)]
+    grid_step: f32,
+}
+
+#[derive(Args)]
+struct EvaluateArgs {
+    #[command(flatten)]
+    layout: LayoutArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args)]
+struct RobustnessArgs {
+    #[command(flatten)]
+    layout: LayoutArgs,
+    /// How far in degrees each receiver can be rotated from where the layout says
+    #[arg(long, default_value_t = 0.0)]
+    angle_jitter: f32,
+    /// How far in mm each receiver can be from where the layout says, in x and y
+    #[arg(long, default_value_t = 0.0)]
+    position_tolerance: f32,
+    /// Chance a receiver reports seeing a mini it can't see
+    #[arg(long, default_value_t = 0.0)]
+    false_positive_rate: f32,
+    /// Chance a receiver misses a mini it can see
+    #[arg(long, default_value_t = 0.0)]
+    false_negative_rate: f32,
+    /// Chance a receiver is dead for the whole trial
+    #[arg(long, default_value_t = 0.0)]
+    dead_rate: f32,
+    #[arg(long, default_value_t = 100)]
+    trials: u64,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args)]
+struct OptimizeArgs {
+    /// Table size, and the layout to start from if it has receivers
+    #[command(flatten)]
+    table: TableArgs,
+    /// Where to write the best layout found
+    #[arg(long, short)]
+    output: PathBuf,
+    /// Largest error in mm allowed anywhere on the playable area
+    #[arg(long, default_value_t = MM_PER_INCH)]
+    max_error: f32,
+    #[arg(long, default_value_t = 1.0)]
+    receiver_cost: f32,
+    /// Extra cost per receiver per degree of view angle
+    #[arg(long, default_value_t = 0.0)]
+    view_angle_cost: f32,
+    /// Cost of each position that fails, or that is max-error over the limit
+    #[arg(long, default_value_t = 10.0)]
+    penalty: f32,
+    #[arg(long, default_value_t = 10.0)]
+    min_view_angle: f32,
+    #[arg(long, default_value_t = 90.0)]
+    max_view_angle: f32,
+    /// Receiver spacing in mm of the uniform layout to start from when there's no layout file
+    #[arg(long, default_value_t = MM_PER_INCH)]
+    spacing: f32,
+    /// View angle of the uniform layout to start from
+    #[arg(long, default_value_t = 30.0)]
+    view_angle: f32,
+    #[arg(long, default_value_t = 2000)]
+    iterations: usize,
+    #[arg(long, default_value_t = 5.0)]
+    start_temperature: f32,
+    #[arg(long, default_value_t = 0.05)]
+    end_temperature: f32,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    #[arg(long, default_value_t = BASE_DIAMETER.0)]
+    footprint: f32,
+    /// A coarser grid than the sweep's keeps each iteration quick
+    #[arg(long, default_value_t = 2.0 * MM_PER_INCH)]
+    grid_step: f32,
+}
+
+#[derive(Args)]
+struct ScenarioArgs {
+    /// Scenario file describing the minis and how they move
+    scenario: PathBuf,
+    /// The grid step is how far off a guess can be and still count
+    #[command(flatten)]
+    layout: LayoutArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    /// Also write what the table saw and solved each frame as a recording the visualizer can replay
+    #[arg(long)]
+    record: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct DriftArgs {
+    /// Minis on the table, each in its own slot
+    #[arg(long, default_value_t = 8)]
+    minis: u8,
+    /// Slot length in microseconds
+    #[arg(long, default_value_t = 1000)]
+    slot_us: u32,
+    /// How long an emitter stays dark at each end of its slot, in microseconds
+    #[arg(long, default_value_t = 50)]
+    guard_us: u32,
+    /// How far in parts per million each mini's crystal can be off, either way
+    #[arg(long, default_value_t = 50.0)]
+    crystal_ppm: f32,
+    /// Chance a mini misses any one sync broadcast
+    #[arg(long, default_value_t = 0.0)]
+    sync_loss: f32,
+    /// Frames between sync broadcasts, as min:max:step or a single value
+    #[arg(long, default_value = "1:100:1")]
+    sync_interval: SweepRange,
+    #[arg(long, default_value_t = 10_000)]
+    frames: u32,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+// min:max:step, inclusive of max
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SweepRange {
+    min: f32,
+    max: f32,
+    step: f32,
+}
 
-        y += MM_PER_INCH / receivers_per_mm;
+impl SweepRange {
+    fn single(value: f32) -> Self {
+        Self {
+            min: value,
+            max: value,
+            step: 1.0,
+        }
     }
 
-    receivers
+    // counted rather than accumulated so 0.1 steps don't drift past max
+    fn values(&self) -> Vec<f32> {
+        let count = ((self.max - self.min) / self.step + 0.0001).floor() as usize + 1;
+        (0..count)
+            .map(|i| self.min + self.step * i as f32)
+            .collect()
+    }
 }
 
-fn place_horizontal_receivers(view_angle: f32, receivers_per_mm: f32) -> Vec<Receiver> {
-    let mut receivers = Vec::new();
-    let mut x = MM_PER_INCH / 2.0;
-    while x < TABLE_WIDTH {
-        receivers.push(Receiver::new(
-            TABLE_WIDTH,
-            TABLE_HEIGHT,
-            view_angle,
-            Point { x, y: 0.0 },
-            mini_tracker::Direction::Up,
-        ));
-        receivers.push(Receiver::new(
-            TABLE_WIDTH,
-            TABLE_HEIGHT,
-            view_angle,
-            Point { x, y: TABLE_HEIGHT },
-            mini_tracker::Direction::Down,
-        ));
+impl FromStr for SweepRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<f32>()
+                .map_err(|_| format!("'{v}' is not a number"))
+        };
+
+        let parts: Vec<&str> = s.split(':').collect();
+        let range = match parts[..] {
+            [value] => Self::single(parse(value)?),
+            [min, max, step] => Self {
+                min: parse(min)?,
+                max: parse(max)?,
+                step: parse(step)?,
+            },
+            _ => {
+                return Err(format!(
+                    "expected min:max:step or a single value, got '{s}'"
+                ))
+            }
+        };
 
-        x += MM_PER_INCH / receivers_per_mm;
+        if range.max < range.min {
+            return Err(format!("max is less than min in '{s}'"));
+        }
+        if range.step <= 0.0 {
+            return Err(format!("step must be positive in '{s}'"));
+        }
+        Ok(range)
     }
+}
 
-    receivers
+impl std::fmt::Display for SweepRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.min == self.max {
+            write!(f, "{}", self.min)
+        } else {
+            write!(f, "{}:{}:{}", self.min, self.max, self.step)
+        }
+    }
 }
 
-#[derive(Debug)]
-struct TestResult {
-    total_receivers: usize,
-    all_correct: bool,
-    avg_area: f32,
-    avg_error: f32,
-    max_area: f32,
-    max_error: f32,
+impl TableArgs {
+    fn load(&self) -> anyhow::Result<LayoutFile> {
+        match &self.layout {
+            Some(path) => {
+                LayoutFile::load(path).with_context(|| format!("loading layout {}", path.display()))
+            }
+            None => Ok(LayoutFile {
+                width: Mm(self.width),
+                height: Mm(self.height),
+                receivers: Vec::new(),
+            }),
+        }
+    }
 }
 
-fn run_test(
-    vert_density: f32,
-    horiz_density: f32,
-    vert_view_angle: f32,
-    horiz_view_angle: f32,
-) -> TestResult {
-    let mut receivers = place_horizontal_receivers(horiz_view_angle, horiz_density);
-    receivers.append(&mut place_vertical_receivers(vert_view_angle, vert_density));
-
-    let total_receivers = receivers.len();
-
-    let table = Table::new(TABLE_WIDTH, TABLE_HEIGHT, receivers);
-
-    // For each valid table location, check if table can determine mini location within error bounds
-    let mut num_correct = 0;
-    let mut tot_locations = 0;
-    let mut avg_area = 0.0;
-    let mut avg_error = 0.0;
-    let mut max_error = 0.0;
-    let mut max_area = 0.0;
-
-    let mut x = STANDOFF_DISTANCE + MM_PER_INCH / 2.0;
-    while x < TABLE_WIDTH - STANDOFF_DISTANCE - MM_PER_INCH / 2.0 {
-        let mut y = STANDOFF_DISTANCE + MM_PER_INCH / 2.0;
-        while y < TABLE_HEIGHT - STANDOFF_DISTANCE - MM_PER_INCH / 2.0 {
-            tot_locations += 1;
-            let mini_location = Point { x, y };
-            let mut num_visible_receivers = 0;
-            let mini_edge_points = get_mini_edge_points(mini_location);
-            let mut visible_receivers = Vec::new();
-            for receiver in table.receivers.iter() {
-                let mut can_see = false;
-                for point in &mini_edge_points {
-                    if receiver.can_see(point) {
-                        can_see = true;
-                        break;
+fn open_output(path: Option<&Path>) -> anyhow::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).with_context(|| format!("creating {}", path.display()))?,
+        )),
+        None => Box::new(BufWriter::new(io::stdout())),
+    })
+}
+
+fn csv_field(value: Option<f32>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn sweep(args: &SweepArgs) -> anyhow::Result<()> {
+    let layout = args.table.load()?;
+    let size = TableSize {
+        width: layout.width,
+        height: layout.height,
+    };
+    let grid_step = Mm(args.grid_step);
+    if grid_step.0 <= 0.0 {
+        bail!("grid step must be positive");
+    }
+    let format = args.output.format;
+
+    let mut configs = Vec::new();
+    for vert_density in args.vert_density.values() {
+        for horiz_density in args.horiz_density.values() {
+            for vert_view_angle in args.vert_view_angle.values() {
+                for horiz_view_angle in args.horiz_view_angle.values() {
+                    for footprint in args.footprint.values() {
+                        configs.push(Config {
+                            vert_density,
+                            horiz_density,
+                            vert_view_angle: Degrees(vert_view_angle),
+                            horiz_view_angle: Degrees(horiz_view_angle),
+                            footprint: Mm(footprint),
+                        });
                     }
                 }
-                if can_see {
-                    num_visible_receivers += 1;
-                }
-                visible_receivers.push((*receiver, can_see));
             }
+        }
+    }
 
-            assert!(num_visible_receivers > 0);
-
-            // println!("{:?}", mini_location);
-            // dbg!(
-            //     vert_density,
-            //     horiz_density,
-            //     vert_view_angle,
-            //     horiz_view_angle
-            // );
-            let Some(bounding_polygon) = table.get_bounding_polygon(&visible_receivers[..]) else {
-                return TestResult {
-                    total_receivers,
-                    all_correct: false,
-                    avg_area: avg_area / tot_locations as f32,
-                    avg_error: avg_error / tot_locations as f32,
-                    max_area,
-                    max_error,
-                };
-            };
-            assert!(!bounding_polygon.points.is_empty());
-            // let Some(shrink_polygon) = bounding_polygon.shrink(25.4 / 2.0) else {
-            //     return TestResult {
-            //         total_receivers,
-            //         all_correct: false,
-            //         avg_area: avg_area / tot_locations as f32,
-            //         avg_error: avg_error / tot_locations as f32,
-            //         max_area,
-            //         max_error,
-            //     };
-            // };
-            let guessed_location = bounding_polygon.center();
-            let error = 0.0; //shrink_polygon.max_width();
-            let area = bounding_polygon.area();
-            avg_area += area;
-            avg_error += error;
-
-            if guessed_location.distance(&mini_location) < GRID_SIZE {
-                num_correct += 1;
-            } else {
-                // dbg!(mini_location, guessed_location);
-                return TestResult {
-                    total_receivers,
-                    all_correct: false,
-                    avg_area: avg_area / tot_locations as f32,
-                    avg_error: avg_error / tot_locations as f32,
-                    max_area,
-                    max_error,
+    let (mut out, done, header): (Box<dyn Write + Send>, _, _) =
+        match (&args.output.output, args.resume) {
+            (None, true) => bail!("--resume needs --output"),
+            (None, false) => (Box::new(io::stdout()), HashSet::new(), true),
+            (Some(path), resume) => {
+                let previous = if resume && path.exists() {
+                    fs::read_to_string(path)
+                        .with_context(|| format!("reading {}", path.display()))?
+                } else {
+                    String::new()
                 };
+                let previous = sweep::complete_lines(&previous);
+                let done = sweep::completed_configs(previous, format)?;
+
+                // drop any row that was cut off part way through, then carry on from the end. Cut in place rather than
+                // written out again, so being stopped now can't lose what's already there.
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(path)
+                    .with_context(|| format!("opening {}", path.display()))?;
+                file.set_len(previous.len() as u64)?;
+                file.seek(SeekFrom::End(0))?;
+                (Box::new(file), done, previous.is_empty())
             }
+        };
 
-            if error > max_error {
-                max_error = error;
-                // dbg!(mini_location);
+    if header {
+        if let Format::Csv = format {
+            writeln!(out, "{}", sweep::CSV_HEADER)?;
+        }
+    }
+
+    let pending: Vec<Config> = configs
+        .iter()
+        .filter(|config| !done.contains(&sweep::config_key(config)))
+        .copied()
+        .collect();
+
+    let progress = ProgressBar::new(configs.len() as u64).with_style(
+        ProgressStyle::with_template(
+            "{elapsed_precise} [{bar:40}] {pos}/{len} configurations, {eta} left",
+        )
+        .unwrap(),
+    );
+    progress.set_position((configs.len() - pending.len()) as u64);
+
+    // each row is written and flushed as soon as its configuration finishes, in whatever order the threads finish
+    let out = Mutex::new(out);
+    pending
+        .par_iter()
+        .try_for_each(|config| -> io::Result<()> {
+            let result = sim::run_test(size, config, grid_step);
+            // a failing configuration gives up at its first wrong location, so running it again on --resume is cheap
+            if !result.all_correct && !args.all {
+                progress.inc(1);
+                return Ok(());
             }
+            let line = sweep::format_row(
+                &SweepRow {
+                    result,
+                    config: *config,
+                },
+                format,
+            );
+
+            let mut out = out.lock().unwrap();
+            out.write_all(line.as_bytes())?;
+            out.flush()?;
+            progress.inc(1);
+            Ok(())
+        })?;
+    progress.finish();
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Evaluation {
+    summary: TestResult,
+    positions: Vec<sim::PositionResult>,
+}
+
+impl LayoutArgs {
+    fn build(&self) -> anyhow::Result<(TableSize, Table)> {
+        let layout = self.table.load()?;
+        let size = TableSize {
+            width: layout.width,
+            height: layout.height,
+        };
+        if self.grid_step <= 0.0 {
+            bail!("grid step must be positive");
+        }
 
-            if area > max_area {
-                max_area = area;
-                // dbg!(mini_location);
+        let table = if layout.receivers.is_empty() {
+            sim::uniform_table(
+                size,
+                &Config {
+                    vert_density: self.vert_density,
+                    horiz_density: self.horiz_density,
+                    vert_view_angle: Degrees(self.vert_view_angle),
+                    horiz_view_angle: Degrees(self.horiz_view_angle),
+                    footprint: Mm(self.footprint),
+                },
+            )
+        } else {
+            sim::layout_table(&layout, Mm(self.footprint))
+        };
+
+        Ok((size, table))
+    }
+}
+
+fn evaluate(args: &EvaluateArgs) -> anyhow::Result<()> {
+    let (size, table) = args.layout.build()?;
+    let footprint = Mm(args.layout.footprint);
+    let grid_step = Mm(args.layout.grid_step);
+
+    let positions: Vec<_> = sim::grid_positions(size, footprint, grid_step)
+        .into_iter()
+        .map(|location| sim::evaluate_position(&table, location, footprint, grid_step))
+        .collect();
+    let summary = sim::summarize(table.receivers.len(), &positions);
+
+    let mut out = open_output(args.output.output.as_deref())?;
+    match args.output.format {
+        Format::Csv => {
+            writeln!(
+                out,
+                "x,y,visible_receivers,guess_x,guess_y,area,error,correct"
+            )?;
+            for p in &positions {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{},{}",
+                    p.x,
+                    p.y,
+                    p.visible_receivers,
+                    csv_field(p.guess_x),
+                    csv_field(p.guess_y),
+                    csv_field(p.area),
+                    csv_field(p.error),
+                    p.correct
+                )?;
             }
-            y += GRID_SIZE;
         }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, &Evaluation { summary, positions })?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()?;
+
+    eprintln!(
+        "{} receivers, all correct: {}, avg area {:.1}, avg error {:.2}, max area {:.1}, max error {:.2}",
+        summary.total_receivers,
+        summary.all_correct,
+        summary.avg_area,
+        summary.avg_error,
+        summary.max_area,
+        summary.max_error
+    );
+    Ok(())
+}
 
-        x += GRID_SIZE;
+fn optimize(args: &OptimizeArgs) -> anyhow::Result<()> {
+    let layout = args.table.load()?;
+    let size = TableSize {
+        width: layout.width,
+        height: layout.height,
+    };
+    if args.grid_step <= 0.0 || args.max_error <= 0.0 {
+        bail!("grid step and max error must be positive");
+    }
+    if args.spacing <= 0.0 {
+        bail!("spacing must be positive");
+    }
+    // past these the edges of a receiver's view never cross the table
+    if [args.min_view_angle, args.max_view_angle, args.view_angle]
+        .iter()
+        .any(|a| *a <= 0.0 || *a >= 180.0)
+    {
+        bail!("view angles must be between 0 and 180 degrees");
+    }
+    if args.min_view_angle > args.max_view_angle {
+        bail!("min view angle can't be more than max view angle");
+    }
+    if args.start_temperature <= 0.0 || args.end_temperature <= 0.0 {
+        bail!("temperatures must be positive");
+    }
+    if args.end_temperature > args.start_temperature {
+        bail!("end temperature can't be more than start temperature");
     }
 
-    TestResult {
-        total_receivers,
-        all_correct: num_correct == tot_locations,
-        avg_area: avg_area / tot_locations as f32,
-        avg_error: avg_error / tot_locations as f32,
-        max_area,
-        max_error,
+    let options = OptimizeOptions {
+        size,
+        footprint: Mm(args.footprint),
+        grid_step: Mm(args.grid_step),
+        max_error: Mm(args.max_error),
+        receiver_cost: args.receiver_cost,
+        view_angle_cost: args.view_angle_cost,
+        penalty: args.penalty,
+        min_view_angle: Degrees(args.min_view_angle),
+        max_view_angle: Degrees(args.max_view_angle),
+        iterations: args.iterations,
+        start_temperature: args.start_temperature,
+        end_temperature: args.end_temperature,
+        seed: args.seed,
+    };
+
+    let start = if layout.receivers.is_empty() {
+        Placement::uniform(size, Mm(args.spacing), Degrees(args.view_angle))
+    } else {
+        Placement::from_layout(&layout)
+    };
+
+    let progress = ProgressBar::new(args.iterations as u64).with_style(
+        ProgressStyle::with_template("{elapsed_precise} [{bar:40}] {pos}/{len} {msg}").unwrap(),
+    );
+    let (best, score) = optimize::anneal(start, &options, |i, best| {
+        progress.set_position(i as u64);
+        progress.set_message(format!(
+            "best cost {:.1}, {} receivers{}",
+            best.cost,
+            best.receivers,
+            if best.feasible { "" } else { ", not feasible" }
+        ));
+    });
+    progress.finish();
+
+    best.to_layout(size)
+        .save(&args.output)
+        .with_context(|| format!("writing {}", args.output.display()))?;
+
+    eprintln!(
+        "{} receivers, cost {:.1}, max error {:.2}, {} failed positions",
+        score.receivers, score.cost, score.max_error, score.failures
+    );
+    if !score.feasible {
+        eprintln!(
+            "no layout met the error limit everywhere, wrote the closest one to {}",
+            args.output.display()
+        );
     }
+    Ok(())
 }
 
-fn main() {
-    const VERT_DENSITY_MIN: f32 = 0.5;
-    const VERT_DENSITY_MAX: f32 = 3.0;
-    const HORIZ_DENSITY_MIN: f32 = 0.5;
-    const HORIZ_DENSITY_MAX: f32 = 3.0;
-    const VERT_VIEW_ANGLE_MIN: f32 = 10.0;
-    const VERT_VIEW_ANGLE_MAX: f32 = 90.0;
-    const HORIZ_VIEW_ANGLE_MIN: f32 = 10.0;
-    const HORIZ_VIEW_ANGLE_MAX: f32 = 90.0;
-
-    const VERT_DENSITY_STEP: f32 = 0.5;
-    const HORIZ_DENSITY_STEP: f32 = 0.5;
-    const VERT_VIEW_ANGLE_STEP: f32 = 10.0;
-    const HORIZ_VIEW_ANGLE_STEP: f32 = 10.0;
-
-    println!("total_receivers,avg_area,avg_error,max_area,max_error,vert_density,horiz_density,vert_view_angle,horiz_view_angle");
-
-    let mut vert_density = VERT_DENSITY_MIN;
-    while vert_density <= VERT_DENSITY_MAX {
-        let mut horiz_density = HORIZ_DENSITY_MIN;
-        while horiz_density <= HORIZ_DENSITY_MAX {
-            let mut vert_view_angle = VERT_VIEW_ANGLE_MIN;
-            while vert_view_angle <= VERT_VIEW_ANGLE_MAX {
-                let mut horiz_view_angle = HORIZ_VIEW_ANGLE_MIN;
-                while horiz_view_angle <= HORIZ_VIEW_ANGLE_MAX {
-                    let result = run_test(
-                        vert_density,
-                        horiz_density,
-                        vert_view_angle,
-                        horiz_view_angle,
-                    );
-
-                    if result.all_correct {
-                        println!(
-                            "{},{},{},{},{},{},{},{},{}",
-                            result.total_receivers,
-                            result.avg_area,
-                            result.avg_error,
-                            result.max_area,
-                            result.max_error,
-                            vert_density,
-                            horiz_density,
-                            vert_view_angle,
-                            horiz_view_angle
-                        );
-                    }
+#[derive(Serialize)]
+struct Robustness {
+    summary: RobustnessSummary,
+    trials: Vec<TrialResult>,
+}
 
-                    horiz_view_angle += HORIZ_VIEW_ANGLE_STEP;
-                }
+fn robustness(args: &RobustnessArgs) -> anyhow::Result<()> {
+    let (size, table) = args.layout.build()?;
+    let footprint = Mm(args.layout.footprint);
+    let grid_step = Mm(args.layout.grid_step);
+    for rate in [
+        args.false_positive_rate,
+        args.false_negative_rate,
+        args.dead_rate,
+    ] {
+        if !(0.0..=1.0).contains(&rate) {
+            bail!("rates must be between 0 and 1");
+        }
+    }
+
+    let perturbation = Perturbation {
+        angle_jitter: Degrees(args.angle_jitter),
+        position_tolerance: Mm(args.position_tolerance),
+        false_positive_rate: args.false_positive_rate,
+        false_negative_rate: args.false_negative_rate,
+        dead_rate: args.dead_rate,
+    };
+    let positions = sim::grid_positions(size, footprint, grid_step);
+    let (trials, summary) = noise::run_trials(
+        &table,
+        &positions,
+        footprint,
+        grid_step,
+        &perturbation,
+        args.seed,
+        args.trials,
+    );
+
+    let mut out = open_output(args.output.output.as_deref())?;
+    match args.output.format {
+        Format::Csv => {
+            writeln!(
+                out,
+                "trial,dead_receivers,positions,failures,median_error,p95_error,max_error"
+            )?;
+            for t in &trials {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{}",
+                    t.trial,
+                    t.dead_receivers,
+                    t.positions,
+                    t.failures,
+                    t.median_error,
+                    t.p95_error,
+                    t.max_error
+                )?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, &Robustness { summary, trials })?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()?;
+
+    eprintln!(
+        "{} trials, failure rate {:.2}%, {:.0}% of trials had a failure, error median {:.2} p95 {:.2} max {:.2}",
+        summary.trials,
+        summary.failure_rate * 100.0,
+        summary.trial_failure_rate * 100.0,
+        summary.median_error,
+        summary.p95_error,
+        summary.max_error
+    );
+    Ok(())
+}
 
-                vert_view_angle += VERT_VIEW_ANGLE_STEP;
+fn scenario(args: &ScenarioArgs) -> anyhow::Result<()> {
+    let scenario = Scenario::load(&args.scenario)?;
+    let (_, table) = args.layout.build()?;
+    let frames = scenario::run(&scenario, &table, Mm(args.layout.grid_step));
+
+    if let Some(path) = &args.record {
+        let mut recording = Recording::new(&table, Mm(args.layout.footprint));
+        recording.frames = frames.iter().map(|f| f.recorded.clone()).collect();
+        recording
+            .save(path)
+            .with_context(|| format!("writing recording {}", path.display()))?;
+    }
+
+    let mut out = open_output(args.output.output.as_deref())?;
+    match args.output.format {
+        Format::Csv => {
+            writeln!(out, "time,mini,x,y,visible_receivers,occluded_receivers,guess_x,guess_y,distance,error,correct,ambiguous_with")?;
+            for m in frames.iter().flat_map(|f| &f.minis) {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{},{},{},{},{},{}",
+                    m.time,
+                    m.mini,
+                    m.x,
+                    m.y,
+                    m.visible_receivers,
+                    m.occluded_receivers,
+                    csv_field(m.guess_x),
+                    csv_field(m.guess_y),
+                    csv_field(m.distance),
+                    csv_field(m.error),
+                    m.correct,
+                    m.ambiguous_with.join(";")
+                )?;
             }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, &frames)?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()?;
+
+    for (i, mini) in scenario.minis.iter().enumerate() {
+        let results: Vec<_> = frames.iter().map(|f| &f.minis[i]).collect();
+        eprintln!(
+            "{}: {} frames, {} wrong, max distance {:.2}, occluded in {}, ambiguous in {}",
+            mini.name,
+            results.len(),
+            results.iter().filter(|m| !m.correct).count(),
+            results
+                .iter()
+                .filter_map(|m| m.distance)
+                .fold(0.0, f32::max),
+            results.iter().filter(|m| m.occluded_receivers > 0).count(),
+            results
+                .iter()
+                .filter(|m| !m.ambiguous_with.is_empty())
+                .count()
+        );
+    }
+    Ok(())
+}
 
-            horiz_density += HORIZ_DENSITY_STEP;
+fn drift(args: &DriftArgs) -> anyhow::Result<()> {
+    let config = DriftConfig {
+        minis: args.minis,
+        slot_us: args.slot_us,
+        guard_us: args.guard_us,
+        crystal_ppm: args.crystal_ppm,
+        sync_loss: args.sync_loss,
+        frames: args.frames,
+    };
+    config.validate()?;
+    let intervals: Vec<u32> = args
+        .sync_interval
+        .values()
+        .into_iter()
+        .map(|v| v.round() as u32)
+        .collect();
+    let results = drift::sweep(&config, &intervals, args.seed)?;
+
+    let mut out = open_output(args.output.output.as_deref())?;
+    match args.output.format {
+        Format::Csv => {
+            writeln!(
+                out,
+                "sync_interval,emissions,collisions,missed_slots,misdecoded,longest_unsynced"
+            )?;
+            for r in &results {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{}",
+                    r.sync_interval,
+                    r.emissions,
+                    r.collisions,
+                    r.missed_slots,
+                    r.misdecoded,
+                    r.longest_unsynced
+                )?;
+            }
         }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, &results)?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()?;
+
+    match config.safe_interval() {
+        Some(frames) => eprintln!(
+            "crystals drift up to {:.2}us a frame, a {}us guard allows a sync every {frames} frames if none are missed",
+            config.drift_per_frame(),
+            config.guard_us
+        ),
+        None => eprintln!("perfect crystals never drift"),
+    }
+    match drift::largest_safe(&results) {
+        Some(frames) => eprintln!(
+            "largest sync interval with no collisions or misdecoded flashes at {:.0}% sync loss: {frames} frames",
+            config.sync_loss * 100.0
+        ),
+        None => eprintln!("every sync interval tried put flashes in the wrong slot"),
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match &cli.command {
+        Command::Sweep(args) => {
+            if let Some(jobs) = args.jobs {
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(jobs)
+                    .build_global()?;
+            }
+            sweep(args)
+        }
+        Command::Evaluate(args) => evaluate(args),
+        Command::Optimize(args) => optimize(args),
+        Command::Robustness(args) => robustness(args),
+        Command::Scenario(args) => scenario(args),
+        Command::Drift(args) => drift(args),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sweep_ranges() {
+        let range: SweepRange = "0.5:3:0.5".parse().unwrap();
+        assert_eq!(range.values(), vec![0.5, 1.0, 1.5, 2.0, 2.5, 3.0]);
+
+        let range: SweepRange = "0:0.3:0.1".parse().unwrap();
+        assert_eq!(range.values().len(), 4);
+
+        let range: SweepRange = "25.4".parse().unwrap();
+        assert_eq!(range.values(), vec![25.4]);
+        assert_eq!(range.to_string().parse::<SweepRange>().unwrap(), range);
+
+        assert!("3:1:1".parse::<SweepRange>().is_err());
+        assert!("1:3:0".parse::<SweepRange>().is_err());
+        assert!("1:3".parse::<SweepRange>().is_err());
+        assert!("a".parse::<SweepRange>().is_err());
+    }
 
-        vert_density += VERT_DENSITY_STEP;
+    #[test]
+    fn cli_parses() {
+        Cli::try_parse_from([
+            "simulator",
+            "sweep",
+            "--vert-density",
+            "1",
+            "--footprint",
+            "20:30:5",
+            "--format",
+            "json",
+        ])
+        .unwrap();
+        // a layout file already gives the table size
+        assert!(Cli::try_parse_from([
+            "simulator",
+            "evaluate",
+            "--layout",
+            "a.json",
+            "--width",
+            "5",
+        ])
+        .is_err());
     }
 }
@@ -0,0 +1,352 @@
+// Real boards don't match the layout exactly: receivers get soldered a little off position and a little rotated, some
+// don't work at all, and the ones that do occasionally report the wrong thing. Each trial builds one such imperfect
+// copy of the table, works out what it would really see, and hands that to the solver, which still believes the
+// nominal layout.
+
+use mini_tracker::units::{Degrees, Mm};
+use mini_tracker::{Point, Receiver, Table};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::sim;
+
+// all spreads are uniform, ± the given amount
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Perturbation {
+    pub angle_jitter: Degrees,
+    pub position_tolerance: Mm,
+    // chance a receiver that can't see the mini says it can
+    pub false_positive_rate: f32,
+    // chance a receiver that can see the mini says it can't
+    pub false_negative_rate: f32,
+    // chance a receiver never sees anything
+    pub dead_rate: f32,
+}
+
+// where a receiver really is and which way it really points
+#[derive(Clone, Copy, Debug)]
+struct ActualReceiver {
+    location: Point,
+    facing: Degrees,
+    view_angle: Degrees,
+    dead: bool,
+}
+
+impl ActualReceiver {
+    fn perturb(nominal: &Receiver, perturbation: &Perturbation, rng: &mut StdRng) -> Self {
+        let spread = |rng: &mut StdRng, amount: f32| {
+            if amount > 0.0 {
+                rng.gen_range(-amount..=amount)
+            } else {
+                0.0
+            }
+        };
+
+        let tolerance = perturbation.position_tolerance.0;
+        Self {
+            location: Point {
+                x: nominal.location.x + spread(rng, tolerance),
+                y: nominal.location.y + spread(rng, tolerance),
+            },
+            facing: nominal.facing.to_degrees() + Degrees(spread(rng, perturbation.angle_jitter.0)),
+            view_angle: nominal.view_angle,
+            dead: rng.gen::<f32>() < perturbation.dead_rate,
+        }
+    }
+
+    fn can_see(&self, point: &Point) -> bool {
+        let offset = (self.location.angle(point) - self.facing).normalized();
+        let offset = if offset > Degrees(180.0) {
+            offset - Degrees(360.0)
+        } else {
+            offset
+        };
+        // the same slack at the edges as Receiver::can_see, so a perfect receiver sees exactly what the solver expects
+        offset.abs() <= self.view_angle / 2.0 + Degrees(0.01)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct TrialResult {
+    pub trial: u64,
+    pub dead_receivers: usize,
+    pub positions: usize,
+    pub failures: usize,
+    // distance from each guess to where the mini really was
+    pub median_error: f32,
+    pub p95_error: f32,
+    pub max_error: f32,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct RobustnessSummary {
+    pub trials: usize,
+    // fraction of all positions over all trials that were wrong or couldn't be solved
+    pub failure_rate: f32,
+    // fraction of trials with at least one failed position
+    pub trial_failure_rate: f32,
+    pub median_error: f32,
+    pub p95_error: f32,
+    pub max_error: f32,
+}
+
+// nearest rank, `values` must be sorted
+pub fn percentile(values: &[f32], p: f32) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let rank = (p * values.len() as f32).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+fn sort(values: &mut [f32]) {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+}
+
+// One imperfect build of `table`. The trial number is mixed into the seed so any single trial can be rerun on its own.
+// Also returns every guess error, for the overall distribution.
+pub fn run_trial(
+    table: &Table,
+    positions: &[Point],
+    footprint: Mm,
+    tolerance: Mm,
+    perturbation: &Perturbation,
+    seed: u64,
+    trial: u64,
+) -> (TrialResult, Vec<f32>) {
+    let mut rng = StdRng::seed_from_u64(seed ^ trial.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+
+    let actual: Vec<ActualReceiver> = table
+        .receivers
+        .iter()
+        .map(|r| ActualReceiver::perturb(r, perturbation, &mut rng))
+        .collect();
+
+    let mut errors = Vec::new();
+    let mut failures = 0;
+    for location in positions {
+        let edge_points = sim::get_mini_edge_points(*location, footprint);
+        let observed: Vec<(Receiver, bool)> = table
+            .receivers
+            .iter()
+            .zip(&actual)
+            .map(|(nominal, actual)| {
+                let sees = !actual.dead && edge_points.iter().any(|p| actual.can_see(p));
+                let reported = if actual.dead {
+                    false
+                } else if sees {
+                    rng.gen::<f32>() >= perturbation.false_negative_rate
+                } else {
+                    rng.gen::<f32>() < perturbation.false_positive_rate
+                };
+                (*nominal, reported)
+            })
+            .collect();
+
+        let result = sim::solve_position(table, &observed, *location, footprint, tolerance);
+        match (result.guess_x, result.guess_y) {
+            (Some(x), Some(y)) => {
+                errors.push(Point { x, y }.distance(location));
+                if !result.correct {
+                    failures += 1;
+                }
+            }
+            _ => failures += 1,
+        }
+    }
+
+    let mut sorted = errors.clone();
+    sort(&mut sorted);
+    (
+        TrialResult {
+            trial,
+            dead_receivers: actual.iter().filter(|a| a.dead).count(),
+            positions: positions.len(),
+            failures,
+            median_error: percentile(&sorted, 0.5),
+            p95_error: percentile(&sorted, 0.95),
+            max_error: sorted.last().copied().unwrap_or(0.0),
+        },
+        errors,
+    )
+}
+
+pub fn run_trials(
+    table: &Table,
+    positions: &[Point],
+    footprint: Mm,
+    tolerance: Mm,
+    perturbation: &Perturbation,
+    seed: u64,
+    trials: u64,
+) -> (Vec<TrialResult>, RobustnessSummary) {
+    let runs: Vec<_> = (0..trials)
+        .into_par_iter()
+        .map(|trial| {
+            run_trial(
+                table,
+                positions,
+                footprint,
+                tolerance,
+                perturbation,
+                seed,
+                trial,
+            )
+        })
+        .collect();
+
+    let mut errors: Vec<f32> = runs.iter().flat_map(|(_, e)| e.iter().copied()).collect();
+    sort(&mut errors);
+    let results: Vec<TrialResult> = runs.into_iter().map(|(r, _)| r).collect();
+
+    let total_positions: usize = results.iter().map(|r| r.positions).sum();
+    let total_failures: usize = results.iter().map(|r| r.failures).sum();
+    let summary = RobustnessSummary {
+        trials: results.len(),
+        failure_rate: total_failures as f32 / total_positions.max(1) as f32,
+        trial_failure_rate: results.iter().filter(|r| r.failures > 0).count() as f32
+            / results.len().max(1) as f32,
+        median_error: percentile(&errors, 0.5),
+        p95_error: percentile(&errors, 0.95),
+        max_error: errors.last().copied().unwrap_or(0.0),
+    };
+
+    (results, summary)
+}
+
+#[cfg(test)]
+mod test {
+    use mini_tracker::units::{BASE_DIAMETER, MM_PER_INCH, STANDOFF_DISTANCE};
+
+    use super::*;
+    use crate::sim::{Config, TableSize};
+
+    const SIZE: TableSize = TableSize {
+        width: Mm(400.0 + STANDOFF_DISTANCE.0),
+        height: Mm(300.0 + STANDOFF_DISTANCE.0),
+    };
+
+    fn setup() -> (Table, Vec<Point>) {
+        let table = sim::uniform_table(
+            SIZE,
+            &Config {
+                vert_density: 1.0,
+                horiz_density: 1.0,
+                vert_view_angle: Degrees(30.0),
+                horiz_view_angle: Degrees(30.0),
+                footprint: BASE_DIAMETER,
+            },
+        );
+        let positions = sim::grid_positions(SIZE, BASE_DIAMETER, Mm(80.0));
+        (table, positions)
+    }
+
+    #[test]
+    fn percentiles() {
+        let values: Vec<f32> = (1..=100).map(|v| v as f32).collect();
+        assert_eq!(percentile(&values, 0.5), 50.0);
+        assert_eq!(percentile(&values, 0.95), 95.0);
+        assert_eq!(percentile(&values, 0.0), 1.0);
+        assert_eq!(percentile(&values, 1.0), 100.0);
+        assert_eq!(percentile(&[], 0.5), 0.0);
+    }
+
+    #[test]
+    fn perfect_receivers_match_the_simulation() {
+        let (table, positions) = setup();
+        let tolerance = Mm(MM_PER_INCH);
+        let (results, summary) = run_trials(
+            &table,
+            &positions,
+            BASE_DIAMETER,
+            tolerance,
+            &Perturbation::default(),
+            1,
+            2,
+        );
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].dead_receivers, 0);
+        for location in &positions {
+            assert!(sim::evaluate_position(&table, *location, BASE_DIAMETER, tolerance).correct);
+        }
+        assert_eq!(summary.failure_rate, 0.0);
+        assert!(summary.median_error <= summary.p95_error);
+        assert!(summary.p95_error <= summary.max_error);
+    }
+
+    #[test]
+    fn perfect_receivers_agree_at_the_edges() {
+        let (table, _) = setup();
+        let mut rng = StdRng::seed_from_u64(0);
+        for receiver in &table.receivers {
+            let actual = ActualReceiver::perturb(receiver, &Perturbation::default(), &mut rng);
+            let facing = receiver.facing.to_degrees();
+            for edge in [-0.5, 0.5] {
+                for nudge in [-0.02, -0.005, 0.0, 0.005, 0.02] {
+                    let angle = (facing + receiver.view_angle * edge + Degrees(nudge)).to_radians();
+                    let point = Point {
+                        x: receiver.location.x + 100.0 * angle.0.cos(),
+                        y: receiver.location.y + 100.0 * angle.0.sin(),
+                    };
+                    assert_eq!(
+                        actual.can_see(&point),
+                        receiver.can_see(&point),
+                        "{point:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn seeded_and_noisy() {
+        let (table, positions) = setup();
+        let perturbation = Perturbation {
+            angle_jitter: Degrees(3.0),
+            position_tolerance: Mm(1.0),
+            false_positive_rate: 0.01,
+            false_negative_rate: 0.05,
+            dead_rate: 0.1,
+        };
+
+        let run = |seed| {
+            run_trials(
+                &table,
+                &positions,
+                BASE_DIAMETER,
+                Mm(MM_PER_INCH),
+                &perturbation,
+                seed,
+                3,
+            )
+        };
+        let (first, summary) = run(5);
+        let (again, _) = run(5);
+        for (a, b) in first.iter().zip(&again) {
+            assert_eq!(a.dead_receivers, b.dead_receivers);
+            assert_eq!(a.failures, b.failures);
+            assert_eq!(a.max_error, b.max_error);
+        }
+        assert!(first.iter().any(|r| r.dead_receivers > 0));
+
+        // everything dead means nothing can be located
+        let dead = Perturbation {
+            dead_rate: 1.0,
+            ..perturbation
+        };
+        let (_, dead_summary) = run_trials(
+            &table,
+            &positions,
+            BASE_DIAMETER,
+            Mm(MM_PER_INCH),
+            &dead,
+            5,
+            1,
+        );
+        assert_eq!(dead_summary.failure_rate, 1.0);
+        assert!(summary.failure_rate < 1.0);
+    }
+}
@@ -0,0 +1,355 @@
+// Simulated annealing over non-uniform layouts. Every edge holds its own list of receivers, each with its own position
+// along the edge and view angle. A layout costs its receivers plus a penalty for every grid position where the mini
+// can't be located or the error is over the limit, so the search is free to pass through layouts that don't work on
+// its way to cheaper ones that do.
+
+use mini_tracker::layout::{LayoutFile, ReceiverLayout};
+use mini_tracker::topology::edge_position;
+use mini_tracker::units::{Degrees, Mm};
+use mini_tracker::{Direction, Point};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::sim::{self, TableSize};
+
+#[derive(Clone, Copy, Debug)]
+pub struct OptimizeOptions {
+    pub size: TableSize,
+    pub footprint: Mm,
+    pub grid_step: Mm,
+    // the largest error allowed anywhere on the playable area
+    pub max_error: Mm,
+    pub receiver_cost: f32,
+    // extra cost per degree of view angle, for when wider lenses cost more
+    pub view_angle_cost: f32,
+    // cost of each position that fails, or of each max_error worth of error over the limit
+    pub penalty: f32,
+    pub min_view_angle: Degrees,
+    pub max_view_angle: Degrees,
+    pub iterations: usize,
+    pub start_temperature: f32,
+    pub end_temperature: f32,
+    pub seed: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EdgeReceiver {
+    pub along: Mm,
+    pub view_angle: Degrees,
+}
+
+// edges are numbered the same way as the discovery chain: 0 bottom, 1 right, 2 top, 3 left
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placement {
+    pub edges: [Vec<EdgeReceiver>; 4],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Score {
+    pub cost: f32,
+    pub receivers: usize,
+    pub max_error: f32,
+    pub failures: usize,
+    pub feasible: bool,
+}
+
+fn edge_length(size: TableSize, edge: usize) -> Mm {
+    if edge.is_multiple_of(2) {
+        size.width
+    } else {
+        size.height
+    }
+}
+
+impl Placement {
+    // evenly spaced receivers on every edge, starting half a space in from the corner
+    pub fn uniform(size: TableSize, spacing: Mm, view_angle: Degrees) -> Self {
+        let edges = std::array::from_fn(|edge| {
+            let mut receivers = Vec::new();
+            let mut along = spacing / 2.0;
+            while along < edge_length(size, edge) {
+                receivers.push(EdgeReceiver { along, view_angle });
+                along += spacing;
+            }
+            receivers
+        });
+
+        Self { edges }
+    }
+
+    // the inverse of to_layout, so a saved layout can be optimized further
+    pub fn from_layout(layout: &LayoutFile) -> Self {
+        let mut edges: [Vec<EdgeReceiver>; 4] = Default::default();
+        for r in &layout.receivers {
+            let (edge, along) = match r.facing {
+                Direction::Up => (0, r.x),
+                Direction::Left => (1, r.y),
+                Direction::Down => (2, layout.width - r.x),
+                Direction::Right => (3, layout.height - r.y),
+            };
+            edges[edge].push(EdgeReceiver {
+                along,
+                view_angle: r.view_angle,
+            });
+        }
+
+        Self { edges }
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.edges.iter().map(|e| e.len()).sum()
+    }
+
+    pub fn to_layout(&self, size: TableSize) -> LayoutFile {
+        let mut receivers = Vec::new();
+        for (edge, edge_receivers) in self.edges.iter().enumerate() {
+            let mut sorted = edge_receivers.clone();
+            sorted.sort_by(|a, b| a.along.partial_cmp(&b.along).unwrap());
+
+            for r in sorted {
+                let (location, facing) = edge_position(size.width, size.height, edge, r.along);
+                receivers.push(ReceiverLayout {
+                    x: Mm(location.x),
+                    y: Mm(location.y),
+                    facing,
+                    view_angle: r.view_angle,
+                });
+            }
+        }
+
+        LayoutFile {
+            width: size.width,
+            height: size.height,
+            receivers,
+        }
+    }
+
+    // the (edge, index) of a receiver picked uniformly over all edges
+    fn pick(&self, rng: &mut StdRng) -> Option<(usize, usize)> {
+        let count = self.receiver_count();
+        if count == 0 {
+            return None;
+        }
+
+        let mut n = rng.gen_range(0..count);
+        for (edge, receivers) in self.edges.iter().enumerate() {
+            if n < receivers.len() {
+                return Some((edge, n));
+            }
+            n -= receivers.len();
+        }
+        unreachable!()
+    }
+}
+
+pub fn score(placement: &Placement, options: &OptimizeOptions, positions: &[Point]) -> Score {
+    let table = sim::layout_table(&placement.to_layout(options.size), options.footprint);
+
+    let results: Vec<_> = positions
+        .par_iter()
+        .map(|location| {
+            sim::evaluate_position(&table, *location, options.footprint, options.grid_step)
+        })
+        .collect();
+
+    let mut violation = 0.0;
+    let mut failures = 0;
+    let mut max_error: f32 = 0.0;
+    for result in &results {
+        match result.error {
+            Some(error) if result.correct => {
+                max_error = max_error.max(error);
+                violation += ((error - options.max_error.0) / options.max_error.0).max(0.0);
+            }
+            _ => {
+                failures += 1;
+                violation += 1.0;
+            }
+        }
+    }
+
+    let receiver_cost: f32 = placement
+        .edges
+        .iter()
+        .flatten()
+        .map(|r| options.receiver_cost + options.view_angle_cost * r.view_angle.0)
+        .sum();
+
+    Score {
+        cost: receiver_cost + options.penalty * violation,
+        receivers: placement.receiver_count(),
+        max_error,
+        failures,
+        feasible: violation == 0.0,
+    }
+}
+
+fn mutate(placement: &Placement, options: &OptimizeOptions, rng: &mut StdRng) -> Placement {
+    let mut next = placement.clone();
+    let picked = next.pick(rng);
+
+    match (rng.gen_range(0..10), picked) {
+        // slide a receiver along its edge
+        (0..=3, Some((edge, i))) => {
+            let length = edge_length(options.size, edge);
+            let step = options.grid_step.0;
+            let r = &mut next.edges[edge][i];
+            r.along = Mm((r.along.0 + rng.gen_range(-step..=step)).clamp(0.0, length.0));
+        }
+        // widen or narrow a receiver's view
+        (4..=5, Some((edge, i))) => {
+            let r = &mut next.edges[edge][i];
+            r.view_angle = Degrees(
+                (r.view_angle.0 + rng.gen_range(-10.0..=10.0))
+                    .clamp(options.min_view_angle.0, options.max_view_angle.0),
+            );
+        }
+        (6..=7, Some((edge, i))) => {
+            next.edges[edge].remove(i);
+        }
+        // add a receiver somewhere, with every mm of edge equally likely
+        _ => {
+            let perimeter = 2.0 * (options.size.width + options.size.height).0;
+            let mut along = rng.gen_range(0.0..perimeter);
+            let mut edge = 0;
+            while along > edge_length(options.size, edge).0 && edge < 3 {
+                along -= edge_length(options.size, edge).0;
+                edge += 1;
+            }
+            let view_angle = if options.min_view_angle < options.max_view_angle {
+                rng.gen_range(options.min_view_angle.0..=options.max_view_angle.0)
+            } else {
+                options.min_view_angle.0
+            };
+            next.edges[edge].push(EdgeReceiver {
+                along: Mm(along),
+                view_angle: Degrees(view_angle),
+            });
+        }
+    }
+
+    next
+}
+
+// Returns the cheapest layout that met the error limit everywhere, or the cheapest layout seen if none did.
+// `progress` is called after every iteration with the iteration number and the best score so far.
+pub fn anneal(
+    start: Placement,
+    options: &OptimizeOptions,
+    mut progress: impl FnMut(usize, &Score),
+) -> (Placement, Score) {
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let positions = sim::grid_positions(options.size, options.footprint, options.grid_step);
+
+    let mut current_score = score(&start, options, &positions);
+    let mut current = start;
+    let mut best = (current.clone(), current_score);
+
+    let better = |a: &Score, b: &Score| (a.feasible, -a.cost) > (b.feasible, -b.cost);
+
+    for i in 0..options.iterations {
+        let fraction = i as f32 / options.iterations.max(1) as f32;
+        let temperature = options.start_temperature
+            * (options.end_temperature / options.start_temperature).powf(fraction);
+
+        let candidate = mutate(&current, options, &mut rng);
+        let candidate_score = score(&candidate, options, &positions);
+
+        let delta = candidate_score.cost - current_score.cost;
+        if delta <= 0.0 || rng.gen::<f32>() < (-delta / temperature).exp() {
+            current = candidate;
+            current_score = candidate_score;
+
+            if better(&current_score, &best.1) {
+                best = (current.clone(), current_score);
+            }
+        }
+
+        progress(i + 1, &best.1);
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SIZE: TableSize = TableSize {
+        width: Mm(360.0),
+        height: Mm(260.0),
+    };
+
+    fn options() -> OptimizeOptions {
+        OptimizeOptions {
+            size: SIZE,
+            footprint: Mm(25.4),
+            grid_step: Mm(60.0),
+            max_error: Mm(60.0),
+            receiver_cost: 1.0,
+            view_angle_cost: 0.0,
+            penalty: 20.0,
+            min_view_angle: Degrees(10.0),
+            max_view_angle: Degrees(90.0),
+            iterations: 40,
+            start_temperature: 2.0,
+            end_temperature: 0.05,
+            seed: 7,
+        }
+    }
+
+    #[test]
+    fn uniform_layout() {
+        let placement = Placement::uniform(SIZE, Mm(40.0), Degrees(30.0));
+        // 9 along the width, 6 along the height, on both sides
+        assert_eq!(placement.receiver_count(), 2 * 9 + 2 * 6);
+
+        let layout = placement.to_layout(SIZE);
+        assert_eq!(layout.receivers.len(), placement.receiver_count());
+        for r in &layout.receivers {
+            assert!(r.x.0 >= 0.0 && r.x <= SIZE.width);
+            assert!(r.y.0 >= 0.0 && r.y <= SIZE.height);
+        }
+        // the top edge runs right to left
+        assert_eq!(layout.receivers[15].x, Mm(340.0));
+        assert_eq!(layout.receivers[15].y, SIZE.height);
+
+        assert_eq!(Placement::from_layout(&layout), placement);
+    }
+
+    #[test]
+    fn anneal_keeps_a_feasible_layout() {
+        let options = options();
+        let start = Placement::uniform(SIZE, Mm(25.4), Degrees(30.0));
+        let positions = sim::grid_positions(SIZE, options.footprint, options.grid_step);
+        let start_score = score(&start, &options, &positions);
+        assert!(start_score.feasible);
+
+        let mut calls = 0;
+        let (best, best_score) = anneal(start.clone(), &options, |_, _| calls += 1);
+        assert_eq!(calls, options.iterations);
+        assert!(best_score.feasible);
+        assert!(best_score.cost <= start_score.cost);
+        assert_eq!(best_score.receivers, best.receiver_count());
+
+        // the same seed finds the same layout
+        let (again, _) = anneal(start, &options, |_, _| {});
+        assert_eq!(again, best);
+    }
+
+    #[test]
+    fn anneal_thins_an_over_dense_layout() {
+        let options = options();
+        // far more receivers than the error limit needs, so removing them should pay off
+        let start = Placement::uniform(SIZE, Mm(10.0), Degrees(30.0));
+        let positions = sim::grid_positions(SIZE, options.footprint, options.grid_step);
+        let start_score = score(&start, &options, &positions);
+        assert!(start_score.feasible);
+
+        let (best, best_score) = anneal(start.clone(), &options, |_, _| {});
+        assert!(best_score.feasible);
+        assert!(best_score.cost < start_score.cost);
+        assert!(best.receiver_count() < start.receiver_count());
+    }
+}
@@ -0,0 +1,549 @@
+// Several minis on the table at once, standing still or moving along paths. Each mini emits in its own slot so the
+// table always knows which mini an observation belongs to, but the other minis get in the way: a receiver only sees a
+// mini if some point on its base has a clear line to the receiver, over or around every other mini on the table.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use mini_tracker::recording::{RecordedFrame, RecordedMini};
+use mini_tracker::units::{Mm, BASE_DIAMETER};
+use mini_tracker::{Point, Polygon, Receiver, Table};
+use serde::{Deserialize, Serialize};
+
+use crate::sim;
+
+fn default_diameter() -> Mm {
+    BASE_DIAMETER
+}
+
+fn default_height() -> Mm {
+    Mm(30.0)
+}
+
+fn default_emitter_height() -> Mm {
+    Mm(5.0)
+}
+
+fn default_frame_interval() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    // seconds
+    pub t: f32,
+    pub x: Mm,
+    pub y: Mm,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MiniSpec {
+    pub name: String,
+    #[serde(default = "default_diameter")]
+    pub diameter: Mm,
+    // how tall the figure on the base is, anything lower than this behind it is hidden
+    #[serde(default = "default_height")]
+    pub height: Mm,
+    // a mini that stands still has just a position, one that moves has a path of keyframes to move between
+    #[serde(default)]
+    pub position: Option<Keyframe>,
+    #[serde(default)]
+    pub path: Vec<Keyframe>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Scenario {
+    #[serde(default = "default_frame_interval")]
+    pub frame_interval: f32,
+    // height above the table of the emitters on a base, and of the receivers along the edge
+    #[serde(default = "default_emitter_height")]
+    pub emitter_height: Mm,
+    #[serde(default = "default_emitter_height")]
+    pub receiver_height: Mm,
+    pub minis: Vec<MiniSpec>,
+}
+
+impl MiniSpec {
+    pub fn location_at(&self, t: f32) -> Point {
+        let keyframes: Vec<Keyframe> = match self.position {
+            Some(position) => vec![position],
+            None => self.path.clone(),
+        };
+
+        let point = |k: &Keyframe| Point { x: k.x.0, y: k.y.0 };
+        let first = &keyframes[0];
+        if t <= first.t {
+            return point(first);
+        }
+        for pair in keyframes.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            if t <= b.t {
+                let f = if b.t > a.t {
+                    (t - a.t) / (b.t - a.t)
+                } else {
+                    1.0
+                };
+                return Point {
+                    x: a.x.0 + (b.x.0 - a.x.0) * f,
+                    y: a.y.0 + (b.y.0 - a.y.0) * f,
+                };
+            }
+        }
+        point(keyframes.last().unwrap())
+    }
+
+    fn end_time(&self) -> f32 {
+        self.path.last().map(|k| k.t).unwrap_or(0.0)
+    }
+}
+
+impl Scenario {
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let scenario: Scenario = serde_json::from_str(json)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        Self::from_json(&json).with_context(|| format!("loading scenario {}", path.display()))
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.frame_interval <= 0.0 {
+            bail!("frame_interval must be positive");
+        }
+        if self.minis.is_empty() {
+            bail!("scenario has no minis");
+        }
+        let mut names = HashSet::new();
+        for mini in &self.minis {
+            if !names.insert(mini.name.as_str()) {
+                bail!("more than one mini is called {}", mini.name);
+            }
+            match (&mini.position, mini.path.is_empty()) {
+                (None, true) => bail!("{} needs a position or a path", mini.name),
+                (Some(_), false) => bail!("{} has both a position and a path", mini.name),
+                _ => {}
+            }
+            if mini.path.windows(2).any(|p| p[1].t < p[0].t) {
+                bail!("the path for {} goes back in time", mini.name);
+            }
+        }
+        Ok(())
+    }
+
+    // every frame time, from 0 to when the last mini stops moving
+    pub fn frame_times(&self) -> Vec<f32> {
+        let end = self.minis.iter().map(|m| m.end_time()).fold(0.0, f32::max);
+        let count = (end / self.frame_interval + 0.0001).floor() as usize + 1;
+        (0..count).map(|i| i as f32 * self.frame_interval).collect()
+    }
+}
+
+// Whether the sight line from `from` (at height from_z) to `to` (at to_z) passes through the body of a mini standing
+// at `center`. The line's height changes linearly along it, so the lowest it gets inside the mini is at one end of
+// the stretch that crosses the base.
+pub fn blocked(
+    from: Point,
+    from_z: Mm,
+    to: Point,
+    to_z: Mm,
+    center: Point,
+    radius: Mm,
+    height: Mm,
+) -> bool {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let (fx, fy) = (from.x - center.x, from.y - center.y);
+    let a = dx * dx + dy * dy;
+    let b = 2.0 * (fx * dx + fy * dy);
+    let c = fx * fx + fy * fy - radius.0 * radius.0;
+
+    if a == 0.0 {
+        return c < 0.0 && from_z < height;
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant <= 0.0 {
+        return false;
+    }
+
+    let root = discriminant.sqrt();
+    let t1 = ((-b - root) / (2.0 * a)).max(0.0);
+    let t2 = ((-b + root) / (2.0 * a)).min(1.0);
+    if t1 >= t2 {
+        return false;
+    }
+
+    let z = |t: f32| from_z.0 + (to_z.0 - from_z.0) * t;
+    z(t1).min(z(t2)) < height.0
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MiniResult {
+    pub time: f32,
+    pub mini: String,
+    pub x: f32,
+    pub y: f32,
+    pub visible_receivers: usize,
+    // receivers that would have seen this mini if the others weren't on the table
+    pub occluded_receivers: usize,
+    pub guess_x: Option<f32>,
+    pub guess_y: Option<f32>,
+    // how far the guess is from where the mini really is
+    pub distance: Option<f32>,
+    // how wide the region the centre could be in is
+    pub error: Option<f32>,
+    pub correct: bool,
+    pub ambiguous_with: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Ambiguity {
+    pub time: f32,
+    pub minis: (String, String),
+    pub overlap_area: f32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Frame {
+    pub time: f32,
+    pub minis: Vec<MiniResult>,
+    pub ambiguities: Vec<Ambiguity>,
+    // what the table saw and solved, for writing out as a recording
+    #[serde(skip)]
+    pub recorded: RecordedFrame,
+}
+
+// Solves every mini in one frame. The table knows which mini is which from its slot, but where the regions each
+// centre could be in overlap it can't tell how the two are arranged within the overlap, so those pairs are reported
+// as ambiguous.
+pub fn run_frame(scenario: &Scenario, table: &Table, time: f32, tolerance: Mm) -> Frame {
+    let locations: Vec<Point> = scenario.minis.iter().map(|m| m.location_at(time)).collect();
+
+    let mut results = Vec::new();
+    let mut recorded = Vec::new();
+    let mut centre_regions: Vec<Option<Polygon>> = Vec::new();
+    for (i, mini) in scenario.minis.iter().enumerate() {
+        let edge_points = sim::get_mini_edge_points(locations[i], mini.diameter);
+
+        let mut occluded = 0;
+        let observed: Vec<(Receiver, bool)> = table
+            .receivers
+            .iter()
+            .map(|receiver| {
+                let in_view: Vec<&Point> =
+                    edge_points.iter().filter(|p| receiver.can_see(p)).collect();
+                let clear = in_view.iter().any(|p| {
+                    !scenario.minis.iter().enumerate().any(|(j, other)| {
+                        j != i
+                            && blocked(
+                                **p,
+                                scenario.emitter_height,
+                                receiver.location,
+                                scenario.receiver_height,
+                                locations[j],
+                                other.diameter / 2.0,
+                                other.height,
+                            )
+                    })
+                });
+                if !in_view.is_empty() && !clear {
+                    occluded += 1;
+                }
+                (*receiver, clear)
+            })
+            .collect();
+
+        let mut result = MiniResult {
+            time,
+            mini: mini.name.clone(),
+            x: locations[i].x,
+            y: locations[i].y,
+            visible_receivers: observed.iter().filter(|(_, v)| *v).count(),
+            occluded_receivers: occluded,
+            guess_x: None,
+            guess_y: None,
+            distance: None,
+            error: None,
+            correct: false,
+            ambiguous_with: Vec::new(),
+        };
+
+        let bounding_polygon = table.get_bounding_polygon(&observed);
+        let centre_region = bounding_polygon
+            .as_ref()
+            .and_then(|p| p.offset(-mini.diameter / 2.0));
+        if let Some(bounding_polygon) = &bounding_polygon {
+            let guess = bounding_polygon.center();
+            let distance = guess.distance(&locations[i]);
+            result.guess_x = Some(guess.x);
+            result.guess_y = Some(guess.y);
+            result.distance = Some(distance);
+            result.error = Some(bounding_polygon.centre_error(mini.diameter));
+            result.correct = distance < tolerance.0;
+        }
+
+        let mut recorded_mini = RecordedMini::new(
+            &mini.name,
+            &observed,
+            bounding_polygon.as_ref(),
+            bounding_polygon.as_ref().map(|p| p.center()),
+        );
+        recorded_mini.actual = Some(locations[i]);
+        recorded.push(recorded_mini);
+
+        results.push(result);
+        centre_regions.push(centre_region);
+    }
+
+    let mut ambiguities = Vec::new();
+    for i in 0..results.len() {
+        for j in i + 1..results.len() {
+            let (Some(a), Some(b)) = (&centre_regions[i], &centre_regions[j]) else {
+                continue;
+            };
+            if let Some(overlap) = a.intersection(b) {
+                let overlap_area = overlap.area();
+                if overlap_area > 0.0 {
+                    let (name_a, name_b) = (results[i].mini.clone(), results[j].mini.clone());
+                    results[i].ambiguous_with.push(name_b.clone());
+                    results[j].ambiguous_with.push(name_a.clone());
+                    ambiguities.push(Ambiguity {
+                        time,
+                        minis: (name_a, name_b),
+                        overlap_area,
+                    });
+                }
+            }
+        }
+    }
+
+    Frame {
+        time,
+        minis: results,
+        ambiguities,
+        recorded: RecordedFrame {
+            time,
+            minis: recorded,
+        },
+    }
+}
+
+pub fn run(scenario: &Scenario, table: &Table, tolerance: Mm) -> Vec<Frame> {
+    scenario
+        .frame_times()
+        .into_iter()
+        .map(|time| run_frame(scenario, table, time, tolerance))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use mini_tracker::units::{Degrees, MM_PER_INCH, STANDOFF_DISTANCE};
+
+    use super::*;
+    use crate::sim::{Config, TableSize};
+
+    const SIZE: TableSize = TableSize {
+        width: Mm(500.0 + STANDOFF_DISTANCE.0),
+        height: Mm(400.0 + STANDOFF_DISTANCE.0),
+    };
+
+    fn table() -> Table {
+        sim::uniform_table(
+            SIZE,
+            &Config {
+                vert_density: 1.0,
+                horiz_density: 1.0,
+                vert_view_angle: Degrees(30.0),
+                horiz_view_angle: Degrees(30.0),
+                footprint: BASE_DIAMETER,
+            },
+        )
+    }
+
+    fn scenario(minis: &str) -> Scenario {
+        Scenario::from_json(&format!(r#"{{ "minis": [{minis}] }}"#)).unwrap()
+    }
+
+    #[test]
+    fn sight_lines() {
+        let from = Point { x: 0.0, y: 0.0 };
+        let to = Point { x: 100.0, y: 0.0 };
+        let centre = Point { x: 50.0, y: 5.0 };
+
+        assert!(blocked(
+            from,
+            Mm(5.0),
+            to,
+            Mm(5.0),
+            centre,
+            Mm(10.0),
+            Mm(30.0)
+        ));
+        // too short to get in the way
+        assert!(!blocked(
+            from,
+            Mm(5.0),
+            to,
+            Mm(5.0),
+            centre,
+            Mm(10.0),
+            Mm(3.0)
+        ));
+        // off to the side
+        assert!(!blocked(
+            from,
+            Mm(5.0),
+            to,
+            Mm(5.0),
+            Point { x: 50.0, y: 20.0 },
+            Mm(10.0),
+            Mm(30.0)
+        ));
+        // beyond the end of the line
+        assert!(!blocked(
+            from,
+            Mm(5.0),
+            to,
+            Mm(5.0),
+            Point { x: 120.0, y: 0.0 },
+            Mm(10.0),
+            Mm(30.0)
+        ));
+        // a receiver up high sees over a mini if the line is above its head where it crosses the base, 44mm up here
+        assert!(blocked(
+            from,
+            Mm(5.0),
+            to,
+            Mm(100.0),
+            centre,
+            Mm(10.0),
+            Mm(50.0)
+        ));
+        assert!(!blocked(
+            from,
+            Mm(5.0),
+            to,
+            Mm(100.0),
+            centre,
+            Mm(10.0),
+            Mm(20.0)
+        ));
+    }
+
+    #[test]
+    fn paths() {
+        let scenario = Scenario::from_json(
+            r#"{ "frame_interval": 0.5, "minis": [
+                { "name": "still", "position": { "t": 0, "x": 100, "y": 100 } },
+                { "name": "moving", "path": [
+                    { "t": 0, "x": 100, "y": 200 },
+                    { "t": 2, "x": 300, "y": 200 }
+                ] }
+            ] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(scenario.frame_times(), vec![0.0, 0.5, 1.0, 1.5, 2.0]);
+        assert_eq!(scenario.minis[0].diameter, BASE_DIAMETER);
+        assert_eq!(
+            scenario.minis[0].location_at(1.0),
+            Point { x: 100.0, y: 100.0 }
+        );
+        assert_eq!(
+            scenario.minis[1].location_at(0.5),
+            Point { x: 150.0, y: 200.0 }
+        );
+        assert_eq!(
+            scenario.minis[1].location_at(5.0),
+            Point { x: 300.0, y: 200.0 }
+        );
+
+        assert!(Scenario::from_json(r#"{ "minis": [] }"#).is_err());
+        assert!(Scenario::from_json(r#"{ "minis": [{ "name": "a" }] }"#).is_err());
+        assert!(Scenario::from_json(
+            r#"{ "minis": [{ "name": "a", "position": { "t": 0, "x": 1, "y": 1 },
+                             "path": [{ "t": 0, "x": 2, "y": 2 }] }] }"#
+        )
+        .is_err());
+        assert!(Scenario::from_json(
+            r#"{ "minis": [
+                { "name": "a", "position": { "t": 0, "x": 1, "y": 1 } },
+                { "name": "a", "position": { "t": 0, "x": 2, "y": 2 } }
+            ] }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn crowding() {
+        let table = table();
+        let tolerance = Mm(MM_PER_INCH);
+
+        let alone = scenario(r#"{ "name": "a", "position": { "t": 0, "x": 200, "y": 200 } }"#);
+        let frame = run_frame(&alone, &table, 0.0, tolerance);
+        assert_eq!(frame.minis[0].occluded_receivers, 0);
+        assert!(frame.minis[0].correct);
+        assert!(frame.ambiguities.is_empty());
+
+        // the recording holds the same solve
+        let recorded = &frame.recorded.minis[0];
+        assert_eq!(recorded.seen.len(), frame.minis[0].visible_receivers);
+        assert_eq!(recorded.actual, Some(Point { x: 200.0, y: 200.0 }));
+        assert_eq!(recorded.estimate.map(|e| e.x), frame.minis[0].guess_x);
+        assert!(recorded
+            .region()
+            .unwrap()
+            .contains(&Point { x: 200.0, y: 200.0 }));
+
+        // a tall mini right next to it hides it from the receivers on that side
+        let crowded = scenario(
+            r#"{ "name": "a", "position": { "t": 0, "x": 200, "y": 200 } },
+               { "name": "b", "height": 60, "position": { "t": 0, "x": 200, "y": 170 } }"#,
+        );
+        let frame = run_frame(&crowded, &table, 0.0, tolerance);
+        assert!(frame.minis[0].occluded_receivers > 0);
+        assert!(
+            frame.minis[0].visible_receivers
+                < run_frame(&alone, &table, 0.0, tolerance).minis[0].visible_receivers
+        );
+
+        // two flat minis side by side on a table with few enough receivers that both land in the same region
+        let sparse = sim::uniform_table(
+            SIZE,
+            &Config {
+                vert_density: 0.25,
+                horiz_density: 0.25,
+                vert_view_angle: Degrees(20.0),
+                horiz_view_angle: Degrees(20.0),
+                footprint: BASE_DIAMETER,
+            },
+        );
+        let side_by_side = scenario(
+            r#"{ "name": "a", "height": 1, "position": { "t": 0, "x": 200, "y": 200 } },
+               { "name": "b", "height": 1, "position": { "t": 0, "x": 230, "y": 200 } }"#,
+        );
+        let frame = run_frame(&side_by_side, &sparse, 0.0, tolerance);
+        assert_eq!(frame.ambiguities.len(), 1);
+        assert_eq!(
+            frame.ambiguities[0].minis,
+            ("a".to_string(), "b".to_string())
+        );
+        assert!(frame.ambiguities[0].overlap_area > 0.0);
+        assert_eq!(frame.minis[0].ambiguous_with, ["b"]);
+        assert_eq!(frame.minis[1].ambiguous_with, ["a"]);
+
+        // far apart, neither gets in the other's way or could be mistaken for the other
+        let apart = scenario(
+            r#"{ "name": "a", "position": { "t": 0, "x": 100, "y": 100 } },
+               { "name": "b", "position": { "t": 0, "x": 400, "y": 300 } }"#,
+        );
+        let frame = run_frame(&apart, &table, 0.0, tolerance);
+        assert!(frame.ambiguities.is_empty());
+        assert!(frame.minis.iter().all(|m| m.ambiguous_with.is_empty()));
+    }
+}
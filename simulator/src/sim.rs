@@ -0,0 +1,352 @@
+use mini_tracker::layout::LayoutFile;
+use mini_tracker::units::{Degrees, Mm, MM_PER_INCH, STANDOFF_DISTANCE};
+use mini_tracker::{self, Point, Receiver, Table};
+use serde::{Deserialize, Serialize};
+
+// grid is
+// |
+// |
+// |
+// .--------
+
+#[derive(Clone, Copy, Debug)]
+pub struct TableSize {
+    pub width: Mm,
+    pub height: Mm,
+}
+
+// one point in the sweep: receivers per inch and view angle for the left/right edges (vert) and the top/bottom edges
+// (horiz), plus the base diameter of the mini being tracked
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Config {
+    pub vert_density: f32,
+    pub horiz_density: f32,
+    pub vert_view_angle: Degrees,
+    pub horiz_view_angle: Degrees,
+    pub footprint: Mm,
+}
+
+pub fn get_mini_edge_points(mini_center: Point, footprint: Mm) -> [Point; 360] {
+    let mut points = [Point { x: 0.0, y: 0.0 }; 360];
+    let distance = footprint.0 / 2.0;
+    for (i, point) in points.iter_mut().enumerate() {
+        let angle = i as f32;
+        point.x = mini_center.x + distance * angle.to_radians().cos();
+        point.y = mini_center.y + distance * angle.to_radians().sin();
+    }
+    points
+}
+
+fn place_vertical_receivers(
+    size: TableSize,
+    view_angle: Degrees,
+    receivers_per_mm: f32,
+    footprint: Mm,
+) -> Vec<Receiver> {
+    let mut receivers = Vec::new();
+    let mut y = MM_PER_INCH / 2.0;
+    while y < size.height.0 {
+        receivers.push(Receiver::with_footprint(
+            size.width,
+            size.height,
+            view_angle,
+            Point { x: 0.0, y },
+            mini_tracker::Direction::Right,
+            footprint,
+        ));
+        receivers.push(Receiver::with_footprint(
+            size.width,
+            size.height,
+            view_angle,
+            Point { x: size.width.0, y },
+            mini_tracker::Direction::Left,
+            footprint,
+        ));
+
+        y += MM_PER_INCH / receivers_per_mm;
+    }
+
+    receivers
+}
+
+fn place_horizontal_receivers(
+    size: TableSize,
+    view_angle: Degrees,
+    receivers_per_mm: f32,
+    footprint: Mm,
+) -> Vec<Receiver> {
+    let mut receivers = Vec::new();
+    let mut x = MM_PER_INCH / 2.0;
+    while x < size.width.0 {
+        receivers.push(Receiver::with_footprint(
+            size.width,
+            size.height,
+            view_angle,
+            Point { x, y: 0.0 },
+            mini_tracker::Direction::Up,
+            footprint,
+        ));
+        receivers.push(Receiver::with_footprint(
+            size.width,
+            size.height,
+            view_angle,
+            Point {
+                x,
+                y: size.height.0,
+            },
+            mini_tracker::Direction::Down,
+            footprint,
+        ));
+
+        x += MM_PER_INCH / receivers_per_mm;
+    }
+
+    receivers
+}
+
+pub fn uniform_table(size: TableSize, config: &Config) -> Table {
+    let mut receivers = place_horizontal_receivers(
+        size,
+        config.horiz_view_angle,
+        config.horiz_density,
+        config.footprint,
+    );
+    receivers.append(&mut place_vertical_receivers(
+        size,
+        config.vert_view_angle,
+        config.vert_density,
+        config.footprint,
+    ));
+
+    Table::new(size.width, size.height, receivers)
+}
+
+// the receivers from a layout file, rebuilt so their expanded view bounds match the footprint being simulated
+pub fn layout_table(layout: &LayoutFile, footprint: Mm) -> Table {
+    let receivers = layout
+        .receivers
+        .iter()
+        .map(|r| {
+            Receiver::with_footprint(
+                layout.width,
+                layout.height,
+                r.view_angle,
+                Point { x: r.x.0, y: r.y.0 },
+                r.facing,
+                footprint,
+            )
+        })
+        .collect();
+
+    Table::new(layout.width, layout.height, receivers)
+}
+
+// every mini location to test, keeping the whole base clear of the standoff
+pub fn grid_positions(size: TableSize, footprint: Mm, grid_step: Mm) -> Vec<Point> {
+    let radius = footprint.0 / 2.0;
+    let mut positions = Vec::new();
+
+    let mut x = STANDOFF_DISTANCE.0 + radius;
+    while x < (size.width - STANDOFF_DISTANCE).0 - radius {
+        let mut y = STANDOFF_DISTANCE.0 + radius;
+        while y < (size.height - STANDOFF_DISTANCE).0 - radius {
+            positions.push(Point { x, y });
+            y += grid_step.0;
+        }
+        x += grid_step.0;
+    }
+
+    positions
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct PositionResult {
+    pub x: f32,
+    pub y: f32,
+    pub visible_receivers: usize,
+    pub guess_x: Option<f32>,
+    pub guess_y: Option<f32>,
+    pub area: Option<f32>,
+    pub error: Option<f32>,
+    pub correct: bool,
+}
+
+// a guess further than `tolerance` from the real location counts as wrong
+pub fn evaluate_position(
+    table: &Table,
+    mini_location: Point,
+    footprint: Mm,
+    tolerance: Mm,
+) -> PositionResult {
+    let mini_edge_points = get_mini_edge_points(mini_location, footprint);
+    let visible_receivers: Vec<(Receiver, bool)> = table
+        .receivers
+        .iter()
+        .map(|receiver| {
+            let can_see = mini_edge_points.iter().any(|point| receiver.can_see(point));
+            (*receiver, can_see)
+        })
+        .collect();
+
+    solve_position(
+        table,
+        &visible_receivers,
+        mini_location,
+        footprint,
+        tolerance,
+    )
+}
+
+// what the table makes of a set of receiver observations of a mini that is really at `mini_location`
+pub fn solve_position(
+    table: &Table,
+    visible_receivers: &[(Receiver, bool)],
+    mini_location: Point,
+    footprint: Mm,
+    tolerance: Mm,
+) -> PositionResult {
+    let mut result = PositionResult {
+        x: mini_location.x,
+        y: mini_location.y,
+        visible_receivers: visible_receivers.iter().filter(|(_, v)| *v).count(),
+        guess_x: None,
+        guess_y: None,
+        area: None,
+        error: None,
+        correct: false,
+    };
+
+    let Some(bounding_polygon) = table.get_bounding_polygon(visible_receivers) else {
+        return result;
+    };
+    let guessed_location = bounding_polygon.center();
+    let error = bounding_polygon.centre_error(footprint);
+
+    result.guess_x = Some(guessed_location.x);
+    result.guess_y = Some(guessed_location.y);
+    result.area = Some(bounding_polygon.area());
+    result.error = Some(error);
+    result.correct = guessed_location.distance(&mini_location) < tolerance.0;
+    result
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct TestResult {
+    pub total_receivers: usize,
+    pub all_correct: bool,
+    pub avg_area: f32,
+    pub avg_error: f32,
+    pub max_area: f32,
+    pub max_error: f32,
+}
+
+pub fn summarize(total_receivers: usize, positions: &[PositionResult]) -> TestResult {
+    let mut avg_area = 0.0;
+    let mut avg_error = 0.0;
+    let mut max_area = 0.0;
+    let mut max_error = 0.0;
+
+    for position in positions {
+        let area = position.area.unwrap_or(0.0);
+        let error = position.error.unwrap_or(0.0);
+        avg_area += area;
+        avg_error += error;
+        if area > max_area {
+            max_area = area;
+        }
+        if error > max_error {
+            max_error = error;
+        }
+    }
+
+    let count = positions.len().max(1) as f32;
+    TestResult {
+        total_receivers,
+        all_correct: positions.iter().all(|p| p.correct),
+        avg_area: avg_area / count,
+        avg_error: avg_error / count,
+        max_area,
+        max_error,
+    }
+}
+
+// For each valid table location, check if table can determine mini location within error bounds. Gives up at the
+// first location that gets it wrong, the sweep only cares about layouts that get everything right.
+pub fn run_test(size: TableSize, config: &Config, grid_step: Mm) -> TestResult {
+    let table = uniform_table(size, config);
+    let footprint = config.footprint;
+
+    let mut positions = Vec::new();
+    for location in grid_positions(size, footprint, grid_step) {
+        let result = evaluate_position(&table, location, footprint, grid_step);
+        positions.push(result);
+        if !result.correct {
+            break;
+        }
+    }
+
+    summarize(table.receivers.len(), &positions)
+}
+
+#[cfg(test)]
+mod test {
+    use mini_tracker::units::BASE_DIAMETER;
+
+    use super::*;
+
+    const SIZE: TableSize = TableSize {
+        width: Mm(930.0 + STANDOFF_DISTANCE.0),
+        height: Mm(523.0 + STANDOFF_DISTANCE.0),
+    };
+
+    #[test]
+    fn grid_stays_inside_standoff() {
+        let positions = grid_positions(SIZE, BASE_DIAMETER, Mm(MM_PER_INCH));
+        assert!(!positions.is_empty());
+        for p in &positions {
+            assert!(p.x - BASE_DIAMETER.0 / 2.0 >= STANDOFF_DISTANCE.0);
+            assert!(p.y - BASE_DIAMETER.0 / 2.0 >= STANDOFF_DISTANCE.0);
+            assert!(p.x + BASE_DIAMETER.0 / 2.0 <= (SIZE.width - STANDOFF_DISTANCE).0);
+            assert!(p.y + BASE_DIAMETER.0 / 2.0 <= (SIZE.height - STANDOFF_DISTANCE).0);
+        }
+
+        // halving the step roughly quadruples the grid
+        let fine = grid_positions(SIZE, BASE_DIAMETER, Mm(MM_PER_INCH / 2.0));
+        assert!(fine.len() > positions.len() * 3);
+    }
+
+    #[test]
+    fn dense_layout_is_correct() {
+        let config = Config {
+            vert_density: 1.0,
+            horiz_density: 1.0,
+            vert_view_angle: Degrees(30.0),
+            horiz_view_angle: Degrees(30.0),
+            footprint: BASE_DIAMETER,
+        };
+        let table = uniform_table(SIZE, &config);
+        let footprint = BASE_DIAMETER;
+
+        let result = evaluate_position(
+            &table,
+            Point { x: 300.0, y: 200.0 },
+            footprint,
+            Mm(MM_PER_INCH),
+        );
+        assert!(result.visible_receivers > 0);
+        assert!(result.correct);
+        assert!(result.error.unwrap() < MM_PER_INCH);
+
+        // the same receivers read back from a layout file behave the same
+        let layout = LayoutFile::from_table(&table);
+        let from_layout = layout_table(&layout, footprint);
+        let again = evaluate_position(
+            &from_layout,
+            Point { x: 300.0, y: 200.0 },
+            footprint,
+            Mm(MM_PER_INCH),
+        );
+        assert_eq!(again.guess_x, result.guess_x);
+        assert_eq!(again.guess_y, result.guess_y);
+    }
+}
@@ -0,0 +1,164 @@
+// Sweep output is written one configuration per line as soon as it finishes, so a run that gets interrupted can be
+// picked up again by reading back which configurations are already in the file.
+
+use std::collections::HashSet;
+
+use anyhow::{bail, Context};
+use mini_tracker::units::{Degrees, Mm};
+use serde::{Deserialize, Serialize};
+
+use crate::sim::{Config, TestResult};
+use crate::Format;
+
+pub const CSV_HEADER: &str = "total_receivers,all_correct,avg_area,avg_error,max_area,max_error,vert_density,horiz_density,vert_view_angle,horiz_view_angle,footprint";
+
+#[derive(Serialize, Deserialize)]
+pub struct SweepRow {
+    #[serde(flatten)]
+    pub result: TestResult,
+    #[serde(flatten)]
+    pub config: Config,
+}
+
+// configurations come from the same ranges every run, so the exact bits are a safe way to match them up
+pub type ConfigKey = [u32; 5];
+
+pub fn config_key(config: &Config) -> ConfigKey {
+    [
+        config.vert_density.to_bits(),
+        config.horiz_density.to_bits(),
+        config.vert_view_angle.0.to_bits(),
+        config.horiz_view_angle.0.to_bits(),
+        config.footprint.0.to_bits(),
+    ]
+}
+
+// one line, including the newline
+pub fn format_row(row: &SweepRow, format: Format) -> String {
+    match format {
+        Format::Csv => format!(
+            "{},{},{},{},{},{},{},{},{},{},{}\n",
+            row.result.total_receivers,
+            row.result.all_correct,
+            row.result.avg_area,
+            row.result.avg_error,
+            row.result.max_area,
+            row.result.max_error,
+            row.config.vert_density,
+            row.config.horiz_density,
+            row.config.vert_view_angle.0,
+            row.config.horiz_view_angle.0,
+            row.config.footprint.0
+        ),
+        Format::Json => {
+            // nothing in a row can fail to serialize
+            let mut line = serde_json::to_string(row).unwrap();
+            line.push('\n');
+            line
+        }
+    }
+}
+
+// Everything up to the last newline of a previous run's output. Anything after it is a row that was cut off part way
+// through being written.
+pub fn complete_lines(text: &str) -> &str {
+    match text.rfind('\n') {
+        Some(end) => &text[..end + 1],
+        None => "",
+    }
+}
+
+// which configurations a previous run's output already has results for
+pub fn completed_configs(text: &str, format: Format) -> anyhow::Result<HashSet<ConfigKey>> {
+    let mut done = HashSet::new();
+
+    match format {
+        Format::Csv => {
+            let mut lines = text.lines();
+            match lines.next() {
+                None => return Ok(done),
+                Some(header) if header == CSV_HEADER => {}
+                Some(_) => bail!("existing output has a different header, can't resume from it"),
+            }
+
+            for (i, line) in lines.enumerate() {
+                let fields: Vec<&str> = line.split(',').collect();
+                if fields.len() != 11 {
+                    bail!("line {} of existing output is malformed", i + 2);
+                }
+                let parse = |field: &str| {
+                    field
+                        .parse::<f32>()
+                        .with_context(|| format!("line {} of existing output is malformed", i + 2))
+                };
+                done.insert(config_key(&Config {
+                    vert_density: parse(fields[6])?,
+                    horiz_density: parse(fields[7])?,
+                    vert_view_angle: Degrees(parse(fields[8])?),
+                    horiz_view_angle: Degrees(parse(fields[9])?),
+                    footprint: Mm(parse(fields[10])?),
+                }));
+            }
+        }
+        Format::Json => {
+            for (i, line) in text.lines().enumerate() {
+                let row: SweepRow = serde_json::from_str(line)
+                    .with_context(|| format!("line {} of existing output is malformed", i + 1))?;
+                done.insert(config_key(&row.config));
+            }
+        }
+    }
+
+    Ok(done)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn row(vert_density: f32, all_correct: bool) -> SweepRow {
+        SweepRow {
+            result: TestResult {
+                total_receivers: 100,
+                all_correct,
+                avg_area: 1000.5,
+                avg_error: 10.25,
+                max_area: 1500.0,
+                max_error: 22.125,
+            },
+            config: Config {
+                vert_density,
+                horiz_density: 1.5,
+                vert_view_angle: Degrees(30.0),
+                horiz_view_angle: Degrees(40.0),
+                footprint: Mm(25.4),
+            },
+        }
+    }
+
+    #[test]
+    fn resume_from_output() {
+        for format in [Format::Csv, Format::Json] {
+            let mut text = match format {
+                Format::Csv => format!("{CSV_HEADER}\n"),
+                Format::Json => String::new(),
+            };
+            text += &format_row(&row(0.5, true), format);
+            text += &format_row(&row(0.1, false), format);
+            // killed half way through writing the third row
+            let cut_off = format_row(&row(2.0, true), format);
+            text += &cut_off[..cut_off.len() / 2];
+
+            let text = complete_lines(&text);
+            let done = completed_configs(text, format).unwrap();
+            assert_eq!(done.len(), 2);
+            assert!(done.contains(&config_key(&row(0.5, true).config)));
+            assert!(done.contains(&config_key(&row(0.1, true).config)));
+            assert!(!done.contains(&config_key(&row(2.0, true).config)));
+        }
+
+        assert!(completed_configs("", Format::Csv).unwrap().is_empty());
+        assert!(completed_configs("a,b\n", Format::Csv).is_err());
+        assert!(completed_configs("{}\n", Format::Json).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+// Chain discovery for the daisy-chained table boards.
+//
+// The controller sits upstream of board 0 and sends Enumerate { position: 0 } down the chain. Every board takes the
+// position it is handed, reports itself upstream and hands position + 1 to the next board downstream. Reports coming
+// up from further down the chain are relayed upstream untouched, so the controller ends up with one report per board,
+// and the board without a downstream neighbour marks its report as the last one.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum BoardType {
+    // straight board with a row of receivers
+    ReceiverStrip = 1,
+    // turns the chain onto the next table edge, no receivers
+    Corner = 2,
+    // extends the chain along the same edge without any receivers
+    Joiner = 3,
+}
+
+impl TryFrom<u8> for BoardType {
+    type Error = DiscoveryError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(BoardType::ReceiverStrip),
+            2 => Ok(BoardType::Corner),
+            3 => Ok(BoardType::Joiner),
+            _ => Err(DiscoveryError::UnknownBoardType(value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardReport {
+    pub position: u8,
+    pub board_type: BoardType,
+    pub receiver_count: u8,
+    pub last_in_chain: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscoveryMessage {
+    Enumerate { position: u8 },
+    Report(BoardReport),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscoveryError {
+    BufferTooSmall,
+    Truncated,
+    UnknownMessage(u8),
+    UnknownBoardType(u8),
+    ChainTooLong,
+}
+
+const ENUMERATE_TAG: u8 = 0x01;
+const REPORT_TAG: u8 = 0x02;
+
+pub const MAX_MESSAGE_LEN: usize = 5;
+
+impl DiscoveryMessage {
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            DiscoveryMessage::Enumerate { .. } => 2,
+            DiscoveryMessage::Report(_) => 5,
+        }
+    }
+
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, DiscoveryError> {
+        let len = self.encoded_len();
+        if buf.len() < len {
+            return Err(DiscoveryError::BufferTooSmall);
+        }
+
+        match self {
+            DiscoveryMessage::Enumerate { position } => {
+                buf[0] = ENUMERATE_TAG;
+                buf[1] = *position;
+            }
+            DiscoveryMessage::Report(report) => {
+                buf[0] = REPORT_TAG;
+                buf[1] = report.position;
+                buf[2] = report.board_type as u8;
+                buf[3] = report.receiver_count;
+                buf[4] = report.last_in_chain as u8;
+            }
+        }
+
+        Ok(len)
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<(Self, usize), DiscoveryError> {
+        let Some(tag) = buf.first() else {
+            return Err(DiscoveryError::Truncated);
+        };
+
+        let message = match *tag {
+            ENUMERATE_TAG => {
+                if buf.len() < 2 {
+                    return Err(DiscoveryError::Truncated);
+                }
+                DiscoveryMessage::Enumerate { position: buf[1] }
+            }
+            REPORT_TAG => {
+                if buf.len() < 5 {
+                    return Err(DiscoveryError::Truncated);
+                }
+                DiscoveryMessage::Report(BoardReport {
+                    position: buf[1],
+                    board_type: BoardType::try_from(buf[2])?,
+                    receiver_count: buf[3],
+                    last_in_chain: buf[4] > 0,
+                })
+            }
+            tag => return Err(DiscoveryError::UnknownMessage(tag)),
+        };
+
+        Ok((message, message.encoded_len()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NodeAction {
+    pub to_upstream: Option<DiscoveryMessage>,
+    pub to_downstream: Option<DiscoveryMessage>,
+}
+
+// Board side of discovery, one per board in the chain
+#[derive(Debug, Clone, Copy)]
+pub struct ChainNode {
+    board_type: BoardType,
+    receiver_count: u8,
+    has_downstream: bool,
+    position: Option<u8>,
+}
+
+impl ChainNode {
+    // has_downstream comes from the joiner's presence detect, it is false on the final board
+    pub fn new(board_type: BoardType, receiver_count: u8, has_downstream: bool) -> Self {
+        Self {
+            board_type,
+            receiver_count,
+            has_downstream,
+            position: None,
+        }
+    }
+
+    pub fn position(&self) -> Option<u8> {
+        self.position
+    }
+
+    pub fn report(&self) -> Option<BoardReport> {
+        Some(BoardReport {
+            position: self.position?,
+            board_type: self.board_type,
+            receiver_count: self.receiver_count,
+            last_in_chain: !self.has_downstream,
+        })
+    }
+
+    pub fn handle_from_upstream(
+        &mut self,
+        message: DiscoveryMessage,
+    ) -> Result<NodeAction, DiscoveryError> {
+        match message {
+            DiscoveryMessage::Enumerate { position } => {
+                self.position = Some(position);
+
+                let to_downstream = if self.has_downstream {
+                    let next = position
+                        .checked_add(1)
+                        .ok_or(DiscoveryError::ChainTooLong)?;
+                    Some(DiscoveryMessage::Enumerate { position: next })
+                } else {
+                    None
+                };
+
+                Ok(NodeAction {
+                    to_upstream: self.report().map(DiscoveryMessage::Report),
+                    to_downstream,
+                })
+            }
+            // reports only ever travel upstream, nothing to do with one coming the wrong way
+            DiscoveryMessage::Report(_) => Ok(NodeAction::default()),
+        }
+    }
+
+    pub fn handle_from_downstream(&mut self, message: DiscoveryMessage) -> NodeAction {
+        match message {
+            DiscoveryMessage::Report(_) => NodeAction {
+                to_upstream: Some(message),
+                to_downstream: None,
+            },
+            DiscoveryMessage::Enumerate { .. } => NodeAction::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn message_round_trip() {
+        let messages = [
+            DiscoveryMessage::Enumerate { position: 12 },
+            DiscoveryMessage::Report(BoardReport {
+                position: 3,
+                board_type: BoardType::Corner,
+                receiver_count: 0,
+                last_in_chain: false,
+            }),
+            DiscoveryMessage::Report(BoardReport {
+                position: 4,
+                board_type: BoardType::ReceiverStrip,
+                receiver_count: 16,
+                last_in_chain: true,
+            }),
+        ];
+
+        for message in messages {
+            let mut buf = [0u8; MAX_MESSAGE_LEN];
+            let len = message.encode(&mut buf).unwrap();
+            assert_eq!(DiscoveryMessage::decode(&buf[..len]), Ok((message, len)));
+        }
+
+        assert_eq!(
+            DiscoveryMessage::decode(&[REPORT_TAG, 0, 9, 0, 0]),
+            Err(DiscoveryError::UnknownBoardType(9))
+        );
+        assert_eq!(
+            DiscoveryMessage::decode(&[REPORT_TAG, 0]),
+            Err(DiscoveryError::Truncated)
+        );
+        assert_eq!(
+            DiscoveryMessage::decode(&[0x7f]),
+            Err(DiscoveryError::UnknownMessage(0x7f))
+        );
+    }
+
+    #[test]
+    fn node_takes_position_and_forwards() {
+        let mut node = ChainNode::new(BoardType::ReceiverStrip, 8, true);
+        assert!(node.report().is_none());
+
+        let action = node
+            .handle_from_upstream(DiscoveryMessage::Enumerate { position: 2 })
+            .unwrap();
+        assert_eq!(node.position(), Some(2));
+        assert_eq!(
+            action.to_upstream,
+            Some(DiscoveryMessage::Report(BoardReport {
+                position: 2,
+                board_type: BoardType::ReceiverStrip,
+                receiver_count: 8,
+                last_in_chain: false,
+            }))
+        );
+        assert_eq!(
+            action.to_downstream,
+            Some(DiscoveryMessage::Enumerate { position: 3 })
+        );
+
+        let mut last = ChainNode::new(BoardType::Corner, 0, false);
+        let action = last
+            .handle_from_upstream(DiscoveryMessage::Enumerate { position: 3 })
+            .unwrap();
+        assert!(action.to_downstream.is_none());
+
+        // the downstream report gets relayed as is
+        let relayed = node.handle_from_downstream(action.to_upstream.unwrap());
+        assert_eq!(relayed.to_upstream, action.to_upstream);
+    }
+}
@@ -0,0 +1,279 @@
+// Wire format for "which receivers on a strip saw the mini in this slot".
+//
+// All multi-byte fields are little endian.
+//
+// offset  size  field
+// 0       1     version
+// 1       1     strip id, the position of the strip on the table
+// 2       1     slot, which is the address of the mini that flashed
+// 3       4     timestamp of the slot
+// 7       2     receiver count
+// 9       n     bitmap, bit i is set if receiver i saw the mini
+// 9 + n   1     crc-8 over everything before it
+//
+// n is receiver_count / 8 rounded up, bit 0 of the first bitmap byte is receiver 0.
+
+pub const FRAME_VERSION: u8 = 1;
+pub const MAX_RECEIVERS: usize = 256;
+pub const MAX_BITMAP_BYTES: usize = MAX_RECEIVERS / 8;
+
+const HEADER_LEN: usize = 9;
+const CHECKSUM_LEN: usize = 1;
+pub const MAX_FRAME_LEN: usize = HEADER_LEN + MAX_BITMAP_BYTES + CHECKSUM_LEN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    BufferTooSmall,
+    Truncated,
+    UnsupportedVersion(u8),
+    TooManyReceivers(u16),
+    BadChecksum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObservationFrame {
+    pub strip_id: u8,
+    pub slot: u8,
+    pub timestamp: u32,
+    receiver_count: u16,
+    bitmap: [u8; MAX_BITMAP_BYTES],
+}
+
+impl ObservationFrame {
+    pub fn new(strip_id: u8, slot: u8, timestamp: u32, receiver_count: u16) -> Self {
+        assert!(receiver_count as usize <= MAX_RECEIVERS);
+
+        Self {
+            strip_id,
+            slot,
+            timestamp,
+            receiver_count,
+            bitmap: [0; MAX_BITMAP_BYTES],
+        }
+    }
+
+    pub fn from_observations(
+        strip_id: u8,
+        slot: u8,
+        timestamp: u32,
+        observations: impl IntoIterator<Item = bool>,
+    ) -> Result<Self, FrameError> {
+        let mut frame = Self::new(strip_id, slot, timestamp, 0);
+        for seen in observations {
+            if frame.receiver_count as usize == MAX_RECEIVERS {
+                return Err(FrameError::TooManyReceivers(frame.receiver_count + 1));
+            }
+            frame.receiver_count += 1;
+            frame.set(frame.receiver_count as usize - 1, seen);
+        }
+
+        Ok(frame)
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.receiver_count as usize
+    }
+
+    pub fn set(&mut self, receiver: usize, seen: bool) {
+        assert!(receiver < self.receiver_count());
+
+        if seen {
+            self.bitmap[receiver / 8] |= 1 << (receiver % 8);
+        } else {
+            self.bitmap[receiver / 8] &= !(1 << (receiver % 8));
+        }
+    }
+
+    pub fn get(&self, receiver: usize) -> bool {
+        assert!(receiver < self.receiver_count());
+
+        self.bitmap[receiver / 8] & (1 << (receiver % 8)) > 0
+    }
+
+    pub fn observations(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.receiver_count()).map(|i| self.get(i))
+    }
+
+    pub fn num_seen(&self) -> usize {
+        self.bitmap[..self.bitmap_len()]
+            .iter()
+            .map(|b| b.count_ones() as usize)
+            .sum()
+    }
+
+    fn bitmap_len(&self) -> usize {
+        self.receiver_count().div_ceil(8)
+    }
+
+    pub fn encoded_len(&self) -> usize {
+        HEADER_LEN + self.bitmap_len() + CHECKSUM_LEN
+    }
+
+    // returns the number of bytes written
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, FrameError> {
+        let len = self.encoded_len();
+        if buf.len() < len {
+            return Err(FrameError::BufferTooSmall);
+        }
+
+        buf[0] = FRAME_VERSION;
+        buf[1] = self.strip_id;
+        buf[2] = self.slot;
+        buf[3..7].copy_from_slice(&self.timestamp.to_le_bytes());
+        buf[7..9].copy_from_slice(&self.receiver_count.to_le_bytes());
+        buf[HEADER_LEN..len - CHECKSUM_LEN].copy_from_slice(&self.bitmap[..self.bitmap_len()]);
+        buf[len - CHECKSUM_LEN] = crc8(&buf[..len - CHECKSUM_LEN]);
+
+        Ok(len)
+    }
+
+    // returns the frame and the number of bytes consumed, so frames can be read back to back out of a log
+    pub fn decode(buf: &[u8]) -> Result<(Self, usize), FrameError> {
+        if buf.len() < HEADER_LEN {
+            return Err(FrameError::Truncated);
+        }
+
+        if buf[0] != FRAME_VERSION {
+            return Err(FrameError::UnsupportedVersion(buf[0]));
+        }
+
+        let receiver_count = u16::from_le_bytes([buf[7], buf[8]]);
+        if receiver_count as usize > MAX_RECEIVERS {
+            return Err(FrameError::TooManyReceivers(receiver_count));
+        }
+
+        let mut frame = Self::new(
+            buf[1],
+            buf[2],
+            u32::from_le_bytes([buf[3], buf[4], buf[5], buf[6]]),
+            receiver_count,
+        );
+
+        let len = frame.encoded_len();
+        if buf.len() < len {
+            return Err(FrameError::Truncated);
+        }
+
+        if crc8(&buf[..len - CHECKSUM_LEN]) != buf[len - CHECKSUM_LEN] {
+            return Err(FrameError::BadChecksum);
+        }
+
+        let bitmap_len = frame.bitmap_len();
+        frame.bitmap[..bitmap_len].copy_from_slice(&buf[HEADER_LEN..HEADER_LEN + bitmap_len]);
+
+        // don't let stray bits past the receiver count leak into num_seen
+        if !receiver_count.is_multiple_of(8) {
+            frame.bitmap[bitmap_len - 1] &= (1 << (receiver_count % 8)) - 1;
+        }
+
+        Ok((frame, len))
+    }
+}
+
+// CRC-8/SMBUS, poly 0x07
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            if crc & 0x80 > 0 {
+                crc = (crc << 1) ^ 0x07;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crc8_check_value() {
+        assert_eq!(crc8(b"123456789"), 0xf4);
+    }
+
+    #[test]
+    fn round_trip() {
+        let seen = [
+            false, false, true, true, true, false, false, false, false, true, false,
+        ];
+        let frame = ObservationFrame::from_observations(3, 17, 0xdead_beef, seen).unwrap();
+        assert_eq!(frame.receiver_count(), seen.len());
+        assert_eq!(frame.num_seen(), 4);
+
+        let mut buf = [0u8; MAX_FRAME_LEN];
+        let len = frame.encode(&mut buf).unwrap();
+        // 11 receivers fit in 2 bitmap bytes
+        assert_eq!(len, HEADER_LEN + 2 + CHECKSUM_LEN);
+
+        let (decoded, consumed) = ObservationFrame::decode(&buf[..len]).unwrap();
+        assert_eq!(consumed, len);
+        assert_eq!(decoded, frame);
+        assert_eq!(decoded.strip_id, 3);
+        assert_eq!(decoded.slot, 17);
+        assert_eq!(decoded.timestamp, 0xdead_beef);
+        assert!(decoded.observations().eq(seen.iter().copied()));
+    }
+
+    #[test]
+    fn back_to_back_frames() {
+        let frame1 = ObservationFrame::from_observations(0, 1, 100, [true; 20]).unwrap();
+        let frame2 = ObservationFrame::from_observations(1, 1, 100, [false; 8]).unwrap();
+
+        let mut buf = [0u8; MAX_FRAME_LEN * 2];
+        let len1 = frame1.encode(&mut buf).unwrap();
+        let len2 = frame2.encode(&mut buf[len1..]).unwrap();
+
+        let (decoded1, consumed1) = ObservationFrame::decode(&buf[..len1 + len2]).unwrap();
+        let (decoded2, consumed2) = ObservationFrame::decode(&buf[consumed1..]).unwrap();
+        assert_eq!(decoded1, frame1);
+        assert_eq!(decoded2, frame2);
+        assert_eq!(consumed1 + consumed2, len1 + len2);
+    }
+
+    #[test]
+    fn max_receivers() {
+        let frame = ObservationFrame::from_observations(0, 0, 0, [true; MAX_RECEIVERS]).unwrap();
+        let mut buf = [0u8; MAX_FRAME_LEN];
+        assert_eq!(frame.encode(&mut buf), Ok(MAX_FRAME_LEN));
+        assert_eq!(
+            ObservationFrame::decode(&buf).unwrap().0.num_seen(),
+            MAX_RECEIVERS
+        );
+
+        assert_eq!(
+            ObservationFrame::from_observations(0, 0, 0, [true; MAX_RECEIVERS + 1]),
+            Err(FrameError::TooManyReceivers(MAX_RECEIVERS as u16 + 1))
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        let frame = ObservationFrame::from_observations(0, 5, 42, [true, false, true]).unwrap();
+        let mut buf = [0u8; MAX_FRAME_LEN];
+        let len = frame.encode(&mut buf).unwrap();
+
+        assert_eq!(frame.encode(&mut [0u8; 4]), Err(FrameError::BufferTooSmall));
+        assert_eq!(
+            ObservationFrame::decode(&buf[..len - 1]),
+            Err(FrameError::Truncated)
+        );
+
+        let mut corrupted = buf;
+        corrupted[HEADER_LEN] ^= 0x02;
+        assert_eq!(
+            ObservationFrame::decode(&corrupted[..len]),
+            Err(FrameError::BadChecksum)
+        );
+
+        let mut wrong_version = buf;
+        wrong_version[0] = FRAME_VERSION + 1;
+        assert_eq!(
+            ObservationFrame::decode(&wrong_version[..len]),
+            Err(FrameError::UnsupportedVersion(FRAME_VERSION + 1))
+        );
+    }
+}
@@ -0,0 +1,6 @@
+#![no_std]
+
+pub mod discovery;
+pub mod frame;
+
+pub use frame::{FrameError, ObservationFrame};
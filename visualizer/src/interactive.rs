@@ -0,0 +1,192 @@
+// Mouse and keyboard handling shared by the solver visualizers. Drag the mini around with the left button, click a
+// receiver to switch it off or back on, - and = shrink and grow the base, R turns every receiver back on. The solvers
+// re-solve from `visible_receivers` every frame.
+
+use super::*;
+use mini_tracker::units::{Px, ScreenPoint};
+use speedy2d::dimen::Vec2;
+use speedy2d::window::{MouseButton, VirtualKeyCode, WindowHelper};
+
+use crate::render::{draw_receiver, Colour, Renderer};
+
+const MIN_FOOTPRINT: Mm = Mm(5.0);
+const MAX_FOOTPRINT: Mm = Mm(4.0 * MM_PER_INCH);
+const FOOTPRINT_STEP: Mm = Mm(1.0);
+
+pub struct Interaction {
+    pub table: Table,
+    pub mini_location: Point,
+    pub footprint: Mm,
+    pub disabled: Vec<bool>,
+    // from the last draw, for the title bar
+    pub estimate: Option<Point>,
+    mouse: Point,
+    dragging: bool,
+}
+
+impl Interaction {
+    pub fn new(table: Table, mini_location: Point, footprint: Mm) -> Self {
+        let disabled = vec![false; table.receivers.len()];
+        Self {
+            table,
+            mini_location,
+            footprint,
+            disabled,
+            estimate: None,
+            mouse: mini_location,
+            dragging: false,
+        }
+    }
+
+    // what each receiver reports, a switched off receiver never sees anything
+    pub fn visible_receivers(&self) -> Vec<(Receiver, bool)> {
+        visible_receivers(&self.table, self.mini_location, self.footprint)
+            .into_iter()
+            .zip(&self.disabled)
+            .map(|((receiver, can_see), disabled)| (receiver, can_see && !disabled))
+            .collect()
+    }
+
+    fn receiver_at(&self, point: Point) -> Option<usize> {
+        let size = transform().length_to_table(Px(RECEIVER_SIZE)).0;
+        self.table.receivers.iter().position(|r| {
+            (r.location.x - point.x).abs() <= size && (r.location.y - point.y).abs() <= size
+        })
+    }
+
+    pub fn toggle_receiver_at(&mut self, point: Point) -> bool {
+        match self.receiver_at(point) {
+            Some(idx) => {
+                self.disabled[idx] = !self.disabled[idx];
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn move_mini(&mut self, point: Point) {
+        let (width, height) = table_size();
+        self.mini_location = Point {
+            x: point.x.clamp(0.0, width.0),
+            y: point.y.clamp(0.0, height.0),
+        };
+    }
+
+    // the expanded view bounds depend on the base size, so the receivers are rebuilt to match
+    pub fn set_footprint(&mut self, footprint: Mm) {
+        self.footprint = footprint.max(MIN_FOOTPRINT).min(MAX_FOOTPRINT);
+        let (width, height) = table_size();
+        let receivers = self
+            .table
+            .receivers
+            .iter()
+            .map(|r| {
+                Receiver::with_footprint(
+                    width,
+                    height,
+                    r.view_angle,
+                    r.location,
+                    r.facing,
+                    self.footprint,
+                )
+            })
+            .collect();
+        self.table = Table::new(width, height, receivers);
+    }
+
+    pub fn on_mouse_move(&mut self, helper: &mut WindowHelper<()>, position: Vec2) {
+        self.mouse = transform().to_table(ScreenPoint {
+            x: Px(position.x),
+            y: Px(position.y),
+        });
+        if self.dragging {
+            self.move_mini(self.mouse);
+            helper.request_redraw();
+        }
+    }
+
+    // clicking a receiver toggles it, clicking anywhere else picks the mini up and drops it under the mouse
+    pub fn on_mouse_button_down(&mut self, helper: &mut WindowHelper<()>, button: MouseButton) {
+        if button != MouseButton::Left {
+            return;
+        }
+        if !self.toggle_receiver_at(self.mouse) {
+            self.dragging = true;
+            self.move_mini(self.mouse);
+        }
+        helper.request_redraw();
+    }
+
+    pub fn on_mouse_button_up(&mut self, _helper: &mut WindowHelper<()>, button: MouseButton) {
+        if button == MouseButton::Left {
+            self.dragging = false;
+        }
+    }
+
+    pub fn on_key_up(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                self.set_footprint(self.footprint - FOOTPRINT_STEP)
+            }
+            VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+                self.set_footprint(self.footprint + FOOTPRINT_STEP)
+            }
+            VirtualKeyCode::R => self.disabled.iter_mut().for_each(|d| *d = false),
+            _ => (),
+        }
+    }
+
+    pub fn draw_mini(&self, renderer: &mut dyn Renderer) {
+        let centre = to_screen(self.mini_location);
+        renderer.circle(
+            centre,
+            transform().length_to_screen(self.footprint / 2.0).0,
+            Colour::RED,
+        );
+        renderer.circle(centre, 1.0 * PX_PER_MM, Colour::BLACK);
+    }
+
+    pub fn draw_receivers(
+        &self,
+        renderer: &mut dyn Renderer,
+        visible_receivers: &[(Receiver, bool)],
+    ) {
+        for ((receiver, can_see), disabled) in visible_receivers.iter().zip(&self.disabled) {
+            let colour = if *disabled {
+                Colour::LIGHT_GRAY
+            } else if *can_see {
+                Colour::BLUE
+            } else {
+                Colour::RED
+            };
+            draw_receiver(renderer, receiver, colour);
+        }
+    }
+
+    // marks the estimate with a line back to the true location
+    pub fn draw_estimate(&mut self, renderer: &mut dyn Renderer, estimate: Option<Point>) {
+        self.estimate = estimate;
+        if let Some(estimate) = estimate {
+            let centre = to_screen(estimate);
+            renderer.line(to_screen(self.mini_location), centre, 1.0, Colour::MAGENTA);
+            renderer.circle(centre, 1.5 * PX_PER_MM, Colour::GREEN);
+        }
+    }
+
+    // the true location, the estimate and the error
+    pub fn title(&self) -> String {
+        let actual = format!(
+            "true ({:.1}, {:.1}), base {:.1}mm",
+            self.mini_location.x, self.mini_location.y, self.footprint.0
+        );
+        match self.estimate {
+            Some(estimate) => format!(
+                "{actual}, estimated ({:.1}, {:.1}), error {:.2}mm",
+                estimate.x,
+                estimate.y,
+                self.mini_location.distance(&estimate)
+            ),
+            None => format!("{actual}, no solution"),
+        }
+    }
+}
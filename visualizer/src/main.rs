@@ -1,13 +1,22 @@
-use mini_tracker::{self, Point, Receiver, Table};
+use std::path::PathBuf;
+use std::sync::OnceLock;
 
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
+use mini_tracker::layout::LayoutFile;
+use mini_tracker::recording::Recording;
+use mini_tracker::units::{
+    Degrees, Mm, Transform, BASE_DIAMETER, MM_PER_INCH, PX_PER_MM, STANDOFF_DISTANCE,
+};
+use mini_tracker::{self, Direction, Point, Receiver, Table};
+
+mod interactive;
+mod modes;
+mod render;
 mod vis_bounding_box;
 mod vis_iterating_solver;
 mod vis_receivers;
-
-// 1 px == 1 mm
-const PX_PER_MM: f32 = 3.0;
-// 25mm == ~1 in
-const MM_PER_INCH: f32 = 25.4;
+mod vis_replay;
 
 // grid is
 // |
@@ -15,36 +24,166 @@ const MM_PER_INCH: f32 = 25.4;
 // |
 // .--------
 
-const TABLE_WIDTH: f32 = 930.0 + 2.0 * MM_PER_INCH;
-const TABLE_HEIGHT: f32 = 523.0 + 2.0 * MM_PER_INCH;
+const TABLE_WIDTH: Mm = Mm(930.0 + STANDOFF_DISTANCE.0);
+const TABLE_HEIGHT: Mm = Mm(523.0 + STANDOFF_DISTANCE.0);
+
+// the table every mode draws, set once from the command line before the window opens
+static TABLE_SIZE: OnceLock<(Mm, Mm)> = OnceLock::new();
 
 const RECEIVER_SIZE: f32 = 2.5 * PX_PER_MM;
 
-fn convert_y(y: f32) -> f32 {
-    (TABLE_HEIGHT - y) * PX_PER_MM
+fn table_size() -> (Mm, Mm) {
+    *TABLE_SIZE.get_or_init(|| (TABLE_WIDTH, TABLE_HEIGHT))
+}
+
+fn transform() -> Transform {
+    Transform::new(PX_PER_MM, table_size().1)
+}
+
+fn to_screen(point: Point) -> (f32, f32) {
+    transform().to_screen(point).into()
+}
+
+#[derive(Parser)]
+#[command(about = "Draw how the solvers locate a mini, Tab switches between the visualizations")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
 }
 
-fn convert_x(x: f32) -> f32 {
-    x * PX_PER_MM
+// in the order Tab cycles through them
+#[derive(Subcommand)]
+enum Command {
+    /// Step through the intersection points each receiver keeps or removes
+    Receivers(VisArgs),
+    /// The bounding polygon from the library solver, shrunk down to where the centre can be
+    BoundingBox(VisArgs),
+    /// The strip solver, using only the outermost receivers on each edge that can see the mini
+    IteratingSolver(VisArgs),
+    /// Step through a recorded session frame by frame
+    Replay(ReplayArgs),
 }
 
-fn place_vertical_receivers(view_angle: f32, receivers_per_mm: f32) -> Vec<Receiver> {
+#[derive(Args)]
+struct ReplayArgs {
+    /// Recording to play back, the table is taken from it
+    recording: PathBuf,
+    /// Frame to start on
+    #[arg(long, default_value_t = 0)]
+    frame: usize,
+    /// Draw the starting frame to an .svg or .png file instead of opening a window
+    #[arg(long)]
+    render: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct VisArgs {
+    /// Layout file to take the table size and receivers from
+    #[arg(long, conflicts_with_all = ["width", "height"])]
+    layout: Option<PathBuf>,
+    /// Table width in mm
+    #[arg(long, default_value_t = TABLE_WIDTH.0)]
+    width: f32,
+    /// Table height in mm
+    #[arg(long, default_value_t = TABLE_HEIGHT.0)]
+    height: f32,
+    /// Where the mini starts in mm, a quarter of the way across the table if not given
+    #[arg(long)]
+    x: Option<f32>,
+    #[arg(long)]
+    y: Option<f32>,
+    /// Base diameter in mm of the mini
+    #[arg(long, default_value_t = BASE_DIAMETER.0)]
+    footprint: f32,
+    /// Receivers per inch on the left and right edges, ignored if the layout file has receivers
+    #[arg(long, default_value_t = 2.0)]
+    vert_density: f32,
+    #[arg(long, default_value_t = 2.0)]
+    horiz_density: f32,
+    #[arg(long, default_value_t = 10.0)]
+    vert_view_angle: f32,
+    #[arg(long, default_value_t = 10.0)]
+    horiz_view_angle: f32,
+    /// Draw the scene to an .svg or .png file instead of opening a window
+    #[arg(long)]
+    render: Option<PathBuf>,
+}
+
+impl VisArgs {
+    fn build(&self) -> anyhow::Result<Table> {
+        let layout = match &self.layout {
+            Some(path) => LayoutFile::load(path)
+                .with_context(|| format!("loading layout {}", path.display()))?,
+            None => LayoutFile {
+                width: Mm(self.width),
+                height: Mm(self.height),
+                receivers: Vec::new(),
+            },
+        };
+        let (width, height, footprint) = (layout.width, layout.height, Mm(self.footprint));
+
+        let receivers = if layout.receivers.is_empty() {
+            let mut receivers = place_horizontal_receivers(
+                width,
+                height,
+                Degrees(self.horiz_view_angle),
+                self.horiz_density,
+                footprint,
+            );
+            receivers.append(&mut place_vertical_receivers(
+                width,
+                height,
+                Degrees(self.vert_view_angle),
+                self.vert_density,
+                footprint,
+            ));
+            receivers
+        } else {
+            layout
+                .receivers
+                .iter()
+                .map(|r| {
+                    Receiver::with_footprint(
+                        width,
+                        height,
+                        r.view_angle,
+                        Point { x: r.x.0, y: r.y.0 },
+                        r.facing,
+                        footprint,
+                    )
+                })
+                .collect()
+        };
+
+        Ok(Table::new(width, height, receivers))
+    }
+}
+
+fn place_vertical_receivers(
+    width: Mm,
+    height: Mm,
+    view_angle: Degrees,
+    receivers_per_mm: f32,
+    footprint: Mm,
+) -> Vec<Receiver> {
     let mut receivers = Vec::new();
     let mut y = MM_PER_INCH / 2.0;
-    while y < TABLE_HEIGHT {
-        receivers.push(Receiver::new(
-            TABLE_WIDTH,
-            TABLE_HEIGHT,
+    while y < height.0 {
+        receivers.push(Receiver::with_footprint(
+            width,
+            height,
             view_angle,
             Point { x: 0.0, y },
-            mini_tracker::Direction::Right,
+            Direction::Right,
+            footprint,
         ));
-        receivers.push(Receiver::new(
-            TABLE_WIDTH,
-            TABLE_HEIGHT,
+        receivers.push(Receiver::with_footprint(
+            width,
+            height,
             view_angle,
-            Point { x: TABLE_WIDTH, y },
-            mini_tracker::Direction::Left,
+            Point { x: width.0, y },
+            Direction::Left,
+            footprint,
         ));
 
         y += MM_PER_INCH / receivers_per_mm;
@@ -53,23 +192,31 @@ fn place_vertical_receivers(view_angle: f32, receivers_per_mm: f32) -> Vec<Recei
     receivers
 }
 
-fn place_horizontal_receivers(view_angle: f32, receivers_per_mm: f32) -> Vec<Receiver> {
+fn place_horizontal_receivers(
+    width: Mm,
+    height: Mm,
+    view_angle: Degrees,
+    receivers_per_mm: f32,
+    footprint: Mm,
+) -> Vec<Receiver> {
     let mut receivers = Vec::new();
     let mut x = MM_PER_INCH / 2.0;
-    while x < TABLE_WIDTH {
-        receivers.push(Receiver::new(
-            TABLE_WIDTH,
-            TABLE_HEIGHT,
+    while x < width.0 {
+        receivers.push(Receiver::with_footprint(
+            width,
+            height,
             view_angle,
             Point { x, y: 0.0 },
-            mini_tracker::Direction::Up,
+            Direction::Up,
+            footprint,
         ));
-        receivers.push(Receiver::new(
-            TABLE_WIDTH,
-            TABLE_HEIGHT,
+        receivers.push(Receiver::with_footprint(
+            width,
+            height,
             view_angle,
-            Point { x, y: TABLE_HEIGHT },
-            mini_tracker::Direction::Down,
+            Point { x, y: height.0 },
+            Direction::Down,
+            footprint,
         ));
 
         x += MM_PER_INCH / receivers_per_mm;
@@ -78,9 +225,9 @@ fn place_horizontal_receivers(view_angle: f32, receivers_per_mm: f32) -> Vec<Rec
     receivers
 }
 
-fn get_mini_edge_points(mini_center: Point) -> [Point; 360] {
+fn get_mini_edge_points(mini_center: Point, footprint: Mm) -> [Point; 360] {
     let mut points = [Point { x: 0.0, y: 0.0 }; 360];
-    let distance = MM_PER_INCH / 2.0;
+    let distance = footprint.0 / 2.0;
     for (i, point) in points.iter_mut().enumerate() {
         let angle = i as f32;
         point.x = mini_center.x + distance * angle.to_radians().cos();
@@ -88,48 +235,64 @@ fn get_mini_edge_points(mini_center: Point) -> [Point; 360] {
     }
     points
 }
-fn main() {
-    let vert_density = 2.0;
-    let horiz_density = 2.0;
-    let vert_view_angle = 10.0;
-    let horiz_view_angle = 10.0;
 
-    let mut receivers = place_horizontal_receivers(horiz_view_angle, horiz_density);
-    receivers.append(&mut place_vertical_receivers(vert_view_angle, vert_density));
+fn visible_receivers(table: &Table, mini_location: Point, footprint: Mm) -> Vec<(Receiver, bool)> {
+    let mini_edge_points = get_mini_edge_points(mini_location, footprint);
+    table
+        .receivers
+        .iter()
+        .map(|receiver| {
+            let can_see = mini_edge_points.iter().any(|p| receiver.can_see(p));
+            (*receiver, can_see)
+        })
+        .collect()
+}
+
+fn replay(args: &ReplayArgs) -> anyhow::Result<()> {
+    let recording = Recording::load(&args.recording)
+        .with_context(|| format!("loading recording {}", args.recording.display()))?;
+    TABLE_SIZE
+        .set((recording.layout.width, recording.layout.height))
+        .unwrap();
+    println!(
+        "{} frames over {:.2}s",
+        recording.frames.len(),
+        recording.frames.last().map(|f| f.time).unwrap_or(0.0)
+    );
 
-    let table = Table::new(TABLE_WIDTH, TABLE_HEIGHT, receivers);
+    let mut replay = vis_replay::handler(recording, args.frame);
+    if let Some(path) = &args.render {
+        return render::save(replay.as_mut(), path);
+    }
+    modes::run(vec![("replay", replay)], 0)
+}
 
-    let mini_location = Point {
-        x: TABLE_WIDTH / 4.0,
-        y: TABLE_HEIGHT / 4.0,
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let (first_mode, args) = match &cli.command {
+        Command::Receivers(args) => (0, args),
+        Command::BoundingBox(args) => (1, args),
+        Command::IteratingSolver(args) => (2, args),
+        Command::Replay(args) => return replay(args),
     };
 
-    let mini_edge_points = get_mini_edge_points(mini_location);
-    let mut visible_receivers = Vec::new();
-    for receiver in table.receivers.iter() {
-        let mut can_see = false;
-        for point in &mini_edge_points {
-            if receiver.can_see(point) {
-                can_see = true;
-                break;
-            }
-        }
-        visible_receivers.push((*receiver, can_see));
-    }
+    let table = args.build()?;
+    let (width, height) = (
+        Mm(table.table_bottom.point2.x),
+        Mm(table.table_left.point2.y),
+    );
+    TABLE_SIZE.set((width, height)).unwrap();
+
+    let footprint = Mm(args.footprint);
+    let mini_location = Point {
+        x: args.x.unwrap_or(width.0 / 4.0),
+        y: args.y.unwrap_or(height.0 / 4.0),
+    };
 
     // for normal library based calculation
-    let receiver_copies = table.receivers.clone();
-    let mut copied_visible_receivers = Vec::new();
-    for receiver in receiver_copies.iter() {
-        let mut can_see = false;
-        for point in &mini_edge_points {
-            if receiver.can_see(point) {
-                can_see = true;
-                break;
-            }
-        }
-        copied_visible_receivers.push((receiver, can_see));
-    }
+    let visible_receivers = visible_receivers(&table, mini_location, footprint);
+    let copied_visible_receivers: Vec<(&Receiver, bool)> =
+        visible_receivers.iter().map(|(r, v)| (r, *v)).collect();
 
     let (estimated_location, location_error) = table.get_location(&copied_visible_receivers);
     let actual_error = mini_location.distance(&estimated_location);
@@ -139,7 +302,23 @@ fn main() {
         estimated_location, location_error, actual_error
     );
 
-    // vis_receivers::run(table.clone(), mini_location, visible_receivers.clone());
-    // vis_bounding_box::run(table, mini_location, visible_receivers.clone());
-    vis_iterating_solver::run(table, mini_location, visible_receivers.clone());
+    let mut modes: Vec<modes::Mode> = vec![
+        (
+            "receivers",
+            vis_receivers::handler(table.clone(), mini_location, footprint),
+        ),
+        (
+            "bounding box",
+            vis_bounding_box::handler(table.clone(), mini_location, footprint),
+        ),
+        (
+            "iterating solver",
+            vis_iterating_solver::handler(table, mini_location, footprint),
+        ),
+    ];
+
+    if let Some(path) = &args.render {
+        return render::save(modes[first_mode].1.as_mut(), path);
+    }
+    modes::run(modes, first_mode)
 }
@@ -0,0 +1,89 @@
+// One window for all the visualizations. Tab moves on to the next one and the number keys pick one directly, each
+// keeps its own state while it isn't showing.
+
+use super::*;
+use speedy2d::window::{KeyScancode, MouseButton, VirtualKeyCode, WindowHandler, WindowHelper};
+
+use crate::render::Scene;
+
+// anything that can be shown in the window and also drawn headless
+pub trait Visualization: WindowHandler + Scene {}
+
+impl<T: WindowHandler + Scene> Visualization for T {}
+
+pub type Mode = (&'static str, Box<dyn Visualization>);
+
+struct Modes {
+    modes: Vec<Mode>,
+    current: usize,
+}
+
+impl Modes {
+    fn switch_to(&mut self, helper: &mut WindowHelper<()>, mode: usize) {
+        if mode >= self.modes.len() {
+            return;
+        }
+        self.current = mode;
+        let name = self.modes[mode].0;
+        println!("Showing {name}");
+        helper.set_title(format!("Mini Tracker Visualizer - {name}"));
+        helper.request_redraw();
+    }
+}
+
+impl WindowHandler for Modes {
+    fn on_draw(&mut self, helper: &mut WindowHelper<()>, graphics: &mut speedy2d::Graphics2D) {
+        self.modes[self.current].1.on_draw(helper, graphics);
+    }
+
+    // every mode follows the mouse so a click lands in the right place straight after switching
+    fn on_mouse_move(&mut self, helper: &mut WindowHelper<()>, position: speedy2d::dimen::Vec2) {
+        for (_, mode) in &mut self.modes {
+            mode.on_mouse_move(helper, position);
+        }
+    }
+
+    fn on_mouse_button_down(&mut self, helper: &mut WindowHelper<()>, button: MouseButton) {
+        self.modes[self.current]
+            .1
+            .on_mouse_button_down(helper, button);
+    }
+
+    fn on_mouse_button_up(&mut self, helper: &mut WindowHelper<()>, button: MouseButton) {
+        for (_, mode) in &mut self.modes {
+            mode.on_mouse_button_up(helper, button);
+        }
+    }
+
+    fn on_key_up(
+        &mut self,
+        helper: &mut WindowHelper<()>,
+        virtual_key_code: Option<VirtualKeyCode>,
+        scancode: KeyScancode,
+    ) {
+        match virtual_key_code {
+            Some(VirtualKeyCode::Tab) => {
+                self.switch_to(helper, (self.current + 1) % self.modes.len());
+            }
+            Some(VirtualKeyCode::Key1) => self.switch_to(helper, 0),
+            Some(VirtualKeyCode::Key2) => self.switch_to(helper, 1),
+            Some(VirtualKeyCode::Key3) => self.switch_to(helper, 2),
+            _ => self.modes[self.current]
+                .1
+                .on_key_up(helper, virtual_key_code, scancode),
+        }
+    }
+}
+
+pub fn run(modes: Vec<Mode>, first: usize) -> ! {
+    let window = speedy2d::Window::new_centered(
+        format!("Mini Tracker Visualizer - {}", modes[first].0),
+        render::screen_size(),
+    )
+    .unwrap();
+
+    window.run_loop(Modes {
+        modes,
+        current: first,
+    });
+}
@@ -0,0 +1,373 @@
+// Everything the visualizations draw goes through `Renderer`, so the same scene can go to the speedy2d window, an SVG
+// or a PNG. Positions and sizes are in screen px, use `to_screen` to get there from the table.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use mini_tracker::{Line, Polygon, Receiver};
+
+use super::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Colour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Colour {
+    pub const WHITE: Colour = Colour::rgb(255, 255, 255);
+    pub const BLACK: Colour = Colour::rgb(0, 0, 0);
+    pub const RED: Colour = Colour::rgb(255, 0, 0);
+    pub const GREEN: Colour = Colour::rgb(0, 255, 0);
+    pub const BLUE: Colour = Colour::rgb(0, 0, 255);
+    pub const CYAN: Colour = Colour::rgb(0, 255, 255);
+    pub const MAGENTA: Colour = Colour::rgb(255, 0, 255);
+    pub const LIGHT_GRAY: Colour = Colour::rgb(191, 191, 191);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+pub trait Renderer {
+    fn clear(&mut self, colour: Colour);
+    // filled
+    fn circle(&mut self, centre: (f32, f32), radius: f32, colour: Colour);
+    fn line(&mut self, from: (f32, f32), to: (f32, f32), thickness: f32, colour: Colour);
+    // filled
+    fn rectangle(&mut self, top_left: (f32, f32), bottom_right: (f32, f32), colour: Colour);
+}
+
+// a visualization in whatever state it's in, drawn without a window
+pub trait Scene {
+    fn draw(&mut self, renderer: &mut dyn Renderer);
+}
+
+pub struct Speedy2dRenderer<'a>(pub &'a mut speedy2d::Graphics2D);
+
+fn speedy2d_colour(colour: Colour) -> speedy2d::color::Color {
+    speedy2d::color::Color::from_int_rgb(colour.r, colour.g, colour.b)
+}
+
+impl Renderer for Speedy2dRenderer<'_> {
+    fn clear(&mut self, colour: Colour) {
+        self.0.clear_screen(speedy2d_colour(colour));
+    }
+
+    fn circle(&mut self, centre: (f32, f32), radius: f32, colour: Colour) {
+        self.0.draw_circle(centre, radius, speedy2d_colour(colour));
+    }
+
+    fn line(&mut self, from: (f32, f32), to: (f32, f32), thickness: f32, colour: Colour) {
+        self.0
+            .draw_line(from, to, thickness, speedy2d_colour(colour));
+    }
+
+    fn rectangle(&mut self, top_left: (f32, f32), bottom_right: (f32, f32), colour: Colour) {
+        self.0.draw_rectangle(
+            speedy2d::shape::Rectangle::from_tuples(top_left, bottom_right),
+            speedy2d_colour(colour),
+        );
+    }
+}
+
+pub struct SvgRenderer {
+    width: u32,
+    height: u32,
+    body: String,
+}
+
+impl SvgRenderer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            body: String::new(),
+        }
+    }
+
+    pub fn finish(self) -> String {
+        let (width, height) = (self.width, self.height);
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n{}</svg>\n",
+            self.body
+        )
+    }
+}
+
+fn svg_colour(colour: Colour) -> String {
+    format!("rgb({},{},{})", colour.r, colour.g, colour.b)
+}
+
+// writing into a String can't fail
+impl Renderer for SvgRenderer {
+    fn clear(&mut self, colour: Colour) {
+        // anything drawn before is covered up anyway
+        self.body.clear();
+        let _ = writeln!(
+            self.body,
+            r#"<rect width="{}" height="{}" fill="{}"/>"#,
+            self.width,
+            self.height,
+            svg_colour(colour)
+        );
+    }
+
+    fn circle(&mut self, (x, y): (f32, f32), radius: f32, colour: Colour) {
+        let _ = writeln!(
+            self.body,
+            r#"<circle cx="{x:.1}" cy="{y:.1}" r="{radius:.1}" fill="{}"/>"#,
+            svg_colour(colour)
+        );
+    }
+
+    fn line(&mut self, (x1, y1): (f32, f32), (x2, y2): (f32, f32), thickness: f32, colour: Colour) {
+        let _ = writeln!(
+            self.body,
+            r#"<line x1="{x1:.1}" y1="{y1:.1}" x2="{x2:.1}" y2="{y2:.1}" stroke="{}" stroke-width="{thickness}"/>"#,
+            svg_colour(colour)
+        );
+    }
+
+    fn rectangle(&mut self, (left, top): (f32, f32), (right, bottom): (f32, f32), colour: Colour) {
+        let _ = writeln!(
+            self.body,
+            r#"<rect x="{left:.1}" y="{top:.1}" width="{:.1}" height="{:.1}" fill="{}"/>"#,
+            right - left,
+            bottom - top,
+            svg_colour(colour)
+        );
+    }
+}
+
+pub struct RasterRenderer {
+    pub pixmap: tiny_skia::Pixmap,
+}
+
+impl RasterRenderer {
+    pub fn new(width: u32, height: u32) -> anyhow::Result<Self> {
+        let pixmap = tiny_skia::Pixmap::new(width, height)
+            .with_context(|| format!("can't make a {width}x{height} image"))?;
+        Ok(Self { pixmap })
+    }
+
+    pub fn save_png(&self, path: &Path) -> anyhow::Result<()> {
+        self.pixmap
+            .save_png(path)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+}
+
+fn paint(colour: Colour) -> tiny_skia::Paint<'static> {
+    let mut paint = tiny_skia::Paint::default();
+    paint.set_color_rgba8(colour.r, colour.g, colour.b, 255);
+    paint.anti_alias = true;
+    paint
+}
+
+// shapes that come out empty, like a zero length line, just aren't drawn
+impl Renderer for RasterRenderer {
+    fn clear(&mut self, colour: Colour) {
+        self.pixmap.fill(tiny_skia::Color::from_rgba8(
+            colour.r, colour.g, colour.b, 255,
+        ));
+    }
+
+    fn circle(&mut self, (x, y): (f32, f32), radius: f32, colour: Colour) {
+        if let Some(path) = tiny_skia::PathBuilder::from_circle(x, y, radius) {
+            self.pixmap.fill_path(
+                &path,
+                &paint(colour),
+                tiny_skia::FillRule::Winding,
+                tiny_skia::Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    fn line(&mut self, from: (f32, f32), to: (f32, f32), thickness: f32, colour: Colour) {
+        let mut builder = tiny_skia::PathBuilder::new();
+        builder.move_to(from.0, from.1);
+        builder.line_to(to.0, to.1);
+        if let Some(path) = builder.finish() {
+            let stroke = tiny_skia::Stroke {
+                width: thickness,
+                ..Default::default()
+            };
+            self.pixmap.stroke_path(
+                &path,
+                &paint(colour),
+                &stroke,
+                tiny_skia::Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    fn rectangle(&mut self, (left, top): (f32, f32), (right, bottom): (f32, f32), colour: Colour) {
+        if let Some(rect) = tiny_skia::Rect::from_ltrb(left, top, right, bottom) {
+            self.pixmap
+                .fill_rect(rect, &paint(colour), tiny_skia::Transform::identity(), None);
+        }
+    }
+}
+
+// the size in px of the whole table
+pub fn screen_size() -> (u32, u32) {
+    let (width, height) = table_size();
+    (
+        transform().length_to_screen(width).0.ceil() as u32,
+        transform().length_to_screen(height).0.ceil() as u32,
+    )
+}
+
+// .svg or .png, by the extension of `path`
+pub fn save(scene: &mut dyn Scene, path: &Path) -> anyhow::Result<()> {
+    let (width, height) = screen_size();
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("svg") => {
+            let mut renderer = SvgRenderer::new(width, height);
+            scene.draw(&mut renderer);
+            fs::write(path, renderer.finish())
+                .with_context(|| format!("writing {}", path.display()))
+        }
+        Some("png") => {
+            let mut renderer = RasterRenderer::new(width, height)?;
+            scene.draw(&mut renderer);
+            renderer.save_png(path)
+        }
+        _ => bail!(
+            "can't tell what to render {} as, use .svg or .png",
+            path.display()
+        ),
+    }
+}
+
+pub fn draw_line(renderer: &mut dyn Renderer, line: Line, colour: Colour) {
+    renderer.line(to_screen(line.point1), to_screen(line.point2), 1.0, colour);
+}
+
+// corners as cyan dots, edges in `colour`
+pub fn draw_polygon(renderer: &mut dyn Renderer, polygon: &Polygon, colour: Colour) {
+    for point in polygon.points.iter() {
+        renderer.circle(to_screen(*point), 3.0 * PX_PER_MM, Colour::CYAN);
+    }
+
+    for line in polygon.lines.iter() {
+        draw_line(renderer, *line, colour);
+    }
+}
+
+pub fn draw_receiver(renderer: &mut dyn Renderer, receiver: &Receiver, colour: Colour) {
+    let (x, y) = to_screen(receiver.location);
+    renderer.rectangle(
+        (x - RECEIVER_SIZE, y - RECEIVER_SIZE),
+        (x + RECEIVER_SIZE, y + RECEIVER_SIZE),
+        colour,
+    );
+}
+
+// the edges of what a receiver can see, and optionally of where it can see any part of the base
+pub fn draw_wedge(
+    renderer: &mut dyn Renderer,
+    receiver: &Receiver,
+    colour: Colour,
+    expanded: Option<Colour>,
+) {
+    draw_line(renderer, receiver.view_bound1, colour);
+    draw_line(renderer, receiver.view_bound2, colour);
+
+    if let Some(expanded) = expanded {
+        draw_line(renderer, receiver.expanded_view_bound1, expanded);
+        draw_line(renderer, receiver.expanded_view_bound2, expanded);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn draw_shapes(renderer: &mut dyn Renderer) {
+        renderer.clear(Colour::WHITE);
+        renderer.rectangle((10.0, 10.0), (20.0, 30.0), Colour::BLUE);
+        renderer.circle((50.0, 20.0), 5.0, Colour::RED);
+        renderer.line((0.0, 35.0), (60.0, 35.0), 2.0, Colour::BLACK);
+    }
+
+    #[test]
+    fn svg_shapes() {
+        let mut renderer = SvgRenderer::new(60, 40);
+        renderer.circle((1.0, 1.0), 1.0, Colour::GREEN);
+        draw_shapes(&mut renderer);
+        let svg = renderer.finish();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        // the circle before the clear is gone
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert!(svg.contains(
+            r#"<rect x="10.0" y="10.0" width="10.0" height="20.0" fill="rgb(0,0,255)"/>"#
+        ));
+        assert!(svg.contains(r#"stroke-width="2""#));
+    }
+
+    #[test]
+    fn raster_shapes() {
+        let mut renderer = RasterRenderer::new(60, 40).unwrap();
+        draw_shapes(&mut renderer);
+        let pixel = |x, y| {
+            let p = renderer.pixmap.pixel(x, y).unwrap();
+            Colour::rgb(p.red(), p.green(), p.blue())
+        };
+
+        assert_eq!(pixel(15, 20), Colour::BLUE);
+        assert_eq!(pixel(50, 20), Colour::RED);
+        assert_eq!(pixel(30, 35), Colour::BLACK);
+        assert_eq!(pixel(40, 5), Colour::WHITE);
+    }
+
+    #[test]
+    fn scenes_render_headless() {
+        let (width, height) = table_size();
+        let footprint = BASE_DIAMETER;
+        let mut receivers =
+            place_horizontal_receivers(width, height, Degrees(30.0), 1.0, footprint);
+        receivers.append(&mut place_vertical_receivers(
+            width,
+            height,
+            Degrees(30.0),
+            1.0,
+            footprint,
+        ));
+        let table = Table::new(width, height, receivers);
+        let mini_location = Point {
+            x: width.0 / 3.0,
+            y: height.0 / 3.0,
+        };
+
+        let mut scene = vis_bounding_box::handler(table.clone(), mini_location, footprint);
+        let mut renderer = SvgRenderer::new(10, 10);
+        scene.draw(&mut renderer);
+        let svg = renderer.finish();
+        // a background, then one square per receiver
+        assert_eq!(svg.matches("<rect").count(), 1 + table.receivers.len());
+        assert!(svg.contains("rgb(0,255,255)"));
+
+        let (width, height) = screen_size();
+        let mut renderer = RasterRenderer::new(width, height).unwrap();
+        vis_iterating_solver::handler(table, mini_location, footprint).draw(&mut renderer);
+        let colours: Vec<Colour> = renderer
+            .pixmap
+            .pixels()
+            .iter()
+            .map(|p| Colour::rgb(p.red(), p.green(), p.blue()))
+            .collect();
+        // the mini, the receivers that see it and the corners of the solution
+        for colour in [Colour::RED, Colour::BLUE, Colour::CYAN] {
+            assert!(colours.contains(&colour));
+        }
+    }
+}
@@ -1,7 +1,11 @@
 use super::*;
-use mini_tracker::{self, Point, Polygon, Receiver, Table};
+use mini_tracker::{self, Point, Table};
 use speedy2d::window::WindowHandler;
 
+use crate::interactive::Interaction;
+use crate::modes::Visualization;
+use crate::render::{draw_polygon, Colour, Renderer, Scene, Speedy2dRenderer};
+
 #[derive(Clone, Copy, Debug)]
 enum ViewMode {
     Initial,
@@ -15,52 +19,81 @@ const VIEW_MODES: [ViewMode; 3] = [
     ViewMode::CenterOnly,
 ];
 
-fn draw_polygon(graphics: &mut speedy2d::Graphics2D, polygon: &Polygon) {
-    for point in polygon.points.iter() {
-        let (x, y) = (convert_x(point.x), convert_y(point.y));
-        graphics.draw_circle((x, y), 3.0 * PX_PER_MM, speedy2d::color::Color::CYAN);
-    }
-
-    for line in polygon.lines.iter() {
-        let (x1, y1) = (convert_x(line.point1.x), convert_y(line.point1.y));
-        let (x2, y2) = (convert_x(line.point2.x), convert_y(line.point2.y));
-        graphics.draw_line((x1, y1), (x2, y2), 1.0, speedy2d::color::Color::LIGHT_GRAY);
-    }
-}
-
 struct Visualizer {
-    table: Table,
-    mini_location: Point,
-    bounding_polygon: Polygon,
-    shrink_lines: Polygon,
-    bounding_polygon_centered: Polygon,
+    interaction: Interaction,
     cur_view_mode: usize,
 }
 
+impl Scene for Visualizer {
+    fn draw(&mut self, renderer: &mut dyn Renderer) {
+        renderer.clear(Colour::WHITE);
+
+        let visible_receivers = self.interaction.visible_receivers();
+        self.interaction.draw_mini(renderer);
+        self.interaction
+            .draw_receivers(renderer, &visible_receivers);
+
+        let radius = self.interaction.footprint / 2.0;
+        let bounding_polygon = self
+            .interaction
+            .table
+            .get_bounding_polygon(&visible_receivers);
+        let bounding_polygon_centered = bounding_polygon.as_ref().and_then(|p| p.shrink(radius));
+
+        if let Some(bounding_polygon) = &bounding_polygon {
+            let view_mode = VIEW_MODES[self.cur_view_mode];
+            match view_mode {
+                ViewMode::Initial => draw_polygon(renderer, bounding_polygon, Colour::LIGHT_GRAY),
+                ViewMode::ShrinkLines => draw_polygon(
+                    renderer,
+                    &bounding_polygon.get_shrink_lines(radius),
+                    Colour::LIGHT_GRAY,
+                ),
+                ViewMode::CenterOnly => {
+                    if let Some(centered) = &bounding_polygon_centered {
+                        draw_polygon(renderer, centered, Colour::LIGHT_GRAY);
+                    }
+                }
+            }
+        }
+
+        let estimate = bounding_polygon_centered.map(|p| p.center());
+        self.interaction.draw_estimate(renderer, estimate);
+    }
+}
+
 impl WindowHandler for Visualizer {
     fn on_draw(
         &mut self,
-        _helper: &mut speedy2d::window::WindowHelper<()>,
+        helper: &mut speedy2d::window::WindowHelper<()>,
         graphics: &mut speedy2d::Graphics2D,
     ) {
-        graphics.clear_screen(speedy2d::color::Color::WHITE);
-
-        let (x, y) = (self.mini_location.x, self.mini_location.y);
-        let (x, y) = (convert_x(x), convert_y(y));
-        graphics.draw_circle(
-            (x, y),
-            (MM_PER_INCH * PX_PER_MM) / 2.0,
-            speedy2d::color::Color::RED,
-        );
-
-        let view_mode = VIEW_MODES[self.cur_view_mode];
-        match view_mode {
-            ViewMode::Initial => draw_polygon(graphics, &self.bounding_polygon),
-            ViewMode::ShrinkLines => draw_polygon(graphics, &self.shrink_lines),
-            ViewMode::CenterOnly => draw_polygon(graphics, &self.bounding_polygon_centered),
-        }
+        self.draw(&mut Speedy2dRenderer(graphics));
+        helper.set_title(self.interaction.title());
+    }
 
-        graphics.draw_circle((x, y), 1.0 * PX_PER_MM, speedy2d::color::Color::BLACK);
+    fn on_mouse_move(
+        &mut self,
+        helper: &mut speedy2d::window::WindowHelper<()>,
+        position: speedy2d::dimen::Vec2,
+    ) {
+        self.interaction.on_mouse_move(helper, position);
+    }
+
+    fn on_mouse_button_down(
+        &mut self,
+        helper: &mut speedy2d::window::WindowHelper<()>,
+        button: speedy2d::window::MouseButton,
+    ) {
+        self.interaction.on_mouse_button_down(helper, button);
+    }
+
+    fn on_mouse_button_up(
+        &mut self,
+        helper: &mut speedy2d::window::WindowHelper<()>,
+        button: speedy2d::window::MouseButton,
+    ) {
+        self.interaction.on_mouse_button_up(helper, button);
     }
 
     fn on_key_up(
@@ -79,51 +112,19 @@ impl WindowHandler for Visualizer {
             Some(speedy2d::window::VirtualKeyCode::Escape) => {
                 std::process::exit(0);
             }
-            _ => (),
+            Some(key) => {
+                self.interaction.on_key_up(key);
+            }
+            None => (),
         }
 
         helper.request_redraw();
     }
 }
 
-pub fn run(table: Table, mini_location: Point, visible_receivers: Vec<(Receiver, bool)>) {
-    let bounding_polygon = table.get_bounding_polygon(&&visible_receivers[..]).unwrap();
-    let shrink_lines = bounding_polygon.get_shrink_lines(25.4 / 2.0);
-    let bounding_polygon_centered = bounding_polygon.shrink(25.4 / 2.0).unwrap();
-    dbg!(&bounding_polygon_centered.points);
-    dbg!(bounding_polygon.points.len());
-    dbg!(bounding_polygon_centered.points.len());
-
-    println!(
-        "possible position area: {}",
-        bounding_polygon_centered.area()
-    );
-
-    println!(
-        "max possible error: {}",
-        bounding_polygon_centered.max_width()
-    );
-
-    // println!(
-    //     "estimated position: {:?}",
-    //     bounding_polygon_centered.center()
-    // );
-
-    let window = speedy2d::Window::new_centered(
-        "Mini Tracker Visualizer",
-        (
-            (TABLE_WIDTH * PX_PER_MM) as u32,
-            (TABLE_HEIGHT * PX_PER_MM) as u32,
-        ),
-    )
-    .unwrap();
-
-    window.run_loop(Visualizer {
-        table,
-        mini_location,
-        bounding_polygon, //: bounding_polygon.clone(),
-        shrink_lines,
-        bounding_polygon_centered, //: bounding_polygon,
+pub fn handler(table: Table, mini_location: Point, footprint: Mm) -> Box<dyn Visualization> {
+    Box::new(Visualizer {
+        interaction: Interaction::new(table, mini_location, footprint),
         cur_view_mode: 0,
-    });
+    })
 }
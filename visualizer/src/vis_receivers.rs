@@ -1,11 +1,15 @@
 use super::*;
 use float_cmp::ApproxEq;
 use mini_tracker::{self, Point, Receiver, Table};
-use speedy2d::{shape::Rectangle, window::WindowHandler};
+use speedy2d::window::WindowHandler;
+
+use crate::modes::Visualization;
+use crate::render::{draw_receiver, draw_wedge, Colour, Renderer, Scene, Speedy2dRenderer};
 
 struct Visualizer {
     table: Table,
     mini_location: Point,
+    footprint: Mm,
     visible_receivers: Vec<(Receiver, bool)>,
     active_receiver_idx: Option<usize>,
     positive_intersect_checkpoints: Vec<Vec<Point>>,
@@ -14,118 +18,61 @@ struct Visualizer {
     showing_negative: bool,
 }
 
-impl WindowHandler for Visualizer {
-    fn on_draw(
-        &mut self,
-        _helper: &mut speedy2d::window::WindowHelper<()>,
-        graphics: &mut speedy2d::Graphics2D,
-    ) {
-        graphics.clear_screen(speedy2d::color::Color::WHITE);
+impl Scene for Visualizer {
+    fn draw(&mut self, renderer: &mut dyn Renderer) {
+        renderer.clear(Colour::WHITE);
 
-        let (x, y) = (self.mini_location.x, self.mini_location.y);
-        let (x, y) = (convert_x(x), convert_y(y));
-        graphics.draw_circle(
-            (x, y),
-            (MM_PER_INCH * PX_PER_MM) / 2.0,
-            speedy2d::color::Color::RED,
+        renderer.circle(
+            to_screen(self.mini_location),
+            transform().length_to_screen(self.footprint / 2.0).0,
+            Colour::RED,
         );
 
         if self.intersects_visible {
-            if !self.showing_negative {
+            let points = if !self.showing_negative {
                 let idx = if let Some(idx) = self.active_receiver_idx {
                     idx + 1
                 } else {
                     0
                 };
-                for point in self.positive_intersect_checkpoints[idx].iter() {
-                    let (x, y) = (point.x, point.y);
-                    let (x, y) = (convert_x(x), convert_y(y));
-                    graphics.draw_circle((x, y), 3.0 * PX_PER_MM, speedy2d::color::Color::CYAN);
-                }
+                &self.positive_intersect_checkpoints[idx]
             } else {
                 let idx = self.active_receiver_idx.unwrap();
-                for point in self.negative_intersect_checkpoints[idx].iter() {
-                    let (x, y) = (point.x, point.y);
-                    let (x, y) = (convert_x(x), convert_y(y));
-                    graphics.draw_circle((x, y), 3.0 * PX_PER_MM, speedy2d::color::Color::CYAN);
-                }
+                &self.negative_intersect_checkpoints[idx]
+            };
+            for point in points.iter() {
+                renderer.circle(to_screen(*point), 3.0 * PX_PER_MM, Colour::CYAN);
             }
         }
 
-        let mut active_receiver;
         for (idx, receiver) in self.table.receivers.iter().enumerate() {
-            let draw_color = if let Some(active_idx) = self.active_receiver_idx {
-                if idx == active_idx {
-                    active_receiver = true;
-                    speedy2d::color::Color::GREEN
-                } else {
-                    active_receiver = false;
-                    speedy2d::color::Color::BLUE
-                }
+            let active_receiver = self.active_receiver_idx == Some(idx);
+            let colour = if active_receiver {
+                Colour::GREEN
             } else {
-                active_receiver = false;
-                speedy2d::color::Color::BLUE
+                Colour::BLUE
             };
-
-            let (x, y) = (receiver.location.x, receiver.location.y);
-            let (x, y) = (convert_x(x), convert_y(y));
-
-            graphics.draw_rectangle(
-                Rectangle::from_tuples(
-                    (x - RECEIVER_SIZE, y - RECEIVER_SIZE),
-                    (x + RECEIVER_SIZE, y + RECEIVER_SIZE),
-                ),
-                draw_color,
-            );
+            draw_receiver(renderer, receiver, colour);
 
             if active_receiver {
-                let color = if self.showing_negative {
-                    speedy2d::color::Color::RED
+                if self.showing_negative {
+                    draw_wedge(renderer, receiver, Colour::RED, None);
                 } else {
-                    speedy2d::color::Color::BLACK
-                };
-
-                // draw view_bounds
-
-                // line 1
-                let line = receiver.view_bound1;
-                let (x1, y1) = (line.point1.x, line.point1.y);
-                let (x1, y1) = (convert_x(x1), convert_y(y1));
-
-                let (x2, y2) = (line.point2.x, line.point2.y);
-                let (x2, y2) = (convert_x(x2), convert_y(y2));
-                graphics.draw_line((x1, y1), (x2, y2), 1.0, color);
-
-                if !self.showing_negative {
-                    let line = receiver.expanded_view_bound1;
-                    let (x1, y1) = (line.point1.x, line.point1.y);
-                    let (x1, y1) = (convert_x(x1), convert_y(y1));
-
-                    let (x2, y2) = (line.point2.x, line.point2.y);
-                    let (x2, y2) = (convert_x(x2), convert_y(y2));
-                    graphics.draw_line((x1, y1), (x2, y2), 1.0, speedy2d::color::Color::BLUE);
-                }
-
-                // line 2
-                let line = receiver.view_bound2;
-                let (x1, y1) = (line.point1.x, line.point1.y);
-                let (x1, y1) = (convert_x(x1), convert_y(y1));
-                let (x2, y2) = (line.point2.x, line.point2.y);
-                let (x2, y2) = (convert_x(x2), convert_y(y2));
-                graphics.draw_line((x1, y1), (x2, y2), 1.0, color);
-
-                if !self.showing_negative {
-                    let line = receiver.expanded_view_bound2;
-                    let (x1, y1) = (line.point1.x, line.point1.y);
-                    let (x1, y1) = (convert_x(x1), convert_y(y1));
-
-                    let (x2, y2) = (line.point2.x, line.point2.y);
-                    let (x2, y2) = (convert_x(x2), convert_y(y2));
-                    graphics.draw_line((x1, y1), (x2, y2), 1.0, speedy2d::color::Color::BLUE);
+                    draw_wedge(renderer, receiver, Colour::BLACK, Some(Colour::BLUE));
                 }
             }
         }
     }
+}
+
+impl WindowHandler for Visualizer {
+    fn on_draw(
+        &mut self,
+        _helper: &mut speedy2d::window::WindowHelper<()>,
+        graphics: &mut speedy2d::Graphics2D,
+    ) {
+        self.draw(&mut Speedy2dRenderer(graphics));
+    }
 
     fn on_key_up(
         &mut self,
@@ -298,27 +245,20 @@ fn get_point_checkpoints(
     (positive_checkpoints, negative_checkpoints)
 }
 
-pub fn run(table: Table, mini_location: Point, visible_receivers: Vec<(Receiver, bool)>) {
+pub fn handler(table: Table, mini_location: Point, footprint: Mm) -> Box<dyn Visualization> {
+    let visible_receivers = visible_receivers(&table, mini_location, footprint);
     let (positive_intersect_checkpoints, negative_intersect_checkpoints) =
         get_point_checkpoints(&table, &visible_receivers);
 
-    let window = speedy2d::Window::new_centered(
-        "Mini Tracker Visualizer",
-        (
-            (TABLE_WIDTH * PX_PER_MM) as u32,
-            (TABLE_HEIGHT * PX_PER_MM) as u32,
-        ),
-    )
-    .unwrap();
-
-    window.run_loop(Visualizer {
+    Box::new(Visualizer {
         table,
         mini_location,
+        footprint,
         visible_receivers,
         active_receiver_idx: None,
         positive_intersect_checkpoints,
         negative_intersect_checkpoints,
         intersects_visible: true,
         showing_negative: false,
-    });
+    })
 }
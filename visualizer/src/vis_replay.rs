@@ -0,0 +1,299 @@
+// Plays back a recording one solve at a time: the receivers that saw the mini, the region the solver left and its
+// estimate, with where the mini really was if the recording knows. Left and Right step a frame, Page Up and Page Down
+// ten, Home and End jump to either end, and dragging along the bar at the bottom scrubs through the whole session.
+// M moves on to the next mini when there's more than one.
+
+use super::*;
+use mini_tracker::recording::{RecordedMini, Recording};
+use mini_tracker::Table;
+use speedy2d::dimen::Vec2;
+use speedy2d::window::{KeyScancode, MouseButton, VirtualKeyCode, WindowHandler, WindowHelper};
+
+use crate::modes::Visualization;
+use crate::render::{
+    draw_polygon, draw_receiver, screen_size, Colour, Renderer, Scene, Speedy2dRenderer,
+};
+
+const TIMELINE_HEIGHT: f32 = 4.0 * PX_PER_MM;
+const PAGE: usize = 10;
+
+struct Replay {
+    recording: Recording,
+    table: Table,
+    frame: usize,
+    mini: usize,
+    mouse: (f32, f32),
+    scrubbing: bool,
+}
+
+impl Replay {
+    fn last_frame(&self) -> usize {
+        self.recording.frames.len().saturating_sub(1)
+    }
+
+    fn step(&mut self, frames: isize) {
+        self.frame = self
+            .frame
+            .saturating_add_signed(frames)
+            .min(self.last_frame());
+    }
+
+    fn current(&self) -> Option<&RecordedMini> {
+        let minis = &self.recording.frames.get(self.frame)?.minis;
+        minis.get(self.mini % minis.len().max(1))
+    }
+
+    // the frame under `x` on the timeline
+    fn scrub_to(&mut self, x: f32) {
+        let width = screen_size().0 as f32;
+        let fraction = (x / width).clamp(0.0, 1.0);
+        self.frame = (fraction * self.last_frame() as f32).round() as usize;
+    }
+
+    fn draw_timeline(&self, renderer: &mut dyn Renderer) {
+        let (width, height) = screen_size();
+        let (width, height) = (width as f32, height as f32);
+        renderer.rectangle(
+            (0.0, height - TIMELINE_HEIGHT),
+            (width, height),
+            Colour::LIGHT_GRAY,
+        );
+
+        let position = if self.last_frame() == 0 {
+            0.0
+        } else {
+            self.frame as f32 / self.last_frame() as f32 * width
+        };
+        renderer.rectangle(
+            (position - 1.0 * PX_PER_MM, height - TIMELINE_HEIGHT),
+            (position + 1.0 * PX_PER_MM, height),
+            Colour::BLACK,
+        );
+    }
+
+    fn title(&self) -> String {
+        let frames = self.recording.frames.len();
+        let Some(frame) = self.recording.frames.get(self.frame) else {
+            return "empty recording".to_string();
+        };
+        let position = format!("frame {}/{frames} at {:.2}s", self.frame + 1, frame.time);
+        let Some(mini) = self.current() else {
+            return format!("{position}, no minis");
+        };
+
+        let mut title = format!("{position}, {} seen by {}", mini.name, mini.seen.len());
+        match mini.estimate {
+            Some(estimate) => {
+                title += &format!(", estimated ({:.1}, {:.1})", estimate.x, estimate.y);
+                if let Some(actual) = mini.actual {
+                    title += &format!(", error {:.2}mm", actual.distance(&estimate));
+                }
+            }
+            None => title += ", no solution",
+        }
+        title
+    }
+}
+
+impl Scene for Replay {
+    fn draw(&mut self, renderer: &mut dyn Renderer) {
+        renderer.clear(Colour::WHITE);
+
+        let radius = transform()
+            .length_to_screen(self.recording.footprint / 2.0)
+            .0;
+        let current = self.current().cloned();
+
+        // the other minis in this frame, greyed out
+        if let Some(frame) = self.recording.frames.get(self.frame) {
+            for mini in &frame.minis {
+                if Some(&mini.name) == current.as_ref().map(|m| &m.name) {
+                    continue;
+                }
+                if let Some(actual) = mini.actual {
+                    renderer.circle(to_screen(actual), radius, Colour::LIGHT_GRAY);
+                }
+            }
+        }
+
+        match &current {
+            Some(mini) => {
+                if let Some(actual) = mini.actual {
+                    renderer.circle(to_screen(actual), radius, Colour::RED);
+                }
+                for (receiver, can_see) in mini.observations(&self.table) {
+                    let colour = if can_see { Colour::BLUE } else { Colour::RED };
+                    draw_receiver(renderer, &receiver, colour);
+                }
+                if let Some(region) = mini.region() {
+                    draw_polygon(renderer, &region, Colour::BLACK);
+                }
+                if let Some(estimate) = mini.estimate {
+                    if let Some(actual) = mini.actual {
+                        renderer.line(to_screen(actual), to_screen(estimate), 1.0, Colour::MAGENTA);
+                    }
+                    renderer.circle(to_screen(estimate), 1.5 * PX_PER_MM, Colour::GREEN);
+                }
+            }
+            None => {
+                for receiver in &self.table.receivers {
+                    draw_receiver(renderer, receiver, Colour::LIGHT_GRAY);
+                }
+            }
+        }
+
+        self.draw_timeline(renderer);
+    }
+}
+
+impl WindowHandler for Replay {
+    fn on_draw(&mut self, helper: &mut WindowHelper<()>, graphics: &mut speedy2d::Graphics2D) {
+        self.draw(&mut Speedy2dRenderer(graphics));
+        helper.set_title(self.title());
+    }
+
+    fn on_mouse_move(&mut self, helper: &mut WindowHelper<()>, position: Vec2) {
+        self.mouse = (position.x, position.y);
+        if self.scrubbing {
+            self.scrub_to(position.x);
+            helper.request_redraw();
+        }
+    }
+
+    fn on_mouse_button_down(&mut self, helper: &mut WindowHelper<()>, button: MouseButton) {
+        let height = screen_size().1 as f32;
+        if button == MouseButton::Left && self.mouse.1 >= height - TIMELINE_HEIGHT {
+            self.scrubbing = true;
+            self.scrub_to(self.mouse.0);
+            helper.request_redraw();
+        }
+    }
+
+    fn on_mouse_button_up(&mut self, _helper: &mut WindowHelper<()>, button: MouseButton) {
+        if button == MouseButton::Left {
+            self.scrubbing = false;
+        }
+    }
+
+    fn on_key_up(
+        &mut self,
+        helper: &mut WindowHelper<()>,
+        virtual_key_code: Option<VirtualKeyCode>,
+        _scancode: KeyScancode,
+    ) {
+        match virtual_key_code {
+            Some(VirtualKeyCode::Left) => self.step(-1),
+            Some(VirtualKeyCode::Right) => self.step(1),
+            Some(VirtualKeyCode::PageUp) => self.step(-(PAGE as isize)),
+            Some(VirtualKeyCode::PageDown) => self.step(PAGE as isize),
+            Some(VirtualKeyCode::Home) => self.frame = 0,
+            Some(VirtualKeyCode::End) => self.frame = self.last_frame(),
+            Some(VirtualKeyCode::M) => self.mini += 1,
+            Some(VirtualKeyCode::Escape) => std::process::exit(0),
+            _ => (),
+        }
+
+        helper.request_redraw();
+    }
+}
+
+pub fn handler(recording: Recording, frame: usize) -> Box<dyn Visualization> {
+    let table = recording.to_table();
+    let mut replay = Replay {
+        recording,
+        table,
+        frame,
+        mini: 0,
+        mouse: (0.0, 0.0),
+        scrubbing: false,
+    };
+    replay.step(0);
+    Box::new(replay)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::SvgRenderer;
+    use mini_tracker::recording::RecordedFrame;
+
+    fn recording(frames: usize) -> Recording {
+        let (width, height) = table_size();
+        let table = Table::new(
+            width,
+            height,
+            place_horizontal_receivers(width, height, Degrees(30.0), 1.0, BASE_DIAMETER),
+        );
+        let mut recording = Recording::new(&table, BASE_DIAMETER);
+        for i in 0..frames {
+            let actual = Point {
+                x: 100.0 + i as f32,
+                y: 100.0,
+            };
+            let observed = visible_receivers(&table, actual, BASE_DIAMETER);
+            let region = table.get_bounding_polygon(&observed);
+            let mut mini = RecordedMini::new(
+                "knight",
+                &observed,
+                region.as_ref(),
+                region.as_ref().map(|r| r.center()),
+            );
+            mini.actual = Some(actual);
+            recording.frames.push(RecordedFrame {
+                time: i as f32 * 0.1,
+                minis: vec![mini],
+            });
+        }
+        recording
+    }
+
+    #[test]
+    fn stepping() {
+        let mut replay = Replay {
+            recording: recording(25),
+            table: Table::new(Mm(1.0), Mm(1.0), Vec::new()),
+            frame: 0,
+            mini: 0,
+            mouse: (0.0, 0.0),
+            scrubbing: false,
+        };
+
+        replay.step(-1);
+        assert_eq!(replay.frame, 0);
+        replay.step(PAGE as isize);
+        replay.step(PAGE as isize);
+        replay.step(PAGE as isize);
+        assert_eq!(replay.frame, 24);
+
+        replay.scrub_to(screen_size().0 as f32 / 2.0);
+        assert_eq!(replay.frame, 12);
+        replay.scrub_to(-5.0);
+        assert_eq!(replay.frame, 0);
+
+        // only one mini to move on to
+        replay.mini += 1;
+        assert_eq!(replay.current().unwrap().name, "knight");
+        assert!(replay.title().starts_with("frame 1/25 at 0.00s, knight"));
+    }
+
+    #[test]
+    fn draws_recorded_frames() {
+        let recording = recording(3);
+        let receivers = recording.layout.receivers.len();
+
+        let mut scene = handler(recording, 99);
+        let mut renderer = SvgRenderer::new(10, 10);
+        scene.draw(&mut renderer);
+        let svg = renderer.finish();
+        // a background, one square per receiver and the timeline with its marker
+        assert_eq!(svg.matches("<rect").count(), 1 + receivers + 2);
+        assert!(svg.contains("rgb(0,0,255)"));
+        assert!(svg.contains("rgb(0,255,0)"));
+
+        let mut empty = handler(
+            Recording::new(&Table::new(Mm(1.0), Mm(1.0), Vec::new()), BASE_DIAMETER),
+            0,
+        );
+        empty.draw(&mut SvgRenderer::new(10, 10));
+    }
+}